@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use crate::cve::ProbeRule;
+use crate::geo::GeoDb;
+use crate::useragent::{is_bot, search_bot};
+use crate::NginxLog;
+
+const SCANNER_PATHS: [&str; 12] = [
+    "/wp-login.php",
+    "/xmlrpc.php",
+    "/wp-admin",
+    "/.env",
+    "/.git/",
+    "/phpmyadmin",
+    "/pma",
+    "/.aws/",
+    "/config.php",
+    "/.ds_store",
+    "/server-status",
+    "/actuator",
+];
+const TOOL_AGENTS: [&str; 10] = ["python-requests", "curl/", "wget/", "go-http-client", "zgrab", "masscan", "nmap", "nikto", "sqlmap", "libwww-perl"];
+const ERROR_WEIGHT: f64 = 25.0;
+const RATE_WEIGHT: f64 = 25.0;
+const RATE_CEILING: f64 = 120.0;
+const BOT_WEIGHT: f64 = 10.0;
+const SCANNER_WEIGHT: f64 = 30.0;
+const SCANNER_CEILING: f64 = 5.0;
+const GEO_WEIGHT: f64 = 10.0;
+
+#[derive(Default)]
+struct Activity {
+    requests: u64,
+    errors: u64,
+    minutes: HashMap<i64, u64>,
+    bot: bool,
+    probes: u64,
+}
+
+pub struct AbuseScore {
+    pub ip: String,
+    pub requests: u64,
+    pub score: f64,
+    pub factors: Vec<(f64, String)>,
+}
+
+pub fn abuse_scores(logs: &[NginxLog], rules: &[ProbeRule], geo: Option<&GeoDb>) -> Vec<AbuseScore> {
+    let mut activity: HashMap<String, Activity> = HashMap::new();
+    for log in logs {
+        let entry = activity.entry(log.client()).or_default();
+        entry.requests += 1;
+        if log.status >= 400 {
+            entry.errors += 1;
+        }
+        *entry.minutes.entry(log.request_time.timestamp().div_euclid(60)).or_default() += 1;
+        let user_agent = log.http_user_agent.trim_matches('"');
+        let lower = user_agent.to_ascii_lowercase();
+        if (is_bot(user_agent) && search_bot(user_agent).is_none()) || TOOL_AGENTS.iter().any(|tool| lower.contains(tool)) || matches!(user_agent, "" | "-") {
+            entry.bot = true;
+        }
+        let path = log.path().unwrap_or("").to_ascii_lowercase();
+        if SCANNER_PATHS.iter().any(|scanner| path.starts_with(scanner)) || rules.iter().any(|rule| rule.matches(log)) {
+            entry.probes += 1;
+        }
+    }
+    let countries: HashMap<&String, Option<String>> = match geo {
+        Some(db) => activity.keys().map(|ip| (ip, db.lookup(ip).and_then(|location| location.country))).collect(),
+        None => HashMap::new(),
+    };
+    let mut per_country: HashMap<&str, u64> = HashMap::new();
+    for country in countries.values().flatten() {
+        *per_country.entry(country).or_default() += 1;
+    }
+    let clients = activity.len().max(1) as f64;
+    let mut scores: Vec<AbuseScore> = activity
+        .iter()
+        .map(|(ip, activity)| {
+            let mut factors = Vec::new();
+            if activity.errors > 0 {
+                let ratio = activity.errors as f64 / activity.requests as f64;
+                let confidence = (activity.requests as f64 / 10.0).min(1.0);
+                factors.push((ratio * confidence * ERROR_WEIGHT, format!("{:.0}% errors", ratio * 100.0)));
+            }
+            let peak = activity.minutes.values().copied().max().unwrap_or(0);
+            if peak > 1 {
+                factors.push(((peak as f64 / RATE_CEILING).min(1.0) * RATE_WEIGHT, format!("{}/min peak", peak)));
+            }
+            if activity.bot {
+                factors.push((BOT_WEIGHT, String::from("bot or tool user agent")));
+            }
+            if activity.probes > 0 {
+                factors.push(((activity.probes as f64 / SCANNER_CEILING).min(1.0) * SCANNER_WEIGHT, format!("{} scanner probes", activity.probes)));
+            }
+            if let Some(Some(country)) = countries.get(ip) {
+                let share = per_country[country.as_str()] as f64 / clients;
+                factors.push(((1.0 - share) * GEO_WEIGHT, format!("{} ({:.1}% of clients)", country, share * 100.0)));
+            }
+            factors.retain(|(points, _)| *points >= 0.05);
+            factors.sort_by(|a, b| b.0.total_cmp(&a.0));
+            AbuseScore { ip: ip.clone(), requests: activity.requests, score: factors.iter().map(|(points, _)| points).sum(), factors }
+        })
+        .collect();
+    scores.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| b.requests.cmp(&a.requests)).then_with(|| a.ip.cmp(&b.ip)));
+    scores
+}
@@ -0,0 +1,268 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use tempfile::TempDir;
+
+use crate::NginxLog;
+
+const ENTRY_OVERHEAD: usize = 64;
+const MAX_FAN_IN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupField {
+    Path,
+    Ip,
+    Referer,
+    UserAgent,
+    Status,
+    Method,
+    Column(String),
+}
+
+impl GroupField {
+    pub fn parse(name: &str) -> Result<GroupField, String> {
+        match name {
+            "path" => Ok(GroupField::Path),
+            "ip" => Ok(GroupField::Ip),
+            "referer" => Ok(GroupField::Referer),
+            "user-agent" | "user_agent" => Ok(GroupField::UserAgent),
+            "status" => Ok(GroupField::Status),
+            "method" => Ok(GroupField::Method),
+            "" => Err(String::from("empty group-by field")),
+            _ => Ok(GroupField::Column(name.to_string())),
+        }
+    }
+
+    pub fn key(&self, log: &NginxLog) -> String {
+        match self {
+            GroupField::Path => log.path().unwrap_or("-").to_string(),
+            GroupField::Ip => log.client(),
+            GroupField::Referer => log.http_referer.trim_matches('"').to_string(),
+            GroupField::UserAgent => log.http_user_agent.trim_matches('"').to_string(),
+            GroupField::Status => log.status.to_string(),
+            GroupField::Method => log.method().unwrap_or("-").to_string(),
+            GroupField::Column(name) => log.field(name).unwrap_or_else(|| String::from("-")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+pub struct SpillingCounter {
+    counts: HashMap<String, Totals>,
+    used: usize,
+    max_memory: Option<usize>,
+    spill_dir: Option<TempDir>,
+    runs: Vec<PathBuf>,
+    spilled: usize,
+}
+
+impl SpillingCounter {
+    pub fn new(max_memory: Option<usize>) -> SpillingCounter {
+        SpillingCounter { counts: HashMap::new(), used: 0, max_memory, spill_dir: None, runs: Vec::new(), spilled: 0 }
+    }
+
+    pub fn add(&mut self, key: String, bytes: u64) -> io::Result<()> {
+        let len = key.len();
+        let totals = self.counts.entry(key).or_default();
+        if totals.requests == 0 {
+            self.used += len + ENTRY_OVERHEAD;
+        }
+        totals.requests += 1;
+        totals.bytes += bytes;
+        if self.max_memory.is_some_and(|max_memory| self.used > max_memory) {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    pub fn spilled_runs(&self) -> usize {
+        self.spilled
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let mut entries: Vec<(String, Totals)> = self.counts.drain().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let path = self.next_run_path()?;
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for (key, totals) in entries {
+            write_entry(&mut writer, &key, totals)?;
+        }
+        writer.flush()?;
+        self.runs.push(path);
+        self.used = 0;
+        if self.runs.len() >= MAX_FAN_IN {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn compact(&mut self) -> io::Result<()> {
+        let runs: Vec<PathBuf> = self.runs.drain(..).collect();
+        let path = self.next_run_path()?;
+        let mut writer = BufWriter::new(File::create(&path)?);
+        merge_runs(&runs, |key, totals| write_entry(&mut writer, &key, totals))?;
+        writer.flush()?;
+        for run in runs {
+            fs::remove_file(run)?;
+        }
+        self.runs.push(path);
+        Ok(())
+    }
+
+    fn next_run_path(&mut self) -> io::Result<PathBuf> {
+        if self.spill_dir.is_none() {
+            self.spill_dir = Some(tempfile::tempdir()?);
+        }
+        self.spilled += 1;
+        Ok(self.spill_dir.as_ref().unwrap().path().join(format!("run-{}", self.spilled)))
+    }
+
+    pub fn top(mut self, limit: usize) -> Result<Vec<(String, Totals)>, Box<dyn Error>> {
+        if self.runs.is_empty() {
+            let mut entries: Vec<(String, Totals)> = self.counts.into_iter().collect();
+            entries.sort_by(|a, b| b.1.requests.cmp(&a.1.requests).then_with(|| a.0.cmp(&b.0)));
+            entries.truncate(limit);
+            return Ok(entries);
+        }
+        if !self.counts.is_empty() {
+            self.spill()?;
+        }
+        let mut best: BinaryHeap<Reverse<(u64, Reverse<String>, u64)>> = BinaryHeap::new();
+        merge_runs(&self.runs, |key, totals| {
+            best.push(Reverse((totals.requests, Reverse(key), totals.bytes)));
+            if best.len() > limit {
+                best.pop();
+            }
+            Ok(())
+        })?;
+        let mut entries: Vec<(String, Totals)> = best
+            .into_iter()
+            .map(|Reverse((requests, Reverse(key), bytes))| (key, Totals { requests, bytes }))
+            .collect();
+        entries.sort_by(|a, b| b.1.requests.cmp(&a.1.requests).then_with(|| a.0.cmp(&b.0)));
+        Ok(entries)
+    }
+}
+
+fn merge_runs<F>(runs: &[PathBuf], mut emit: F) -> io::Result<()>
+where
+    F: FnMut(String, Totals) -> io::Result<()>,
+{
+    let mut readers = Vec::with_capacity(runs.len());
+    for path in runs {
+        readers.push(BufReader::new(File::open(path)?));
+    }
+    let mut heap = BinaryHeap::new();
+    for (index, reader) in readers.iter_mut().enumerate() {
+        if let Some((key, totals)) = read_entry(reader)? {
+            heap.push(Reverse((key, index, totals.requests, totals.bytes)));
+        }
+    }
+    let mut current: Option<(String, Totals)> = None;
+    while let Some(Reverse((key, index, requests, bytes))) = heap.pop() {
+        if let Some((next_key, totals)) = read_entry(&mut readers[index])? {
+            heap.push(Reverse((next_key, index, totals.requests, totals.bytes)));
+        }
+        match &mut current {
+            Some((current_key, totals)) if *current_key == key => {
+                totals.requests += requests;
+                totals.bytes += bytes;
+            }
+            _ => {
+                if let Some((done_key, totals)) = current.replace((key, Totals { requests, bytes })) {
+                    emit(done_key, totals)?;
+                }
+            }
+        }
+    }
+    if let Some((key, totals)) = current {
+        emit(key, totals)?;
+    }
+    Ok(())
+}
+
+fn write_entry<W: Write>(writer: &mut W, key: &str, totals: Totals) -> io::Result<()> {
+    writer.write_all(&(key.len() as u32).to_le_bytes())?;
+    writer.write_all(key.as_bytes())?;
+    writer.write_all(&totals.requests.to_le_bytes())?;
+    writer.write_all(&totals.bytes.to_le_bytes())
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<(String, Totals)>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut key = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut key)?;
+    let mut number = [0; 8];
+    reader.read_exact(&mut number)?;
+    let requests = u64::from_le_bytes(number);
+    reader.read_exact(&mut number)?;
+    let bytes = u64::from_le_bytes(number);
+    let key = String::from_utf8(key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some((key, Totals { requests, bytes })))
+}
+
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: usize = number.parse().map_err(|_| format!("invalid size: {}", value))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("invalid size unit in {} (expected K, M or G)", value)),
+    };
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_runs_merge_to_the_same_totals() {
+        // a one-byte limit spills on every add, enough runs to force compaction as well
+        let mut counter = SpillingCounter::new(Some(1));
+        let mut expected: HashMap<String, Totals> = HashMap::new();
+        for index in 0..500u64 {
+            let key = format!("/page/{}", index * index % 37);
+            let totals = expected.entry(key.clone()).or_default();
+            totals.requests += 1;
+            totals.bytes += index;
+            counter.add(key, index).unwrap();
+        }
+        assert!(counter.spilled_runs() > MAX_FAN_IN);
+        let mut expected: Vec<(String, Totals)> = expected.into_iter().collect();
+        expected.sort_by(|a, b| b.1.requests.cmp(&a.1.requests).then_with(|| a.0.cmp(&b.0)));
+        expected.truncate(10);
+        let top = counter.top(10).unwrap();
+        let summary = |entries: &[(String, Totals)]| entries.iter().map(|(key, totals)| (key.clone(), totals.requests, totals.bytes)).collect::<Vec<_>>();
+        assert_eq!(summary(&top), summary(&expected));
+    }
+
+    #[test]
+    fn counts_stay_in_memory_under_the_limit() {
+        let mut counter = SpillingCounter::new(Some(1 << 20));
+        for key in ["/a", "/b", "/a"] {
+            counter.add(key.to_string(), 10).unwrap();
+        }
+        assert_eq!(counter.spilled_runs(), 0);
+        let top: Vec<(String, u64, u64)> = counter.top(5).unwrap().into_iter().map(|(key, totals)| (key, totals.requests, totals.bytes)).collect();
+        assert_eq!(top, [(String::from("/a"), 2, 20), (String::from("/b"), 1, 10)]);
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Local};
+
+use crate::w3c::split_fields;
+use crate::NginxLog;
+
+const EXTRA_FIELDS: [(usize, &str); 5] = [(2, "elb"), (15, "ssl_protocol"), (16, "target_group_arn"), (22, "actions_executed"), (24, "error_reason")];
+
+pub fn parse_alb_line(line: &str) -> Option<NginxLog> {
+    let values = split_fields(line);
+    if values.len() < 14 {
+        return None;
+    }
+    let (method, url, protocol) = {
+        let mut parts = values[12].splitn(3, ' ');
+        (parts.next()?, parts.next()?, parts.next().unwrap_or("-"))
+    };
+    let (host, path) = split_url(url);
+    let mut log = NginxLog {
+        remote_addr: values[3].rsplit_once(':').map_or(values[3].as_str(), |(addr, _)| addr).trim_matches(|c| c == '[' || c == ']').to_string(),
+        remote_user: String::from("-"),
+        request_time: DateTime::parse_from_rfc3339(&values[1]).ok()?.with_timezone(&Local),
+        request: format!("{} {} {}", method, path, protocol),
+        status: values[8].parse().ok()?,
+        body_bytes_sent: values[11].parse().ok()?,
+        http_referer: String::from("-"),
+        http_user_agent: values[13].clone(),
+        request_id: values.get(17).filter(|id| *id != "-").cloned(),
+        ..Default::default()
+    };
+    if let Some(host) = values.get(18).filter(|domain| *domain != "-").map(String::as_str).or(host) {
+        log.extra.insert(String::from("host"), host.to_string());
+    }
+    if values[4] != "-" {
+        log.extra.insert(String::from("upstream_addr"), values[4].clone());
+    }
+    if values[9] != "-" {
+        log.extra.insert(String::from("upstream_status"), values[9].clone());
+    }
+    let timings: Vec<f64> = values[5..8].iter().filter_map(|value| value.parse().ok()).collect();
+    if timings.len() == 3 && timings.iter().all(|timing| *timing >= 0.0) {
+        log.extra.insert(String::from("request_time"), format!("{:.3}", timings.iter().sum::<f64>()));
+        log.extra.insert(String::from("upstream_response_time"), format!("{:.3}", timings[1]));
+    }
+    for (index, name) in EXTRA_FIELDS {
+        if let Some(value) = values.get(index).filter(|value| !value.is_empty() && *value != "-") {
+            log.extra.insert(name.to_string(), value.clone());
+        }
+    }
+    Some(log)
+}
+
+// ALB logs the absolute URL; the port is dropped from the host like nginx's $host does
+fn split_url(url: &str) -> (Option<&str>, &str) {
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => return (None, url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    (Some(authority.rsplit_once(':').map_or(authority, |(host, _)| host)), path)
+}
@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Datelike, Local, Timelike, Weekday};
+
+use crate::interval::{trend_analysis, Alignment, Interval};
+use crate::NginxLog;
+
+const HOUR: Interval = Interval::Seconds(3600);
+const MIN_SEASON_SAMPLES: usize = 2;
+
+#[derive(Clone, Copy)]
+pub enum Metric {
+    Requests,
+    ErrorRate,
+    Bytes,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Requests => "requests",
+            Metric::ErrorRate => "5xx rate",
+            Metric::Bytes => "bytes",
+        }
+    }
+
+    pub fn render(&self, value: f64) -> String {
+        match self {
+            Metric::ErrorRate => format!("{:.2}%", value),
+            _ => format!("{:.0}", value),
+        }
+    }
+
+    // A flat history has a MAD of zero; the floor keeps single-request wobbles from being flagged
+    fn min_spread(&self, median: f64) -> f64 {
+        match self {
+            Metric::ErrorRate => 0.5,
+            _ => (median * 0.05).max(1.0),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct Bucket {
+    pub requests: u64,
+    pub errors: u64,
+    pub bytes: u64,
+}
+
+impl Bucket {
+    fn value(&self, metric: Metric) -> f64 {
+        match metric {
+            Metric::Requests => self.requests as f64,
+            Metric::ErrorRate if self.requests == 0 => 0.0,
+            Metric::ErrorRate => self.errors as f64 / self.requests as f64 * 100.0,
+            Metric::Bytes => self.bytes as f64,
+        }
+    }
+}
+
+pub struct Anomaly {
+    pub start: DateTime<Local>,
+    pub metric: Metric,
+    pub value: f64,
+    pub expected: f64,
+    pub deviation: f64,
+}
+
+pub fn hourly_buckets(logs: &[NginxLog]) -> BTreeMap<DateTime<Local>, Bucket> {
+    let mut buckets: BTreeMap<DateTime<Local>, Bucket> = trend_analysis(logs, HOUR, Alignment::Calendar).into_keys().map(|start| (start, Bucket::default())).collect();
+    for log in logs {
+        let bucket = buckets.entry(HOUR.bucket_start(log.request_time, Alignment::Calendar, log.request_time)).or_default();
+        bucket.requests += 1;
+        bucket.errors += u64::from(log.status >= 500);
+        bucket.bytes += log.body_bytes_sent;
+    }
+    buckets
+}
+
+pub fn detect(buckets: &BTreeMap<DateTime<Local>, Bucket>, train_from: DateTime<Local>, evaluate_from: DateTime<Local>, threshold: f64) -> Vec<Anomaly> {
+    let training: Vec<(&DateTime<Local>, &Bucket)> = buckets.range(train_from..evaluate_from).collect();
+    // the newest bucket is still filling up and would always look like a drop
+    let mut evaluated: Vec<(&DateTime<Local>, &Bucket)> = buckets.range(evaluate_from..).collect();
+    evaluated.pop();
+    let mut anomalies = Vec::new();
+    for metric in [Metric::Requests, Metric::ErrorRate, Metric::Bytes] {
+        let mut by_week_hour: HashMap<(Weekday, u32), Vec<f64>> = HashMap::new();
+        let mut by_hour: HashMap<u32, Vec<f64>> = HashMap::new();
+        for (start, bucket) in &training {
+            by_week_hour.entry((start.weekday(), start.hour())).or_default().push(bucket.value(metric));
+            by_hour.entry(start.hour()).or_default().push(bucket.value(metric));
+        }
+        for (start, bucket) in &evaluated {
+            let samples = match by_week_hour.get(&(start.weekday(), start.hour())) {
+                Some(samples) if samples.len() >= MIN_SEASON_SAMPLES => samples,
+                _ => match by_hour.get(&start.hour()) {
+                    Some(samples) if samples.len() >= MIN_SEASON_SAMPLES => samples,
+                    _ => continue,
+                },
+            };
+            let expected = median(samples);
+            let spread = median(&samples.iter().map(|sample| (sample - expected).abs()).collect::<Vec<_>>()).max(metric.min_spread(expected));
+            let value = bucket.value(metric);
+            let deviation = (value - expected) / spread;
+            if deviation.abs() > threshold {
+                anomalies.push(Anomaly { start: **start, metric, value, expected, deviation });
+            }
+        }
+    }
+    anomalies.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.metric.name().cmp(b.metric.name())));
+    anomalies
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
@@ -0,0 +1,51 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use sha2::{Digest, Sha256};
+
+use crate::NginxLog;
+
+const IPV4_PREFIX: u8 = 24;
+const IPV6_PREFIX: u8 = 48;
+pub const ADDRESS_FIELDS: [&str; 2] = ["http_x_forwarded_for", "http_x_real_ip"];
+
+pub enum Anonymizer {
+    Mask,
+    Hash(String),
+}
+
+impl Anonymizer {
+    pub fn parse(mode: &str, salt: Option<&str>) -> Result<Anonymizer, String> {
+        match (mode, salt) {
+            ("mask", _) => Ok(Anonymizer::Mask),
+            ("hash", Some(salt)) if !salt.is_empty() => Ok(Anonymizer::Hash(salt.to_string())),
+            ("hash", _) => Err(String::from("--anonymize hash needs a non-empty --anonymize-salt")),
+            _ => Err(format!("unknown anonymization mode: {} (expected mask or hash)", mode)),
+        }
+    }
+
+    pub fn address(&self, addr: &str) -> String {
+        match self {
+            Anonymizer::Mask => match addr.parse::<IpAddr>() {
+                Ok(ip) => {
+                    let prefix = if ip.is_ipv4() { IPV4_PREFIX } else { IPV6_PREFIX };
+                    IpNet::new(ip, prefix).unwrap().trunc().addr().to_string()
+                }
+                Err(_) => addr.to_string(),
+            },
+            Anonymizer::Hash(salt) => {
+                let digest = Sha256::new().chain_update(salt).chain_update(addr).finalize();
+                digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+            }
+        }
+    }
+
+    pub fn apply(&self, log: &mut NginxLog) {
+        log.remote_addr = self.address(&log.remote_addr);
+        for field in ADDRESS_FIELDS {
+            if let Some(value) = log.extra.get_mut(field) {
+                *value = value.split(',').map(|addr| self.address(addr.trim())).collect::<Vec<_>>().join(", ");
+            }
+        }
+    }
+}
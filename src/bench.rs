@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::parser::LineParser;
+
+pub struct BenchResult {
+    pub backend: &'static str,
+    pub lines: usize,
+    pub parsed: usize,
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn lines_per_sec(&self) -> f64 {
+        self.lines as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    pub fn mb_per_sec(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+pub fn load_sample<P: AsRef<Path>>(path: P, max_lines: Option<usize>) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let mut data = Vec::new();
+    let file = BufReader::new(File::open(path)?);
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        MultiGzDecoder::new(file).read_to_end(&mut data)?;
+    } else {
+        let mut file = file;
+        file.read_to_end(&mut data)?;
+    }
+    let text = String::from_utf8_lossy(&data);
+    let lines = text.lines().filter(|line| !line.trim().is_empty()).map(String::from);
+    Ok(match max_lines {
+        Some(max_lines) => lines.take(max_lines).collect(),
+        None => lines.collect(),
+    })
+}
+
+pub fn bench_parser(parser: LineParser, lines: &[String]) -> BenchResult {
+    let bytes = lines.iter().map(|line| line.len() + 1).sum();
+    let started = Instant::now();
+    let parsed = lines.iter().filter(|line| parser.parse(line).is_some()).count();
+    BenchResult {
+        backend: parser.name(),
+        lines: lines.len(),
+        parsed,
+        bytes,
+        elapsed: started.elapsed(),
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Local};
+
+use crate::interval::{Alignment, Interval};
+use crate::NginxLog;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Campaign {
+    pub source: String,
+    pub medium: String,
+    pub name: String,
+}
+
+impl Campaign {
+    pub fn from_log(log: &NginxLog) -> Option<Campaign> {
+        let source = log.query_param("utm_source");
+        let name = log.query_param("utm_campaign");
+        if source.is_none() && name.is_none() {
+            return None;
+        }
+        let or_dash = |value: Option<String>| value.filter(|value| !value.is_empty()).unwrap_or_else(|| String::from("-"));
+        Some(Campaign { source: or_dash(source), medium: or_dash(log.query_param("utm_medium")), name: or_dash(name) })
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} / {} / {}", self.source, self.medium, self.name)
+    }
+}
+
+pub struct CampaignStats {
+    pub sessions: u64,
+    pub requests: u64,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+}
+
+pub struct CampaignReport {
+    pub campaigns: Vec<(Campaign, CampaignStats)>,
+    pub buckets: BTreeMap<DateTime<Local>, HashMap<Campaign, u64>>,
+}
+
+pub fn campaign_report(sessions: &[Vec<&NginxLog>], interval: Interval, align: Alignment) -> CampaignReport {
+    let mut totals: HashMap<Campaign, CampaignStats> = HashMap::new();
+    let mut attributed = Vec::new();
+    for session in sessions {
+        let campaign = match session.iter().find_map(|log| Campaign::from_log(log)) {
+            Some(campaign) => campaign,
+            None => continue,
+        };
+        let (first, last) = (session[0].request_time, session[session.len() - 1].request_time);
+        let stats = totals.entry(campaign.clone()).or_insert(CampaignStats { sessions: 0, requests: 0, first_seen: first, last_seen: last });
+        stats.sessions += 1;
+        stats.requests += session.len() as u64;
+        stats.first_seen = stats.first_seen.min(first);
+        stats.last_seen = stats.last_seen.max(last);
+        attributed.push((first, campaign));
+    }
+    let mut buckets: BTreeMap<DateTime<Local>, HashMap<Campaign, u64>> = BTreeMap::new();
+    if let Some(origin) = attributed.iter().map(|(start, _)| *start).min() {
+        for (start, campaign) in attributed {
+            *buckets.entry(interval.bucket_start(start, align, origin)).or_default().entry(campaign).or_default() += 1;
+        }
+    }
+    let mut campaigns: Vec<(Campaign, CampaignStats)> = totals.into_iter().collect();
+    campaigns.sort_by(|a, b| b.1.sessions.cmp(&a.1.sessions).then_with(|| a.0.cmp(&b.0)));
+    CampaignReport { campaigns, buckets }
+}
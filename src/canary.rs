@@ -0,0 +1,97 @@
+use crate::hosts::host_of;
+use crate::latency::{percentile, request_seconds};
+use crate::NginxLog;
+
+pub struct Thresholds {
+    pub max_5xx_increase: f64,
+    pub max_4xx_increase: f64,
+    pub max_latency_ratio: f64,
+}
+
+pub struct Sample {
+    pub requests: u64,
+    // share of 1xx..5xx responses in percent
+    pub status_mix: [f64; 5],
+    pub latencies: Vec<f64>,
+}
+
+pub struct Check {
+    pub metric: String,
+    pub baseline: String,
+    pub canary: String,
+    pub limit: String,
+    pub passed: Option<bool>,
+}
+
+// Without explicit baseline hosts, every other host is the baseline
+pub fn split<'a>(logs: &'a [NginxLog], baseline: &[&str], canary: &str) -> (Vec<&'a NginxLog>, Vec<&'a NginxLog>) {
+    let canary_logs: Vec<&NginxLog> = logs.iter().filter(|log| host_of(log) == canary).collect();
+    // only compare against the baseline while the canary was serving
+    let start = canary_logs.iter().map(|log| log.request_time).min();
+    let end = canary_logs.iter().map(|log| log.request_time).max();
+    let in_baseline = |host: &str| if baseline.is_empty() { host != canary && host != "-" } else { baseline.contains(&host) };
+    let baseline_logs = logs
+        .iter()
+        .filter(|log| in_baseline(host_of(log)))
+        .filter(|log| start.is_some_and(|start| log.request_time >= start) && end.is_some_and(|end| log.request_time <= end))
+        .collect();
+    (baseline_logs, canary_logs)
+}
+
+pub fn sample(logs: &[&NginxLog]) -> Sample {
+    let mut status_mix = [0.0; 5];
+    for log in logs {
+        if let class @ 1..=5 = log.status / 100 {
+            status_mix[usize::from(class) - 1] += 1.0;
+        }
+    }
+    for share in &mut status_mix {
+        *share = *share / logs.len().max(1) as f64 * 100.0;
+    }
+    let mut latencies: Vec<f64> = logs.iter().filter_map(|log| request_seconds(log)).collect();
+    latencies.sort_by(f64::total_cmp);
+    Sample { requests: logs.len() as u64, status_mix, latencies }
+}
+
+pub fn checks(baseline: &Sample, canary: &Sample, thresholds: &Thresholds) -> Vec<Check> {
+    let mut checks = vec![Check {
+        metric: String::from("requests"),
+        baseline: baseline.requests.to_string(),
+        canary: canary.requests.to_string(),
+        limit: String::new(),
+        passed: None,
+    }];
+    for (index, class) in ["1xx", "2xx", "3xx", "4xx", "5xx"].iter().enumerate() {
+        let (base, can) = (baseline.status_mix[index], canary.status_mix[index]);
+        let max_increase = match *class {
+            "4xx" => Some(thresholds.max_4xx_increase),
+            "5xx" => Some(thresholds.max_5xx_increase),
+            _ => None,
+        };
+        if base == 0.0 && can == 0.0 && max_increase.is_none() {
+            continue;
+        }
+        checks.push(Check {
+            metric: format!("{} share", class),
+            baseline: format!("{:.2}%", base),
+            canary: format!("{:.2}%", can),
+            limit: max_increase.map(|max| format!("<= {:.2}%", base + max)).unwrap_or_default(),
+            passed: max_increase.map(|max| can <= base + max),
+        });
+    }
+    for percent in [50.0, 95.0, 99.0] {
+        let (base, can) = match (percentile(&baseline.latencies, percent), percentile(&canary.latencies, percent)) {
+            (Some(base), Some(can)) => (base, can),
+            _ => continue,
+        };
+        let checked = percent > 50.0;
+        checks.push(Check {
+            metric: format!("p{} request time", percent),
+            baseline: format!("{:.3}s", base),
+            canary: format!("{:.3}s", can),
+            limit: if checked { format!("<= {:.3}s", base * thresholds.max_latency_ratio) } else { String::new() },
+            passed: if checked { Some(can <= base * thresholds.max_latency_ratio) } else { None },
+        });
+    }
+    checks
+}
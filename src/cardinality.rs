@@ -0,0 +1,37 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::NginxLog;
+
+pub const DEFAULT_FIELDS: [&str; 6] = ["ip", "path", "user_agent", "referer", "method", "status"];
+
+pub struct Cardinality {
+    pub field: String,
+    // entries that have the field at all
+    pub present: u64,
+    pub distinct: usize,
+    pub top: Option<(String, u64)>,
+}
+
+// Without explicit fields, the standard ones plus every extra field in the entries
+pub fn default_fields(logs: &[NginxLog]) -> Vec<String> {
+    let extra: BTreeSet<&String> = logs.iter().flat_map(|log| log.extra.keys()).collect();
+    DEFAULT_FIELDS.iter().map(|field| field.to_string()).chain(extra.into_iter().cloned()).collect()
+}
+
+pub fn cardinality(logs: &[NginxLog], fields: &[String]) -> Vec<Cardinality> {
+    fields
+        .iter()
+        .map(|field| {
+            let mut counts: HashMap<String, u64> = HashMap::new();
+            for value in logs.iter().filter_map(|log| log.field(field)).filter(|value| !value.is_empty() && value != "-") {
+                *counts.entry(value).or_insert(0) += 1;
+            }
+            Cardinality {
+                field: field.clone(),
+                present: counts.values().sum(),
+                distinct: counts.len(),
+                top: counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0))),
+            }
+        })
+        .collect()
+}
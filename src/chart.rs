@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use crate::interval::{trend_analysis, trend_by, Alignment, Interval};
+use crate::latency::{percentile, request_seconds};
+use crate::NginxLog;
+
+const LATENCY_PERCENTILES: [f64; 3] = [50.0, 95.0, 99.0];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChartKind {
+    Requests,
+    Status,
+    Latency,
+}
+
+impl ChartKind {
+    pub fn parse(name: &str) -> Result<ChartKind, String> {
+        match name {
+            "requests" => Ok(ChartKind::Requests),
+            "status" => Ok(ChartKind::Status),
+            "latency" => Ok(ChartKind::Latency),
+            _ => Err(format!("unknown chart: {} (expected requests, status or latency)", name)),
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ChartKind::Requests => "Requests",
+            ChartKind::Status => "Requests by status class",
+            ChartKind::Latency => "Request time percentiles (s)",
+        }
+    }
+}
+
+pub struct Chart {
+    pub kind: ChartKind,
+    // for status charts each series already includes the ones before it, so the areas stack
+    pub series: Vec<(String, Vec<(DateTime<Local>, f64)>)>,
+}
+
+pub fn build_chart(logs: &[NginxLog], kind: ChartKind, interval: Interval, align: Alignment) -> Result<Chart, String> {
+    let series = match kind {
+        ChartKind::Requests => vec![(String::from("requests"), trend_analysis(logs, interval, align).into_iter().map(|(start, count)| (start, count as f64)).collect())],
+        ChartKind::Status => {
+            let trends = trend_by(logs, interval, align, |log| format!("{}xx", log.status / 100));
+            let classes: BTreeSet<&String> = trends.values().flat_map(|counts| counts.keys()).collect();
+            let mut series: Vec<(String, Vec<(DateTime<Local>, f64)>)> = Vec::new();
+            for class in classes {
+                let points = trends
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (start, counts))| {
+                        let below = series.last().map_or(0.0, |(_, points)| points[index].1);
+                        (*start, below + counts.get(class).copied().unwrap_or(0) as f64)
+                    })
+                    .collect();
+                series.push((class.clone(), points));
+            }
+            series
+        }
+        ChartKind::Latency => {
+            let origin = logs.iter().map(|log| log.request_time).min().ok_or_else(|| String::from("no entries to draw"))?;
+            let mut periods: BTreeMap<DateTime<Local>, Vec<f64>> = BTreeMap::new();
+            for log in logs {
+                if let Some(seconds) = request_seconds(log) {
+                    periods.entry(interval.bucket_start(log.request_time, align, origin)).or_default().push(seconds);
+                }
+            }
+            for seconds in periods.values_mut() {
+                seconds.sort_by(f64::total_cmp);
+            }
+            LATENCY_PERCENTILES
+                .iter()
+                .map(|percent| {
+                    let points = periods.iter().filter_map(|(start, seconds)| Some((*start, percentile(seconds, *percent)?))).collect();
+                    (format!("p{}", percent), points)
+                })
+                .collect()
+        }
+    };
+    if series.iter().map(|(_, points)| points.len()).max().unwrap_or(0) < 2 {
+        let hint = if kind == ChartKind::Latency { "; latency charts need $request_time in the log format" } else { "" };
+        return Err(format!("need at least two periods to draw a chart; use a shorter --interval{}", hint));
+    }
+    Ok(Chart { kind, series })
+}
+
+#[cfg(feature = "charts")]
+pub fn render(chart: &Chart, path: &Path, size: (u32, u32)) -> Result<(), Box<dyn Error>> {
+    use plotters::prelude::*;
+
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg")) {
+        draw(SVGBackend::new(path, size).into_drawing_area(), chart)
+    } else {
+        draw(BitMapBackend::new(path, size).into_drawing_area(), chart)
+    }
+}
+
+#[cfg(not(feature = "charts"))]
+pub fn render(_chart: &Chart, path: &Path, _size: (u32, u32)) -> Result<(), Box<dyn Error>> {
+    Err(format!("{}: built without chart support; rebuild with --features charts", path.display()).into())
+}
+
+#[cfg(feature = "charts")]
+fn draw<DB: plotters::prelude::DrawingBackend>(root: plotters::prelude::DrawingArea<DB, plotters::coord::Shift>, chart: &Chart) -> Result<(), Box<dyn Error>> {
+    use plotters::prelude::*;
+
+    let points = chart.series.iter().flat_map(|(_, points)| points);
+    let start = points.clone().map(|(time, _)| *time).min().ok_or("nothing to draw")?;
+    let end = points.clone().map(|(time, _)| *time).max().ok_or("nothing to draw")?;
+    let max = points.map(|(_, value)| *value).fold(0.0, f64::max);
+    root.fill(&WHITE).map_err(plot_error)?;
+    let mut plot = ChartBuilder::on(&root)
+        .caption(chart.kind.title(), ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(start..end, 0.0..if max > 0.0 { max * 1.1 } else { 1.0 })
+        .map_err(plot_error)?;
+    plot.configure_mesh().x_label_formatter(&|time: &DateTime<Local>| time.format("%m-%d %H:%M").to_string()).draw().map_err(plot_error)?;
+    // stacked areas are drawn from the top down so each class stays visible above the ones below it
+    for (index, (name, points)) in chart.series.iter().enumerate().rev() {
+        let color = Palette99::pick(index).mix(1.0);
+        let annotation = if chart.kind == ChartKind::Latency {
+            plot.draw_series(LineSeries::new(points.iter().copied(), color.stroke_width(2))).map_err(plot_error)?
+        } else {
+            plot.draw_series(AreaSeries::new(points.iter().copied(), 0.0, color.mix(0.4)).border_style(color)).map_err(plot_error)?
+        };
+        annotation.label(name.as_str()).legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+    }
+    plot.configure_series_labels().background_style(WHITE.mix(0.8)).border_style(BLACK).draw().map_err(plot_error)?;
+    root.present().map_err(plot_error)?;
+    Ok(())
+}
+
+#[cfg(feature = "charts")]
+fn plot_error<E: std::fmt::Display>(error: E) -> String {
+    error.to_string()
+}
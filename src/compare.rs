@@ -0,0 +1,74 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::aggregate::GroupField;
+use crate::NginxLog;
+
+pub struct Metric {
+    pub name: String,
+    pub before: f64,
+    pub after: f64,
+    pub percent: bool,
+}
+
+impl Metric {
+    fn count(name: &str, before: usize, after: usize) -> Metric {
+        Metric { name: name.to_string(), before: before as f64, after: after as f64, percent: false }
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+
+    pub fn change(&self) -> Option<f64> {
+        if self.before == 0.0 {
+            None
+        } else {
+            Some(self.delta() / self.before * 100.0)
+        }
+    }
+
+    pub fn render(&self, value: f64) -> String {
+        if self.percent {
+            format!("{:.2}%", value)
+        } else {
+            format!("{}", value)
+        }
+    }
+}
+
+pub fn compare(before: &[NginxLog], after: &[NginxLog], by: (&str, &GroupField), top: usize) -> Vec<Metric> {
+    let clients = |logs: &[NginxLog]| logs.iter().map(|log| log.client()).collect::<HashSet<_>>().len();
+    let bytes = |logs: &[NginxLog]| logs.iter().map(|log| log.body_bytes_sent).sum::<u64>() as f64;
+    let with_status = |logs: &[NginxLog], class: u16| logs.iter().filter(|log| log.status / 100 == class).count();
+    let error_rate = |logs: &[NginxLog]| {
+        let errors = logs.iter().filter(|log| log.status >= 400).count();
+        if logs.is_empty() {
+            0.0
+        } else {
+            errors as f64 / logs.len() as f64 * 100.0
+        }
+    };
+    let mut metrics = vec![
+        Metric::count("requests", before.len(), after.len()),
+        Metric::count("unique clients", clients(before), clients(after)),
+        Metric { name: String::from("body bytes sent"), before: bytes(before), after: bytes(after), percent: false },
+        Metric { name: String::from("error rate"), before: error_rate(before), after: error_rate(after), percent: true },
+    ];
+    for class in 2..=5 {
+        metrics.push(Metric::count(&format!("{}xx", class), with_status(before, class), with_status(after, class)));
+    }
+    let (by_name, by) = by;
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for log in before {
+        counts.entry(by.key(log)).or_default().0 += 1;
+    }
+    for log in after {
+        counts.entry(by.key(log)).or_default().1 += 1;
+    }
+    let mut counts: Vec<(String, (usize, usize))> = counts.into_iter().collect();
+    counts.sort_by(|a, b| (b.1 .0 + b.1 .1).cmp(&(a.1 .0 + a.1 .1)).then_with(|| a.0.cmp(&b.0)));
+    for (key, (before, after)) in counts.into_iter().take(top) {
+        metrics.push(Metric::count(&format!("{}={}", by_name, key), before, after));
+    }
+    metrics
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::parser::PARSER_NAMES;
+use crate::{in_path, NginxLog};
+
+pub const DEFAULT_CONFIG: &str = "nginx-log-explorer.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub log_format: Option<String>,
+    pub parser: Option<String>,
+    #[serde(default)]
+    pub formats: BTreeMap<String, String>,
+    #[serde(default)]
+    pub pipelines: BTreeMap<String, Pipeline>,
+    #[serde(default)]
+    pub api_tokens: BTreeMap<String, ApiToken>,
+    #[serde(default)]
+    pub schedules: BTreeMap<String, Schedule>,
+}
+
+// A report the daemon builds on a cron schedule and sends to its sinks
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    pub cron: String,
+    pub report: ScheduledReport,
+    #[serde(default = "default_window")]
+    pub window: String,
+    #[serde(default = "default_report_top")]
+    pub top: usize,
+    pub path: Option<String>,
+    #[serde(default)]
+    pub email: Vec<String>,
+    pub smtp: Option<String>,
+    pub from: Option<String>,
+    pub webhook: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledReport {
+    Summary,
+    Digest,
+}
+
+// What a bearer token may see in serve mode
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiToken {
+    pub token: String,
+    // e.g. "7d": only entries from the last seven days
+    pub lookback: Option<String>,
+    pub anonymize: Option<String>,
+    pub anonymize_salt: Option<String>,
+    #[serde(default)]
+    pub hide_fields: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    #[serde(default)]
+    pub filters: Vec<FilterStep>,
+    #[serde(default)]
+    pub enrich: Vec<Enricher>,
+    pub aggregate: Option<Aggregation>,
+    #[serde(default)]
+    pub sinks: Vec<Sink>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterStep {
+    pub field: String,
+    pub equals: Option<String>,
+    pub contains: Option<String>,
+    pub matches: Option<String>,
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Enricher {
+    Extract(String),
+    KnownIps(String),
+    KnownCidrs(String),
+    ExcludeKnown(bool),
+    Plugin(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aggregation {
+    pub by: String,
+    #[serde(default = "default_top")]
+    pub top: usize,
+    pub max_memory: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sink {
+    #[serde(default)]
+    pub format: SinkFormat,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+fn default_top() -> usize {
+    20
+}
+
+fn default_window() -> String {
+    String::from("1d")
+}
+
+fn default_report_top() -> usize {
+    10
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| in_path(path.display(), e))?;
+        let config: Config = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(parser) = config.parser.as_deref().filter(|parser| !PARSER_NAMES.contains(parser)) {
+            return Err(format!("{}: unknown parser: {} (expected one of {})", path.display(), parser, PARSER_NAMES.join(", ")).into());
+        }
+        Ok(config)
+    }
+
+    pub fn load_or_default(path: Option<&str>) -> Result<Config, Box<dyn Error>> {
+        match path {
+            Some(path) => Config::load(path),
+            None if Path::new(DEFAULT_CONFIG).exists() => Config::load(DEFAULT_CONFIG),
+            None => Ok(Config::default()),
+        }
+    }
+
+    pub fn pipeline(&self, name: &str) -> Result<&Pipeline, String> {
+        self.pipelines.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.pipelines.keys().map(String::as_str).collect();
+            format!("unknown pipeline: {} (defined: {})", name, known.join(", "))
+        })
+    }
+}
+
+pub struct CompiledFilter<'a> {
+    step: &'a FilterStep,
+    pattern: Option<Regex>,
+}
+
+impl FilterStep {
+    pub fn compile(&self) -> Result<CompiledFilter<'_>, String> {
+        let pattern = self
+            .matches
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("invalid pipeline filter regex for {}: {}", self.field, e))?;
+        Ok(CompiledFilter { step: self, pattern })
+    }
+}
+
+impl CompiledFilter<'_> {
+    pub fn keep(&self, log: &NginxLog) -> bool {
+        let step = self.step;
+        let matched = log.field(&step.field).is_some_and(|value| {
+            step.equals.as_ref().is_none_or(|equals| value == *equals)
+                && step.contains.as_ref().is_none_or(|contains| value.contains(contains.as_str()))
+                && self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(&value))
+        });
+        matched != step.exclude
+    }
+}
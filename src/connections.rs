@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::NginxLog;
+
+pub const LENGTH_BUCKETS: [(u64, u64); 6] = [(1, 1), (2, 2), (3, 5), (6, 10), (11, 100), (101, u64::MAX)];
+
+#[derive(Default)]
+pub struct ClientReuse {
+    pub client: String,
+    pub requests: u64,
+    // requests served per connection → number of connections
+    pub lengths: BTreeMap<u64, u64>,
+}
+
+impl ClientReuse {
+    pub fn connections(&self) -> u64 {
+        self.lengths.values().sum()
+    }
+
+    pub fn never_reused(&self) -> bool {
+        self.lengths.keys().all(|length| *length == 1)
+    }
+}
+
+pub struct ReuseStats {
+    pub requests: u64,
+    pub connections: u64,
+    pub buckets: [u64; LENGTH_BUCKETS.len()],
+    pub clients: Vec<ClientReuse>,
+}
+
+impl ReuseStats {
+    pub fn per_connection(&self) -> f64 {
+        if self.connections == 0 {
+            0.0
+        } else {
+            self.requests as f64 / self.connections as f64
+        }
+    }
+}
+
+pub fn connection_reuse(logs: &[NginxLog]) -> Option<ReuseStats> {
+    let mut by_id: HashMap<(String, String), u64> = HashMap::new();
+    // without $connection, a connection that served k requests logs exactly one entry with $connection_requests = k
+    let mut by_sequence: HashMap<String, BTreeMap<u64, u64>> = HashMap::new();
+    let mut clients: HashMap<String, ClientReuse> = HashMap::new();
+    for log in logs {
+        let sequence = log.extra.get("connection_requests").and_then(|value| value.parse::<u64>().ok());
+        match (log.extra.get("connection"), sequence) {
+            (Some(id), _) => {
+                let length = by_id.entry((log.client(), id.clone())).or_insert(0);
+                *length = sequence.unwrap_or(*length + 1).max(*length);
+            }
+            (None, Some(sequence)) => *by_sequence.entry(log.client()).or_default().entry(sequence).or_insert(0) += 1,
+            (None, None) => continue,
+        }
+        let client = log.client();
+        clients.entry(client.clone()).or_insert_with(|| ClientReuse { client, ..Default::default() }).requests += 1;
+    }
+    if clients.is_empty() {
+        return None;
+    }
+    for ((client, _), length) in by_id {
+        if let Some(reuse) = clients.get_mut(&client) {
+            *reuse.lengths.entry(length).or_insert(0) += 1;
+        }
+    }
+    for (client, sequences) in by_sequence {
+        let reuse = match clients.get_mut(&client) {
+            Some(reuse) => reuse,
+            None => continue,
+        };
+        for (sequence, count) in &sequences {
+            let continued = sequences.get(&(sequence + 1)).copied().unwrap_or(0);
+            let ended = count.saturating_sub(continued);
+            if ended > 0 {
+                *reuse.lengths.entry(*sequence).or_insert(0) += ended;
+            }
+        }
+    }
+    let mut stats = ReuseStats { requests: 0, connections: 0, buckets: [0; LENGTH_BUCKETS.len()], clients: clients.into_values().collect() };
+    for client in &stats.clients {
+        stats.requests += client.requests;
+        for (length, count) in &client.lengths {
+            stats.connections += count;
+            if let Some(index) = LENGTH_BUCKETS.iter().position(|(low, high)| (*low..=*high).contains(length)) {
+                stats.buckets[index] += count;
+            }
+        }
+    }
+    stats.clients.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.client.cmp(&b.client)));
+    Some(stats)
+}
+
+pub fn bucket_label(index: usize) -> String {
+    match LENGTH_BUCKETS[index] {
+        (low, high) if low == high => low.to_string(),
+        (low, u64::MAX) => format!("{}+", low),
+        (low, high) => format!("{}-{}", low, high),
+    }
+}
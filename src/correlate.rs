@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::NginxLog;
+
+const EDGE_ONLY_RESULTS: [&str; 5] = ["Hit", "Redirect", "LimitExceeded", "CapacityExceeded", "FunctionGeneratedResponse"];
+const ORIGIN_ID_FIELDS: [&str; 3] = ["http_x_amz_cf_id", "http_x_amzn_trace_id", "http_x_request_id"];
+
+#[derive(Clone, Copy)]
+pub enum MatchBy {
+    Auto,
+    RequestId,
+    TimePath,
+}
+
+impl MatchBy {
+    pub fn parse(value: &str) -> Result<MatchBy, String> {
+        match value {
+            "auto" => Ok(MatchBy::Auto),
+            "id" | "request-id" => Ok(MatchBy::RequestId),
+            "time-path" => Ok(MatchBy::TimePath),
+            _ => Err(format!("invalid --by: {} (expected auto, id or time-path)", value)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchBy::Auto => "auto",
+            MatchBy::RequestId => "request id",
+            MatchBy::TimePath => "time and path",
+        }
+    }
+}
+
+pub struct Correlation {
+    pub by: MatchBy,
+    pub edge_requests: usize,
+    pub edge_bytes: u64,
+    pub cache_hits: usize,
+    pub matched: usize,
+    pub matched_bytes: u64,
+    pub origin_requests: usize,
+    pub origin_only: usize,
+    pub missing: Vec<(String, usize)>,
+    pub missing_total: usize,
+}
+
+impl Correlation {
+    pub fn offload(&self) -> f64 {
+        ratio((self.edge_requests - self.matched) as f64, self.edge_requests as f64)
+    }
+
+    pub fn bytes_offload(&self) -> f64 {
+        ratio((self.edge_bytes - self.matched_bytes) as f64, self.edge_bytes as f64)
+    }
+}
+
+pub fn correlate(edge: &[NginxLog], origin: &[NginxLog], by: MatchBy, window: f64, top: usize) -> Correlation {
+    let origin_ids: HashMap<&str, usize> = origin.iter().enumerate().flat_map(|(index, log)| origin_ids(log).into_iter().map(move |id| (id, index))).collect();
+    let by = match by {
+        MatchBy::Auto if edge.iter().any(|log| log.request_id.as_deref().is_some_and(|id| origin_ids.contains_key(id))) => MatchBy::RequestId,
+        MatchBy::Auto => MatchBy::TimePath,
+        by => by,
+    };
+    let matches: Vec<Option<usize>> = match by {
+        MatchBy::RequestId => edge.iter().map(|log| log.request_id.as_deref().and_then(|id| origin_ids.get(id).copied())).collect(),
+        _ => match_time_path(edge, origin, window),
+    };
+    let matched_origin: HashSet<usize> = matches.iter().flatten().copied().collect();
+    let mut missing: HashMap<String, usize> = HashMap::new();
+    for (log, matched) in edge.iter().zip(&matches) {
+        if matched.is_none() && expected_at_origin(log) {
+            *missing.entry(log.path().unwrap_or("-").to_string()).or_insert(0) += 1;
+        }
+    }
+    let missing_total = missing.values().sum();
+    let mut missing: Vec<(String, usize)> = missing.into_iter().collect();
+    missing.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    missing.truncate(top);
+    Correlation {
+        by,
+        edge_requests: edge.len(),
+        edge_bytes: edge.iter().map(|log| log.body_bytes_sent).sum(),
+        cache_hits: edge.iter().filter(|log| !expected_at_origin(log)).count(),
+        matched: matches.iter().flatten().count(),
+        matched_bytes: edge.iter().zip(&matches).filter(|(_, matched)| matched.is_some()).map(|(log, _)| log.body_bytes_sent).sum(),
+        origin_requests: origin.len(),
+        origin_only: origin.len() - matched_origin.len(),
+        missing,
+        missing_total,
+    }
+}
+
+fn origin_ids(log: &NginxLog) -> Vec<&str> {
+    let mut ids: Vec<&str> = ORIGIN_ID_FIELDS.iter().filter_map(|name| log.extra.get(*name)).map(String::as_str).collect();
+    ids.extend(log.request_id.as_deref());
+    ids.retain(|id| !id.is_empty() && *id != "-");
+    ids
+}
+
+// CloudFront says whether it answered from cache; entries without that field are all expected upstream
+fn expected_at_origin(log: &NginxLog) -> bool {
+    log.extra.get("x_edge_result_type").is_none_or(|result| !EDGE_ONLY_RESULTS.contains(&result.as_str()))
+}
+
+fn match_time_path(edge: &[NginxLog], origin: &[NginxLog], window: f64) -> Vec<Option<usize>> {
+    let key = |log: &NginxLog| (log.method().unwrap_or("GET").to_string(), log.path().unwrap_or("-").to_string());
+    let mut candidates: HashMap<(String, String), Vec<(i64, usize)>> = HashMap::new();
+    for (index, log) in origin.iter().enumerate() {
+        candidates.entry(key(log)).or_default().push((log.request_time.timestamp_millis(), index));
+    }
+    for times in candidates.values_mut() {
+        times.sort_unstable();
+    }
+    let window = (window * 1000.0) as i64;
+    let mut order: Vec<usize> = (0..edge.len()).filter(|index| expected_at_origin(&edge[*index])).collect();
+    order.sort_by_key(|index| edge[*index].request_time);
+    let mut matches = vec![None; edge.len()];
+    let mut used: HashSet<usize> = HashSet::new();
+    for index in order {
+        let time = edge[index].request_time.timestamp_millis();
+        let times = match candidates.get(&key(&edge[index])) {
+            Some(times) => times,
+            None => continue,
+        };
+        let first = times.partition_point(|(origin_time, _)| *origin_time < time - window);
+        let found = times[first..].iter().take_while(|(origin_time, _)| *origin_time <= time + window).find(|(_, origin)| !used.contains(origin));
+        if let Some((_, origin)) = found {
+            used.insert(*origin);
+            matches[index] = Some(*origin);
+        }
+    }
+    matches
+}
+
+fn ratio(part: f64, total: f64) -> f64 {
+    if total == 0.0 {
+        0.0
+    } else {
+        part / total * 100.0
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Local};
+
+use crate::cve::percent_decode;
+use crate::interval::{Alignment, Interval};
+use crate::robots::Robots;
+use crate::useragent::search_bot;
+use crate::NginxLog;
+
+const SEARCH_ENGINES: &[(&str, &str)] = &[
+    ("google.", "q"),
+    ("bing.com", "q"),
+    ("duckduckgo.com", "q"),
+    ("ecosia.org", "q"),
+    ("search.yahoo.", "p"),
+    ("yandex.", "text"),
+    ("baidu.com", "wd"),
+];
+
+#[derive(Default)]
+pub struct BotStats {
+    pub requests: u64,
+    pub paths: HashSet<String>,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub disallowed: u64,
+}
+
+#[derive(Default)]
+pub struct CrawlReport {
+    pub bots: BTreeMap<&'static str, BotStats>,
+    pub daily: BTreeMap<DateTime<Local>, HashMap<&'static str, u64>>,
+    pub paths: HashMap<String, u64>,
+    pub errors: HashMap<(&'static str, u16, String), u64>,
+    pub keywords: HashMap<(&'static str, String), u64>,
+}
+
+pub fn crawl_report(logs: &[NginxLog], interval: Interval, align: Alignment, robots: Option<&Robots>) -> CrawlReport {
+    let mut report = CrawlReport::default();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return report,
+    };
+    for log in logs {
+        let user_agent = log.http_user_agent.trim_matches('"');
+        let bot = match search_bot(user_agent) {
+            Some(bot) => bot,
+            None => {
+                if let Some(keyword) = search_keyword(log.http_referer.trim_matches('"')) {
+                    *report.keywords.entry(keyword).or_default() += 1;
+                }
+                continue;
+            }
+        };
+        let path = log.path().unwrap_or("-").to_string();
+        let stats = report.bots.entry(bot).or_default();
+        stats.requests += 1;
+        match log.status {
+            400..=499 => stats.client_errors += 1,
+            500..=599 => stats.server_errors += 1,
+            _ => {}
+        }
+        if robots.is_some_and(|robots| !robots.is_allowed(user_agent, &path)) {
+            stats.disallowed += 1;
+        }
+        if log.status >= 400 {
+            *report.errors.entry((bot, log.status, path.clone())).or_default() += 1;
+        }
+        stats.paths.insert(path.clone());
+        *report.paths.entry(path).or_default() += 1;
+        let bucket = interval.bucket_start(log.request_time, align, origin);
+        *report.daily.entry(bucket).or_default().entry(bot).or_default() += 1;
+    }
+    report
+}
+
+pub fn search_keyword(referer: &str) -> Option<(&'static str, String)> {
+    let rest = referer.strip_prefix("https://").or_else(|| referer.strip_prefix("http://"))?;
+    let (host, query) = match rest.split_once('?') {
+        Some((address, query)) => (address.split('/').next().unwrap_or(address), query),
+        None => return None,
+    };
+    let (engine, param) = SEARCH_ENGINES.iter().find(|(engine, _)| host.contains(engine))?;
+    let value = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key == param)
+        .map(|(_, value)| percent_decode(&value.replace('+', " ")).trim().to_lowercase())
+        .filter(|value| !value.is_empty())?;
+    Some((engine.trim_matches('.'), value))
+}
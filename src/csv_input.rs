@@ -0,0 +1,117 @@
+use crate::parser::{parse_log_time, parse_status, ParseError};
+use crate::NginxLog;
+
+enum Column {
+    Index(usize),
+    Name(String),
+}
+
+pub struct CsvFormat {
+    delimiter: char,
+    header: Option<String>,
+    columns: Vec<(String, usize)>,
+}
+
+impl CsvFormat {
+    pub fn compile(specs: &[&str], delimiter: char, header: Option<&str>) -> Result<CsvFormat, String> {
+        let mut mapping = Vec::new();
+        for spec in specs.iter().flat_map(|spec| spec.split(',')).map(str::trim).filter(|spec| !spec.is_empty()) {
+            let (field, column) = spec.split_once('=').ok_or_else(|| format!("invalid --csv-map entry: {} (expected FIELD=COLUMN)", spec))?;
+            let column = match column.trim().parse::<usize>() {
+                Ok(0) => return Err(format!("invalid --csv-map entry: {} (columns are numbered from 1)", spec)),
+                Ok(index) => Column::Index(index - 1),
+                Err(_) => Column::Name(column.trim().to_string()),
+            };
+            mapping.push((canonical(field.trim()).to_string(), column));
+        }
+        if !mapping.iter().any(|(field, _)| field == "time") {
+            return Err(String::from("--csv-map needs a time column, e.g. time=timestamp"));
+        }
+        if !mapping.iter().any(|(field, _)| field == "request" || field == "path") {
+            return Err(String::from("--csv-map needs a request or path column"));
+        }
+        let names: Option<Vec<String>> = header.map(|header| split(header, delimiter).into_iter().map(|name| name.trim().to_lowercase()).collect());
+        let mut columns = Vec::new();
+        for (field, column) in mapping {
+            let index = match column {
+                Column::Index(index) => index,
+                Column::Name(name) => {
+                    let names = names.as_ref().ok_or_else(|| format!("--csv-map refers to column {} by name but the input has no header line", name))?;
+                    names.iter().position(|candidate| *candidate == name.to_lowercase()).ok_or_else(|| format!("--csv-map: no column named {} in header {}", name, names.join(",")))?
+                }
+            };
+            columns.push((field, index));
+        }
+        Ok(CsvFormat { delimiter, header: header.map(|header| header.trim().to_string()), columns })
+    }
+
+    pub fn is_header(&self, line: &str) -> bool {
+        self.header.as_deref() == Some(line.trim())
+    }
+
+    pub fn parse_line(&self, line: &str) -> Result<NginxLog, ParseError> {
+        let values = split(line, self.delimiter);
+        let mut log = NginxLog { remote_addr: String::from("-"), remote_user: String::from("-"), http_referer: String::from("-"), http_user_agent: String::from("-"), ..Default::default() };
+        let (mut time, mut method, mut path, mut protocol) = (None, None, None, None);
+        for (field, index) in &self.columns {
+            let value = values.get(*index).map(|value| value.trim()).filter(|value| !value.is_empty()).ok_or(ParseError::NoMatch("csv"))?;
+            match field.as_str() {
+                "time" => time = Some(parse_log_time(value)?),
+                "method" => method = Some(value),
+                "path" => path = Some(value),
+                "protocol" => protocol = Some(value),
+                "request" => log.request = value.to_string(),
+                "remote_addr" => log.remote_addr = value.to_string(),
+                "remote_user" => log.remote_user = value.to_string(),
+                "status" => log.status = parse_status(value)?,
+                "body_bytes_sent" => log.body_bytes_sent = value.parse::<f64>().map_err(|_| ParseError::invalid("body_bytes_sent", value))? as u64,
+                "http_referer" => log.http_referer = value.to_string(),
+                "http_user_agent" => log.http_user_agent = value.to_string(),
+                "request_id" => log.request_id = Some(value.to_string()).filter(|id| id != "-"),
+                _ => {
+                    log.extra.insert(field.clone(), value.to_string());
+                }
+            }
+        }
+        log.request_time = time.ok_or(ParseError::Missing("time"))?;
+        if let Some(path) = path {
+            log.request = format!("{} {} {}", method.unwrap_or("GET"), path, protocol.unwrap_or("HTTP/1.1"));
+        }
+        Ok(log)
+    }
+}
+
+fn canonical(field: &str) -> &str {
+    match field {
+        "time_local" | "time_iso8601" | "timestamp" | "@timestamp" | "date" => "time",
+        "ip" | "client_ip" => "remote_addr",
+        "user" => "remote_user",
+        "request_method" => "method",
+        "uri" | "request_uri" | "url" => "path",
+        "server_protocol" => "protocol",
+        "bytes" | "bytes_sent" => "body_bytes_sent",
+        "referer" | "referrer" => "http_referer",
+        "user_agent" | "user-agent" => "http_user_agent",
+        _ => field,
+    }
+}
+
+fn split(line: &str, delimiter: char) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => values.push(std::mem::take(&mut value)),
+            c => value.push(c),
+        }
+    }
+    values.push(value);
+    values
+}
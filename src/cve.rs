@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::NginxLog;
+
+const DEFAULT_RULES: &str = include_str!("../rules/cve-probes.txt");
+
+pub struct ProbeRule {
+    pub id: String,
+    pattern: Regex,
+}
+
+pub struct ProbeHits {
+    pub id: String,
+    pub hits: u64,
+    pub non_404: u64,
+    pub ips: HashMap<String, u64>,
+    pub statuses: HashMap<u16, u64>,
+}
+
+pub fn default_rules() -> Vec<ProbeRule> {
+    parse_rules(DEFAULT_RULES).expect("built-in CVE rules are valid")
+}
+
+pub fn load_rules<P: AsRef<Path>>(path: P) -> Result<Vec<ProbeRule>, Box<dyn Error>> {
+    let path = path.as_ref();
+    parse_rules(&fs::read_to_string(path)?).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+fn parse_rules(text: &str) -> Result<Vec<ProbeRule>, String> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (id, pattern) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("line {}: expected <id> <regex>", number + 1))?;
+        let pattern = Regex::new(pattern.trim()).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        rules.push(ProbeRule { id: id.to_string(), pattern });
+    }
+    Ok(rules)
+}
+
+impl ProbeRule {
+    pub fn matches(&self, log: &NginxLog) -> bool {
+        [&log.request, &log.http_referer, &log.http_user_agent]
+            .iter()
+            .any(|field| self.pattern.is_match(field) || (field.contains('%') && self.pattern.is_match(&percent_decode(field))))
+    }
+}
+
+pub fn cve_scan(logs: &[NginxLog], rules: &[ProbeRule]) -> Vec<ProbeHits> {
+    let mut hits: Vec<ProbeHits> = rules
+        .iter()
+        .map(|rule| ProbeHits {
+            id: rule.id.clone(),
+            hits: 0,
+            non_404: 0,
+            ips: HashMap::new(),
+            statuses: HashMap::new(),
+        })
+        .collect();
+    for log in logs {
+        for (rule, hit) in rules.iter().zip(hits.iter_mut()) {
+            if rule.matches(log) {
+                hit.hits += 1;
+                if log.status != 404 {
+                    hit.non_404 += 1;
+                }
+                *hit.ips.entry(log.client()).or_insert(0) += 1;
+                *hit.statuses.entry(log.status).or_insert(0) += 1;
+            }
+        }
+    }
+    hits.retain(|hit| hit.hits > 0);
+    hits.sort_by(|a, b| b.non_404.cmp(&a.non_404).then_with(|| b.hits.cmp(&a.hits)));
+    hits
+}
+
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                decoded.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
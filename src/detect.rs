@@ -0,0 +1,237 @@
+use std::net::IpAddr;
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::format::LogFormat;
+use crate::parser::COMBINED_FORMAT;
+
+const METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+const KEY_ALIASES: [(&str, &str); 7] = [
+    ("ja3", "ssl_ja3_hash"),
+    ("ja4", "ssl_ja4"),
+    ("rt", "request_time"),
+    ("uct", "upstream_connect_time"),
+    ("uht", "upstream_header_time"),
+    ("urt", "upstream_response_time"),
+    ("ua", "upstream_addr"),
+];
+const QUOTED_AFTER_REQUEST: [&str; 3] = ["http_referer", "http_user_agent", "http_x_forwarded_for"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bare,
+    Quoted,
+    Bracketed,
+}
+
+pub struct Detection {
+    pub parser: &'static str,
+    pub log_format: Option<String>,
+    pub fields: Vec<String>,
+    pub matched: usize,
+    pub sampled: usize,
+}
+
+#[derive(Default)]
+struct State {
+    assigned: Vec<String>,
+    ident: bool,
+    quoted_after_request: usize,
+    unknown: usize,
+}
+
+impl State {
+    fn has(&self, name: &str) -> bool {
+        self.assigned.iter().any(|assigned| assigned == name)
+    }
+
+    fn take(&mut self, name: &str) -> String {
+        self.assigned.push(name.to_string());
+        format!("${}", name)
+    }
+
+    fn unknown(&mut self) -> String {
+        self.unknown += 1;
+        let name = format!("field_{}", self.unknown);
+        self.take(&name)
+    }
+}
+
+pub fn detect_format(lines: &[String]) -> Option<Detection> {
+    let lines: Vec<&str> = lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()).collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let objects: Vec<serde_json::Map<String, Value>> = lines
+        .iter()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(Value::Object(object)) => Some(object),
+            _ => None,
+        })
+        .collect();
+    if objects.len() * 2 > lines.len() {
+        let mut fields: Vec<String> = objects.iter().flat_map(|object| object.keys().cloned()).collect();
+        fields.sort();
+        fields.dedup();
+        return Some(Detection { parser: "json", log_format: None, fields, matched: objects.len(), sampled: lines.len() });
+    }
+
+    let tokenized: Vec<Vec<(Kind, &str)>> = lines.iter().map(|line| tokenize(line)).collect();
+    let template = most_common_shape(&tokenized)?;
+    let columns: Vec<Vec<&str>> = (0..template.len())
+        .map(|index| tokenized.iter().filter(|tokens| same_shape(tokens, &template)).map(|tokens| tokens[index].1).collect())
+        .collect();
+    let mut state = State::default();
+    let mut pieces = Vec::with_capacity(template.len());
+    for (index, (kind, _)) in template.iter().enumerate() {
+        let piece = classify(*kind, &columns[index], &mut state);
+        pieces.push(match kind {
+            Kind::Bare => piece,
+            Kind::Quoted => format!("\"{}\"", piece),
+            Kind::Bracketed => format!("[{}]", piece),
+        });
+    }
+    let spec = pieces.join(" ");
+    let format = LogFormat::compile(&spec).ok()?;
+    let matched = lines.iter().filter(|line| format.parse_line(line).is_ok()).count();
+    let parser = if spec == COMBINED_FORMAT { "split" } else { "log-format" };
+    Some(Detection { parser, log_format: Some(spec), fields: state.assigned, matched, sampled: lines.len() })
+}
+
+fn tokenize(line: &str) -> Vec<(Kind, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let (kind, close) = match rest.as_bytes()[0] {
+            b'"' => (Kind::Quoted, Some('"')),
+            b'[' => (Kind::Bracketed, Some(']')),
+            _ => (Kind::Bare, None),
+        };
+        let (token, after) = match close {
+            Some(close) => match rest[1..].find(close) {
+                Some(end) => (&rest[1..end + 1], &rest[end + 2..]),
+                None => (&rest[1..], ""),
+            },
+            None => {
+                let end = rest.find(' ').unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        tokens.push((kind, token));
+        rest = after.trim_start();
+    }
+    tokens
+}
+
+fn same_shape(a: &[(Kind, &str)], b: &[(Kind, &str)]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.0 == y.0)
+}
+
+fn most_common_shape<'a>(tokenized: &[Vec<(Kind, &'a str)>]) -> Option<Vec<(Kind, &'a str)>> {
+    tokenized
+        .iter()
+        .max_by_key(|tokens| tokenized.iter().filter(|other| same_shape(tokens, other)).count())
+        .cloned()
+}
+
+fn all<F: Fn(&str) -> bool>(values: &[&str], predicate: F) -> bool {
+    let present: Vec<&&str> = values.iter().filter(|value| **value != "-" && !value.is_empty()).collect();
+    !present.is_empty() && present.iter().all(|value| predicate(value))
+}
+
+fn is_ip(value: &str) -> bool {
+    value.parse::<IpAddr>().is_ok()
+}
+
+fn is_ip_port(value: &str) -> bool {
+    value.split([',', ' ']).filter(|part| !part.is_empty()).all(|part| match part.rsplit_once(':') {
+        Some((host, port)) => port.parse::<u16>().is_ok() && host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok(),
+        None => false,
+    })
+}
+
+fn is_float(value: &str) -> bool {
+    value.contains('.') && value.split([',', ' ', ':']).filter(|part| !part.is_empty()).all(|part| part.parse::<f64>().is_ok())
+}
+
+fn is_ja3(value: &str) -> bool {
+    let parts: Vec<&str> = value.split(',').collect();
+    parts.len() == 5 && parts[0].parse::<u16>().is_ok() && parts[1..].iter().all(|part| part.split('-').all(|id| id.is_empty() || id.parse::<u16>().is_ok()))
+}
+
+fn classify(kind: Kind, values: &[&str], state: &mut State) -> String {
+    let all_dash = values.iter().all(|value| *value == "-");
+    if kind == Kind::Bracketed && all(values, |value| DateTime::parse_from_str(value, "%d/%b/%Y:%H:%M:%S %z").is_ok()) {
+        return state.take("time_local");
+    }
+    if all(values, |value| DateTime::parse_from_rfc3339(value).is_ok()) && !state.has("time_iso8601") {
+        return state.take("time_iso8601");
+    }
+    if kind == Kind::Quoted {
+        if !state.has("request") && all(values, |value| METHODS.iter().any(|method| value.starts_with(&format!("{} ", method)))) {
+            return state.take("request");
+        }
+        if state.has("request") && state.quoted_after_request < QUOTED_AFTER_REQUEST.len() {
+            let name = QUOTED_AFTER_REQUEST[state.quoted_after_request];
+            state.quoted_after_request += 1;
+            return state.take(name);
+        }
+        return state.unknown();
+    }
+    if !state.has("remote_addr") && all(values, is_ip) {
+        return state.take("remote_addr");
+    }
+    let before_time = !state.has("time_local") && !state.has("time_iso8601");
+    if state.has("remote_addr") && before_time {
+        if all_dash && !state.ident {
+            state.ident = true;
+            return String::from("-");
+        }
+        if !state.has("remote_user") {
+            return state.take("remote_user");
+        }
+    }
+    if state.has("request") && !state.has("status") && all(values, |value| value.parse::<u16>().is_ok_and(|status| (100..600).contains(&status))) {
+        return state.take("status");
+    }
+    if state.has("status") && !state.has("body_bytes_sent") && all(values, |value| value.parse::<u64>().is_ok()) {
+        return state.take("body_bytes_sent");
+    }
+    if all(values, is_ip_port) && !state.has("upstream_addr") {
+        return state.take("upstream_addr");
+    }
+    if all(values, is_float) {
+        for name in ["request_time", "upstream_response_time"] {
+            if !state.has(name) {
+                return state.take(name);
+            }
+        }
+    }
+    let hex = |value: &str| value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit());
+    if !state.has("request_id") && all(values, hex) {
+        return state.take("request_id");
+    }
+    if !state.has("ssl_ja3_hash") && all(values, hex) {
+        return state.take("ssl_ja3_hash");
+    }
+    if !state.has("ssl_ja3") && all(values, is_ja3) {
+        return state.take("ssl_ja3");
+    }
+    if let Some((key, _)) = values.iter().find(|value| **value != "-").and_then(|value| value.split_once('=')) {
+        if values.iter().all(|value| value.starts_with(&format!("{}=", key))) {
+            let inner: Vec<&str> = values.iter().map(|value| &value[key.len() + 1..]).collect();
+            let quoted = inner.iter().all(|value| value.len() >= 2 && value.starts_with('"') && value.ends_with('"'));
+            let inner: Vec<&str> = if quoted { inner.iter().map(|value| &value[1..value.len() - 1]).collect() } else { inner };
+            let piece = match KEY_ALIASES.iter().find(|(alias, _)| *alias == key) {
+                Some((_, name)) if !state.has(name) => state.take(name),
+                _ => classify(Kind::Bare, &inner, state),
+            };
+            return if quoted { format!("{}=\"{}\"", key, piece) } else { format!("{}={}", key, piece) };
+        }
+    }
+    if !state.has("host") && all(values, |value| value.contains('.') && value.chars().any(|c| c.is_ascii_alphabetic()) && !value.contains('/')) {
+        return state.take("host");
+    }
+    state.unknown()
+}
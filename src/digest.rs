@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+
+use crate::NginxLog;
+
+pub struct Incident {
+    pub template: String,
+    pub upstream: String,
+    pub status: u16,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    pub count: u64,
+}
+
+pub fn path_template(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or(path);
+    path.split('/').map(segment_template).collect::<Vec<_>>().join("/")
+}
+
+fn segment_template(segment: &str) -> &str {
+    let hex = segment.len() >= 16 && segment.chars().all(|c| c.is_ascii_hexdigit());
+    let uuid = segment.len() == 36 && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+        ":id"
+    } else if uuid {
+        ":uuid"
+    } else if hex {
+        ":hash"
+    } else {
+        segment
+    }
+}
+
+pub fn error_digest(logs: &[NginxLog], min_status: u16) -> Vec<Incident> {
+    let mut incidents: HashMap<(String, String, u16), Incident> = HashMap::new();
+    for log in logs.iter().filter(|log| log.status >= min_status) {
+        let template = path_template(log.path().unwrap_or("-"));
+        let upstream = log.extra.get("upstream_addr").cloned().unwrap_or_else(|| String::from("-"));
+        let incident = incidents.entry((template.clone(), upstream.clone(), log.status)).or_insert_with(|| Incident {
+            template,
+            upstream,
+            status: log.status,
+            first_seen: log.request_time,
+            last_seen: log.request_time,
+            count: 0,
+        });
+        incident.first_seen = incident.first_seen.min(log.request_time);
+        incident.last_seen = incident.last_seen.max(log.request_time);
+        incident.count += 1;
+    }
+    let mut incidents: Vec<Incident> = incidents.into_values().collect();
+    incidents.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.first_seen.cmp(&b.first_seen)));
+    incidents
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{in_path, NginxLog};
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+enum Segment {
+    Literal(String),
+    Param,
+    Rest,
+}
+
+struct Endpoint {
+    method: Option<String>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Default)]
+pub struct UnknownEndpoint {
+    pub method: String,
+    pub path: String,
+    pub requests: u64,
+    pub clients: HashSet<String>,
+    pub statuses: BTreeMap<u16, u64>,
+}
+
+#[derive(Default)]
+pub struct Allowlist {
+    endpoints: Vec<Endpoint>,
+}
+
+impl Allowlist {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Allowlist, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| in_path(path.display(), e))?;
+        if text.trim_start().starts_with('{') {
+            let spec: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            return Allowlist::from_openapi(&spec).map_err(|e| format!("{}: {}", path.display(), e).into());
+        }
+        let mut allowlist = Allowlist::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (method, pattern) = match line.split_once(char::is_whitespace) {
+                Some((method, pattern)) => (method, pattern.trim()),
+                None => ("*", line),
+            };
+            if !pattern.starts_with('/') {
+                return Err(format!("{}:{}: expected METHOD /path, got: {}", path.display(), number + 1, line).into());
+            }
+            allowlist.add(method, pattern);
+        }
+        Ok(allowlist)
+    }
+
+    fn from_openapi(spec: &Value) -> Result<Allowlist, String> {
+        let paths = spec.get("paths").and_then(Value::as_object).ok_or("OpenAPI spec has no paths object")?;
+        let base = spec.get("basePath").and_then(Value::as_str).unwrap_or("").trim_end_matches('/');
+        let mut allowlist = Allowlist::default();
+        for (pattern, operations) in paths {
+            let pattern = format!("{}{}", base, pattern);
+            let operations = operations.as_object().ok_or_else(|| format!("path item {} is not an object", pattern))?;
+            for method in operations.keys().filter(|method| METHODS.contains(&method.as_str())) {
+                allowlist.add(method, &pattern);
+            }
+        }
+        Ok(allowlist)
+    }
+
+    fn add(&mut self, method: &str, pattern: &str) {
+        let method = match method {
+            "*" | "ANY" | "any" => None,
+            _ => Some(method.to_ascii_uppercase()),
+        };
+        let segments = pattern
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| match segment {
+                "*" | "**" => Segment::Rest,
+                _ if segment.starts_with('{') && segment.ends_with('}') => Segment::Param,
+                _ if segment.starts_with(':') && segment.len() > 1 => Segment::Param,
+                _ => Segment::Literal(segment.to_string()),
+            })
+            .collect();
+        self.endpoints.push(Endpoint { method, segments });
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_allowed(&self, method: &str, path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or(path);
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        self.endpoints.iter().any(|endpoint| endpoint.method.as_deref().is_none_or(|allowed| allowed == method) && endpoint.matches(&parts))
+    }
+}
+
+impl Endpoint {
+    fn matches(&self, parts: &[&str]) -> bool {
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Rest => return true,
+                Segment::Param if parts.get(index).is_some_and(|part| !part.is_empty()) => {}
+                Segment::Literal(literal) if parts.get(index) == Some(&literal.as_str()) => {}
+                _ => return false,
+            }
+        }
+        parts.len() == self.segments.len()
+    }
+}
+
+pub fn unknown_endpoints(logs: &[NginxLog], allowlist: &Allowlist) -> Vec<UnknownEndpoint> {
+    let mut unknown: HashMap<(&str, &str), UnknownEndpoint> = HashMap::new();
+    for log in logs {
+        let (method, path) = (log.method().unwrap_or("-"), log.path().unwrap_or("-"));
+        if allowlist.is_allowed(method, path) {
+            continue;
+        }
+        let path = path.split('?').next().unwrap_or(path);
+        let entry = unknown.entry((method, path)).or_insert_with(|| UnknownEndpoint { method: method.to_string(), path: path.to_string(), ..Default::default() });
+        entry.requests += 1;
+        entry.clients.insert(log.client());
+        *entry.statuses.entry(log.status).or_default() += 1;
+    }
+    let mut unknown: Vec<UnknownEndpoint> = unknown.into_values().collect();
+    unknown.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| (&a.method, &a.path).cmp(&(&b.method, &b.path))));
+    unknown
+}
@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::parser::LineParser;
+use crate::NginxLog;
+
+#[derive(Default)]
+pub struct Excerpt {
+    pub lines: u64,
+    pub written: u64,
+    pub bytes: u64,
+    pub digest: Sha256,
+}
+
+impl Excerpt {
+    pub fn copy_matching<R, W, F>(&mut self, reader: &mut R, out: &mut W, parser: LineParser, mut keep: F) -> Result<(), Box<dyn Error>>
+    where
+        R: BufRead,
+        W: Write,
+        F: FnMut(NginxLog) -> Result<bool, Box<dyn Error>>,
+    {
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                return Ok(());
+            }
+            let raw = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+            let line = String::from_utf8_lossy(raw.strip_suffix(b"\r").unwrap_or(raw));
+            let line = line.trim_matches('\0');
+            if parser.skips(line) {
+                continue;
+            }
+            self.lines += 1;
+            if let Some(log) = parser.parse(line) {
+                if keep(log)? {
+                    out.write_all(&buffer)?;
+                    self.digest.update(&buffer);
+                    self.written += 1;
+                    self.bytes += buffer.len() as u64;
+                }
+            }
+        }
+    }
+
+    pub fn sha256(self) -> String {
+        self.digest.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
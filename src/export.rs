@@ -0,0 +1,118 @@
+use serde_json::{json, Value};
+
+use crate::latency::request_seconds;
+use crate::NginxLog;
+
+// Logged $http_* variables that are replayed as request headers, e.g. http_x_forwarded_for
+const HEADER_PREFIX: &str = "http_";
+
+pub fn request_url(log: &NginxLog, base_url: Option<&str>) -> Option<String> {
+    let path = log.path().filter(|path| path.starts_with('/'))?;
+    let base = match base_url {
+        Some(base) => base.trim_end_matches('/').to_string(),
+        None => {
+            let scheme = log.extra.get("scheme").map(String::as_str).unwrap_or("https");
+            format!("{}://{}", scheme, log.field("host").unwrap_or_else(|| String::from("localhost")))
+        }
+    };
+    Some(format!("{}{}", base, path))
+}
+
+pub fn request_headers(log: &NginxLog) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    for (name, value) in [("User-Agent", &log.http_user_agent), ("Referer", &log.http_referer)] {
+        let value = value.trim_matches('"');
+        if !value.is_empty() && value != "-" {
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+    let mut logged: Vec<(&String, &String)> = log.extra.iter().filter(|(name, value)| name.starts_with(HEADER_PREFIX) && name.as_str() != "http_host" && !value.is_empty() && value.as_str() != "-").collect();
+    logged.sort();
+    for (name, value) in logged {
+        headers.push((header_name(&name[HEADER_PREFIX.len()..]), value.clone()));
+    }
+    headers
+}
+
+fn header_name(variable: &str) -> String {
+    variable
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub fn curl_command(log: &NginxLog, base_url: Option<&str>) -> Option<String> {
+    let url = request_url(log, base_url)?;
+    let method = log.method().unwrap_or("GET");
+    let mut command = String::from("curl");
+    match method {
+        "GET" => {}
+        "HEAD" => command.push_str(" -I"),
+        _ => command.push_str(&format!(" -X {}", method)),
+    }
+    command.push_str(&format!(" {}", shell_quote(&url)));
+    for (name, value) in request_headers(log) {
+        command.push_str(&format!(" \\\n  -H {}", shell_quote(&format!("{}: {}", name, value))));
+    }
+    Some(command)
+}
+
+pub fn har(logs: &[NginxLog], base_url: Option<&str>) -> Value {
+    let entries: Vec<Value> = logs
+        .iter()
+        .filter_map(|log| {
+            let url = request_url(log, base_url)?;
+            let http_version = log.protocol().unwrap_or("HTTP/1.1");
+            let headers: Vec<Value> = request_headers(log).into_iter().map(|(name, value)| json!({ "name": name, "value": value })).collect();
+            let query: Vec<Value> = log
+                .query()
+                .into_iter()
+                .flat_map(|query| query.split('&'))
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect();
+            let millis = request_seconds(log).map(|seconds| seconds * 1000.0).unwrap_or(0.0);
+            Some(json!({
+                "startedDateTime": log.request_time.to_rfc3339(),
+                "time": millis,
+                "request": {
+                    "method": log.method().unwrap_or("GET"),
+                    "url": url,
+                    "httpVersion": http_version,
+                    "cookies": [],
+                    "headers": headers,
+                    "queryString": query,
+                    "headersSize": -1,
+                    "bodySize": -1,
+                },
+                "response": {
+                    "status": log.status,
+                    "statusText": "",
+                    "httpVersion": http_version,
+                    "cookies": [],
+                    "headers": [],
+                    "content": { "size": log.body_bytes_sent, "mimeType": "" },
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": log.body_bytes_sent,
+                },
+                "cache": {},
+                "timings": { "send": 0, "wait": millis, "receive": 0 },
+                "comment": format!("client {}", log.remote_addr),
+            }))
+        })
+        .collect();
+    json!({ "log": { "version": "1.2", "creator": { "name": "nginx-log-explorer", "version": env!("CARGO_PKG_VERSION") }, "entries": entries } })
+}
@@ -0,0 +1,47 @@
+use regex::Regex;
+
+use crate::NginxLog;
+
+pub struct ExtractRule {
+    name: String,
+    pattern: Regex,
+    source: String,
+}
+
+impl ExtractRule {
+    pub fn parse(spec: &str) -> Result<ExtractRule, String> {
+        let usage = || format!("invalid --extract rule: {} (expected NAME=REGEX:FIELD)", spec);
+        let (name, rest) = spec.split_once('=').ok_or_else(usage)?;
+        let (pattern, source) = rest.rsplit_once(':').ok_or_else(usage)?;
+        if name.is_empty() || source.is_empty() {
+            return Err(usage());
+        }
+        let pattern = Regex::new(pattern).map_err(|e| format!("invalid --extract regex for {}: {}", name, e))?;
+        Ok(ExtractRule {
+            name: name.to_string(),
+            pattern,
+            source: source.to_string(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} from {} matching /{}/", self.name, self.source, self.pattern)
+    }
+
+    pub fn apply(&self, log: &mut NginxLog) {
+        let source = match log.field(&self.source) {
+            Some(source) => source,
+            None => return,
+        };
+        if let Some(captures) = self.pattern.captures(&source) {
+            let value = captures
+                .name("value")
+                .or_else(|| captures.iter().skip(1).flatten().next())
+                .or_else(|| captures.get(0))
+                .map(|m| m.as_str().to_string());
+            if let Some(value) = value {
+                log.extra.insert(self.name.clone(), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::aggregate::GroupField;
+use crate::{in_path, NginxLog};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub by: String,
+    pub first_seen: BTreeMap<String, DateTime<Local>>,
+}
+
+pub struct Newcomer {
+    pub value: String,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    pub requests: u64,
+    pub paths: HashMap<String, u64>,
+}
+
+impl Newcomer {
+    pub fn top_path(&self) -> Option<(&str, u64)> {
+        self.paths.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0))).map(|(path, count)| (path.as_str(), *count))
+    }
+}
+
+impl Baseline {
+    pub fn load_or_default(path: &str, by: &str) -> Result<Baseline, Box<dyn Error>> {
+        if !Path::new(path).exists() {
+            return Ok(Baseline { by: by.to_string(), ..Default::default() });
+        }
+        let text = fs::read_to_string(path).map_err(|e| in_path(path, e))?;
+        let baseline: Baseline = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        if baseline.by != by {
+            return Err(format!("{}: baseline was recorded with --by {}, not {}", path, baseline.by, by).into());
+        }
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, serde_json::to_string_pretty(self)?).map_err(|e| in_path(&temporary, e))?;
+        fs::rename(&temporary, path).map_err(|e| in_path(path, e))?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, logs: &[NginxLog], by: &GroupField) {
+        for log in logs {
+            let first_seen = self.first_seen.entry(by.key(log)).or_insert(log.request_time);
+            *first_seen = (*first_seen).min(log.request_time);
+        }
+    }
+
+    pub fn knows(&self, value: &str) -> bool {
+        self.first_seen.contains_key(value)
+    }
+}
+
+pub fn newcomers(window: &[NginxLog], by: &GroupField, known: &Baseline) -> Vec<Newcomer> {
+    let mut newcomers: HashMap<String, Newcomer> = HashMap::new();
+    for log in window {
+        let value = by.key(log);
+        if known.knows(&value) {
+            continue;
+        }
+        let newcomer = newcomers.entry(value.clone()).or_insert_with(|| Newcomer { value, first_seen: log.request_time, last_seen: log.request_time, requests: 0, paths: HashMap::new() });
+        newcomer.first_seen = newcomer.first_seen.min(log.request_time);
+        newcomer.last_seen = newcomer.last_seen.max(log.request_time);
+        newcomer.requests += 1;
+        *newcomer.paths.entry(log.path().unwrap_or("-").to_string()).or_default() += 1;
+    }
+    let mut newcomers: Vec<Newcomer> = newcomers.into_values().collect();
+    newcomers.sort_by(|a, b| a.first_seen.cmp(&b.first_seen).then_with(|| a.value.cmp(&b.value)));
+    newcomers
+}
@@ -0,0 +1,66 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+const READ_SIZE: usize = 64 * 1024;
+
+pub struct Tail {
+    path: PathBuf,
+    file: File,
+    offset: u64,
+    pending: Vec<u8>,
+}
+
+impl Tail {
+    pub fn open<P: AsRef<Path>>(path: P, from_start: bool) -> io::Result<Tail> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let offset = if from_start { 0 } else { file.seek(SeekFrom::End(0))? };
+        Ok(Tail { path, file, offset, pending: Vec::new() })
+    }
+
+    pub fn poll(&mut self) -> io::Result<Vec<String>> {
+        let len = fs::metadata(&self.path)?.len();
+        if len < self.offset {
+            debug!("stage=follow file={} reason=truncated reopening", self.path.display());
+            self.file = File::open(&self.path)?;
+            self.offset = 0;
+            self.pending.clear();
+        }
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut lines = Vec::new();
+        let mut buffer = vec![0; READ_SIZE];
+        loop {
+            let read = self.file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            self.offset += read as u64;
+            for &byte in &buffer[..read] {
+                self.push(byte, &mut lines);
+            }
+        }
+        Ok(lines)
+    }
+
+    fn push(&mut self, byte: u8, lines: &mut Vec<String>) {
+        match byte {
+            0 => {
+                if !self.pending.is_empty() {
+                    debug!("stage=follow file={} reason=nul-padding dropped_partial_bytes={}", self.path.display(), self.pending.len());
+                }
+                self.pending.clear();
+            }
+            b'\n' => {
+                let line = String::from_utf8_lossy(&self.pending).trim_end_matches('\r').to_string();
+                self.pending.clear();
+                if !line.trim().is_empty() {
+                    lines.push(line);
+                }
+            }
+            _ => self.pending.push(byte),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use regex::Regex;
+
+use crate::parser::{is_extra_field, parse_log_time, parse_status, unescape, ParseError};
+use crate::NginxLog;
+
+pub struct LogFormat {
+    regex: Regex,
+    extra: Vec<String>,
+}
+
+impl LogFormat {
+    pub fn compile(spec: &str) -> Result<LogFormat, String> {
+        let mut pattern = String::from("^");
+        let mut names: Vec<String> = Vec::new();
+        let mut rest = spec;
+        while let Some(pos) = rest.find('$') {
+            pattern.push_str(&regex::escape(&rest[..pos]));
+            let (name, after) = split_variable(&rest[pos + 1..]);
+            if name.is_empty() {
+                pattern.push_str(&regex::escape("$"));
+                rest = after;
+                continue;
+            }
+            let value = match after.chars().next() {
+                // nginx escapes " inside quoted variables, other writers use \"
+                Some('"') => String::from(r#"(?:[^"\\]|\\.)*"#),
+                Some(delimiter) => format!("[^{}]*", regex::escape(&delimiter.to_string())),
+                None => String::from(".*"),
+            };
+            if names.iter().any(|seen| seen == name) {
+                pattern.push_str(&format!("(?:{})", value));
+            } else {
+                pattern.push_str(&format!("(?P<{}>{})", name, value));
+                names.push(name.to_string());
+            }
+            rest = after;
+        }
+        pattern.push_str(&regex::escape(rest));
+        if !names.iter().any(|name| name == "time_local" || name == "time_iso8601" || name == "msec") {
+            return Err(String::from("log format must contain $time_local, $time_iso8601 or $msec"));
+        }
+        let regex = Regex::new(&pattern).map_err(|e| format!("invalid log format: {}", e))?;
+        let extra = names.into_iter().filter(|name| is_extra_field(name)).collect();
+        Ok(LogFormat { regex, extra })
+    }
+
+    pub fn parse_line(&self, line: &str) -> Result<NginxLog, ParseError> {
+        let captures = self.regex.captures(line).ok_or(ParseError::NoMatch("log-format"))?;
+        let field = |name: &str| captures.name(name).map(|m| m.as_str()).unwrap_or("-").to_string();
+        let time = ["time_local", "time_iso8601", "msec"].iter().find_map(|name| captures.name(name)).ok_or(ParseError::Missing("time"))?;
+        let request_time = parse_log_time(time.as_str())?;
+        // A format without $status logs none, but a logged one has to be a status code
+        let status = captures.name("status").map(|m| parse_status(m.as_str())).transpose()?.unwrap_or(0);
+        let request_id = ["request_id", "http_x_request_id"]
+            .iter()
+            .filter_map(|name| captures.name(name))
+            .map(|m| m.as_str())
+            .find(|value| !value.is_empty() && *value != "-")
+            .map(String::from);
+        let extra = self
+            .extra
+            .iter()
+            .filter_map(|name| Some((name.clone(), unescape(captures.name(name)?.as_str()).into_owned())))
+            .filter(|(_, value)| !value.is_empty() && value != "-")
+            .collect();
+        Ok(NginxLog {
+            remote_addr: field("remote_addr"),
+            remote_user: field("remote_user"),
+            request_time,
+            request: field("request"),
+            status,
+            body_bytes_sent: field("body_bytes_sent").parse().unwrap_or(0),
+            http_referer: unescape(&field("http_referer")).into_owned(),
+            http_user_agent: unescape(&field("http_user_agent")).into_owned(),
+            request_id,
+            extra,
+            ..Default::default()
+        })
+    }
+}
+
+fn split_variable(text: &str) -> (&str, &str) {
+    if let Some(braced) = text.strip_prefix('{') {
+        if let Some(end) = braced.find('}') {
+            return (&braced[..end], &braced[end + 1..]);
+        }
+    }
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
@@ -0,0 +1,46 @@
+use std::sync::OnceLock;
+
+use crate::csv_input::CsvFormat;
+use crate::format::LogFormat;
+use crate::parser::{LineParser, COMBINED_FORMAT};
+use crate::w3c::W3cFormat;
+
+struct Formats {
+    format: LogFormat,
+    csv: CsvFormat,
+    iis: W3cFormat,
+    cloudfront: W3cFormat,
+}
+
+fn formats() -> &'static Formats {
+    static FORMATS: OnceLock<Formats> = OnceLock::new();
+    FORMATS.get_or_init(|| Formats {
+        format: LogFormat::compile(COMBINED_FORMAT).unwrap(),
+        csv: CsvFormat::compile(&["time=1,remote_addr=2,method=3,path=4,status=5,body_bytes_sent=6"], ',', None).unwrap(),
+        iis: W3cFormat::iis(None).unwrap(),
+        cloudfront: W3cFormat::cloudfront(None).unwrap(),
+    })
+}
+
+// Runs every line parser over each line of the input, as the reader would; any panic is a bug
+pub fn parse_all(data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    let formats = formats();
+    let parsers = [
+        LineParser::Regex,
+        LineParser::Split,
+        LineParser::Json,
+        LineParser::Alb,
+        LineParser::Format(&formats.format),
+        LineParser::Csv(&formats.csv),
+        LineParser::W3c(&formats.iis),
+        LineParser::W3c(&formats.cloudfront),
+    ];
+    for line in text.lines() {
+        for parser in &parsers {
+            if !parser.skips(line) {
+                let _ = parser.parse_line(line);
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::io::{self, Write};
+
+use chrono::{DateTime, Duration, Local, Timelike};
+
+use crate::parser::COMBINED_FORMAT;
+
+const USER_AGENTS: [&str; 8] = [
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0",
+    "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36",
+    "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+    "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)",
+    "curl/8.5.0",
+];
+const SECTIONS: [&str; 6] = ["products", "blog", "api/v1/users", "api/v1/orders", "category", "search"];
+const ASSETS: [&str; 4] = ["js/app", "css/main", "img/hero", "fonts/inter"];
+const ASSET_EXTENSIONS: [&str; 4] = ["js", "css", "webp", "woff2"];
+
+pub struct Profile {
+    pub lines: u64,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub paths: usize,
+    pub clients: usize,
+    // fractions of all responses, e.g. 0.02
+    pub error_rate: f64,
+    pub client_error_rate: f64,
+}
+
+// xorshift64*; deterministic for a given --seed so generated files can be reproduced
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.unit() * n as f64) as usize % n.max(1)
+    }
+
+    // Heavily skewed towards low indexes, like the popularity of pages and clients
+    fn popular(&mut self, n: usize) -> usize {
+        ((n as f64 + 1.0).powf(self.unit()) - 1.0) as usize % n.max(1)
+    }
+
+    fn exponential(&mut self, mean: f64) -> f64 {
+        -mean * (1.0 - self.unit()).ln()
+    }
+}
+
+pub fn parse_count(spec: &str) -> Result<u64, String> {
+    let (number, scale) = match spec.chars().last() {
+        Some('k' | 'K') => (&spec[..spec.len() - 1], 1_000.0),
+        Some('m' | 'M') => (&spec[..spec.len() - 1], 1_000_000.0),
+        Some('g' | 'G') => (&spec[..spec.len() - 1], 1_000_000_000.0),
+        _ => (spec, 1.0),
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok((value * scale).round() as u64),
+        _ => Err(format!("invalid count: {} (expected e.g. 5000, 10k or 1M)", spec)),
+    }
+}
+
+pub fn parse_rate(spec: &str) -> Result<f64, String> {
+    match spec.trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+        _ => Err(format!("invalid percentage: {} (expected e.g. 2%)", spec)),
+    }
+}
+
+// Resolves combined and json to a template; anything else must be an nginx log_format string
+pub fn template(format: &str) -> Result<Option<String>, String> {
+    match format {
+        "combined" => Ok(Some(COMBINED_FORMAT.to_string())),
+        "json" => Ok(None),
+        _ if format.contains('$') => Ok(Some(format.to_string())),
+        _ => Err(format!("unknown format: {} (expected combined, json or a log_format string)", format)),
+    }
+}
+
+fn path(rng: &mut Rng, index: usize) -> String {
+    match index {
+        0 => String::from("/"),
+        1 => String::from("/login"),
+        _ if index % 5 == 2 => format!("/static/{}.{:08x}.{}", ASSETS[index % ASSETS.len()], index * 2_654_435_761 % 0xffff_ffff, ASSET_EXTENSIONS[index % ASSETS.len()]),
+        _ if index % 7 == 3 => format!("/search?q=term{}&page={}", index, rng.below(5) + 1),
+        _ => format!("/{}/{}", SECTIONS[index % SECTIONS.len()], index),
+    }
+}
+
+fn client(index: usize) -> String {
+    let hash = (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    format!("{}.{}.{}.{}", 11 + hash % 200, (hash >> 8) % 256, (hash >> 16) % 256, 1 + (hash >> 24) % 254)
+}
+
+fn status(rng: &mut Rng, profile: &Profile, asset: bool) -> u16 {
+    let roll = rng.unit();
+    if roll < profile.error_rate {
+        [500, 502, 503, 504][rng.below(4)]
+    } else if roll < profile.error_rate + profile.client_error_rate {
+        [404, 404, 404, 403, 400, 401, 429][rng.below(7)]
+    } else if asset && rng.unit() < 0.3 {
+        304
+    } else if rng.unit() < 0.02 {
+        301
+    } else {
+        200
+    }
+}
+
+// Relative traffic over the day: quiet at night, busiest in the afternoon
+fn daily_load(time: DateTime<Local>) -> f64 {
+    let hour = f64::from(time.hour()) + f64::from(time.minute()) / 60.0;
+    1.0 + 0.7 * (2.0 * PI * (hour - 9.0) / 24.0).sin()
+}
+
+pub fn entry(rng: &mut Rng, profile: &Profile, time: DateTime<Local>) -> BTreeMap<&'static str, String> {
+    let path_index = rng.popular(profile.paths);
+    let uri = path(rng, path_index);
+    let asset = uri.starts_with("/static/");
+    let method = match rng.unit() {
+        roll if roll < 0.9 || asset => "GET",
+        roll if roll < 0.98 => "POST",
+        _ => "HEAD",
+    };
+    let status = status(rng, profile, asset);
+    let bytes = match status {
+        304 | 301 => 0,
+        _ if method == "HEAD" => 0,
+        _ if status >= 400 => 150 + rng.below(400) as u64,
+        _ if asset => 20_000 + rng.exponential(80_000.0) as u64,
+        _ => 2_000 + rng.exponential(15_000.0) as u64,
+    };
+    let seconds = if asset { rng.exponential(0.005) } else { rng.exponential(0.08) } * if status >= 500 { 20.0 } else { 1.0 };
+    let referer = if path_index > 0 && rng.unit() < 0.6 {
+        let from = rng.popular(profile.paths);
+        format!("https://example.com{}", path(rng, from))
+    } else {
+        String::from("-")
+    };
+    let user_agent = USER_AGENTS[rng.popular(USER_AGENTS.len())];
+    let mut fields = BTreeMap::new();
+    fields.insert("remote_addr", client(rng.popular(profile.clients)));
+    fields.insert("remote_user", String::from("-"));
+    fields.insert("time_local", time.format("%d/%b/%Y:%H:%M:%S %z").to_string());
+    fields.insert("time_iso8601", time.to_rfc3339());
+    fields.insert("request", format!("{} {} HTTP/1.1", method, uri));
+    fields.insert("request_method", method.to_string());
+    fields.insert("request_uri", uri);
+    fields.insert("server_protocol", String::from("HTTP/1.1"));
+    fields.insert("status", status.to_string());
+    fields.insert("body_bytes_sent", bytes.to_string());
+    fields.insert("bytes_sent", (bytes + 250).to_string());
+    fields.insert("http_referer", referer);
+    fields.insert("http_user_agent", user_agent.to_string());
+    fields.insert("request_time", format!("{:.3}", seconds));
+    fields.insert("upstream_response_time", if asset { String::from("-") } else { format!("{:.3}", seconds * 0.95) });
+    fields.insert("request_id", format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64()));
+    fields.insert("host", String::from("example.com"));
+    fields
+}
+
+fn render(template: &str, fields: &BTreeMap<&'static str, String>) -> String {
+    let mut line = String::with_capacity(template.len() * 2);
+    let mut rest = template;
+    while let Some(index) = rest.find('$') {
+        line.push_str(&rest[..index]);
+        let name_len = rest[index + 1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len() - index - 1);
+        let name = &rest[index + 1..index + 1 + name_len];
+        line.push_str(fields.get(name).map(String::as_str).unwrap_or("-"));
+        rest = &rest[index + 1 + name_len..];
+    }
+    line.push_str(rest);
+    line
+}
+
+pub fn generate<W: Write>(out: &mut W, profile: &Profile, template: Option<&str>, seed: u64) -> io::Result<()> {
+    let mut rng = Rng::new(seed);
+    let span = (profile.end - profile.start).num_milliseconds().max(0) as f64;
+    let mean_gap = span / profile.lines.max(1) as f64;
+    let mut time = profile.start;
+    for _ in 0..profile.lines {
+        let fields = entry(&mut rng, profile, time);
+        match template {
+            Some(template) => writeln!(out, "{}", render(template, &fields))?,
+            None => writeln!(out, "{}", serde_json::to_string(&fields)?)?,
+        }
+        time = (time + Duration::milliseconds(rng.exponential(mean_gap / daily_load(time)) as i64)).min(profile.end);
+    }
+    Ok(())
+}
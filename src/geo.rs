@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::IpAddr;
+use std::path::Path;
+
+use maxminddb::{geoip2, Reader};
+use serde_json::json;
+
+use crate::NginxLog;
+
+pub struct GeoDb {
+    reader: Reader<Vec<u8>>,
+}
+
+pub struct GeoLocation {
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoLevel {
+    Country,
+    City,
+}
+
+pub struct GeoCount {
+    pub key: String,
+    pub country: String,
+    pub city: Option<String>,
+    pub count: u64,
+    latitude_sum: f64,
+    longitude_sum: f64,
+    located: u64,
+}
+
+impl GeoDb {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<GeoDb, Box<dyn Error>> {
+        Ok(GeoDb { reader: Reader::open_readfile(path)? })
+    }
+
+    pub fn lookup(&self, addr: &str) -> Option<GeoLocation> {
+        let ip: IpAddr = addr.parse().ok()?;
+        let city: geoip2::City = self.reader.lookup(ip).ok()?;
+        let english = |names: Option<std::collections::BTreeMap<&str, &str>>| names.and_then(|names| names.get("en").map(|name| name.to_string()));
+        Some(GeoLocation {
+            country: city.country.and_then(|country| country.iso_code.map(String::from)),
+            city: city.city.and_then(|city| english(city.names)),
+            latitude: city.location.as_ref().and_then(|location| location.latitude),
+            longitude: city.location.as_ref().and_then(|location| location.longitude),
+        })
+    }
+}
+
+impl GeoCount {
+    pub fn centroid(&self) -> Option<(f64, f64)> {
+        if self.located == 0 {
+            return None;
+        }
+        Some((self.longitude_sum / self.located as f64, self.latitude_sum / self.located as f64))
+    }
+}
+
+pub fn geo_counts(logs: &[NginxLog], db: &GeoDb, level: GeoLevel) -> Vec<GeoCount> {
+    let mut cache: HashMap<&str, Option<GeoLocation>> = HashMap::new();
+    let mut counts: HashMap<String, GeoCount> = HashMap::new();
+    for log in logs {
+        let location = cache.entry(&log.remote_addr).or_insert_with(|| db.lookup(&log.remote_addr));
+        let location = match location {
+            Some(location) => location,
+            None => continue,
+        };
+        let country = location.country.clone().unwrap_or_else(|| String::from("-"));
+        let city = match level {
+            GeoLevel::Country => None,
+            GeoLevel::City => Some(location.city.clone().unwrap_or_else(|| String::from("-"))),
+        };
+        let key = match &city {
+            Some(city) => format!("{}/{}", country, city),
+            None => country.clone(),
+        };
+        let entry = counts.entry(key.clone()).or_insert_with(|| GeoCount {
+            key,
+            country,
+            city,
+            count: 0,
+            latitude_sum: 0.0,
+            longitude_sum: 0.0,
+            located: 0,
+        });
+        entry.count += 1;
+        if let (Some(latitude), Some(longitude)) = (location.latitude, location.longitude) {
+            entry.latitude_sum += latitude;
+            entry.longitude_sum += longitude;
+            entry.located += 1;
+        }
+    }
+    let mut counts: Vec<GeoCount> = counts.into_values().collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    counts
+}
+
+pub fn write_geojson<P: AsRef<Path>>(path: P, counts: &[GeoCount]) -> Result<(), Box<dyn Error>> {
+    let features: Vec<_> = counts
+        .iter()
+        .map(|count| {
+            let geometry = match count.centroid() {
+                Some((longitude, latitude)) => json!({ "type": "Point", "coordinates": [longitude, latitude] }),
+                None => serde_json::Value::Null,
+            };
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "country": count.country,
+                    "city": count.city,
+                    "requests": count.count,
+                },
+            })
+        })
+        .collect();
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &collection)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::digest::path_template;
+use crate::NginxLog;
+
+pub fn referer_host_path(referer: &str) -> Option<(&str, &str)> {
+    let rest = referer.strip_prefix("https://").or_else(|| referer.strip_prefix("http://"))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    Some((host.split(':').next().unwrap_or(host), path))
+}
+
+pub fn navigation_edges(logs: &[NginxLog], sites: &[String]) -> HashMap<(String, String), u64> {
+    let fallback = if sites.is_empty() { most_common_referer_host(logs) } else { None };
+    let mut edges: HashMap<(String, String), u64> = HashMap::new();
+    for log in logs {
+        let (host, from) = match referer_host_path(log.http_referer.trim_matches('"')) {
+            Some(referer) => referer,
+            None => continue,
+        };
+        let internal = if !sites.is_empty() {
+            sites.iter().any(|site| site.eq_ignore_ascii_case(host))
+        } else {
+            match log.field("host") {
+                Some(own) => own.split(':').next().unwrap_or(&own).eq_ignore_ascii_case(host),
+                None => fallback.as_deref() == Some(host),
+            }
+        };
+        let to = match log.path() {
+            Some(path) if internal => path,
+            _ => continue,
+        };
+        *edges.entry((path_template(from), path_template(to))).or_default() += 1;
+    }
+    edges
+}
+
+pub fn most_common_referer_host(logs: &[NginxLog]) -> Option<String> {
+    let mut hosts: HashMap<&str, u64> = HashMap::new();
+    for log in logs {
+        if let Some((host, _)) = referer_host_path(log.http_referer.trim_matches('"')) {
+            *hosts.entry(host).or_default() += 1;
+        }
+    }
+    hosts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0))).map(|(host, _)| host.to_string())
+}
+
+pub fn write_dot<W: Write>(out: &mut W, edges: &[((String, String), u64)]) -> io::Result<()> {
+    let max = edges.iter().map(|(_, count)| *count).max().unwrap_or(1) as f64;
+    writeln!(out, "digraph navigation {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+    writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+    for ((from, to), count) in edges {
+        let width = 1.0 + 7.0 * *count as f64 / max;
+        writeln!(out, "    {} -> {} [label=\"{}\", penwidth={:.1}];", quote(from), quote(to), count, width)?;
+    }
+    writeln!(out, "}}")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,77 @@
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use log::{debug, info};
+use serde_json::{json, Value};
+
+// What a long-running mode reports about itself at /healthz
+pub struct Health {
+    started: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    lines: u64,
+    lines_per_second: f64,
+    newest: Option<DateTime<Local>>,
+}
+
+impl Health {
+    pub fn start() -> Health {
+        Health { started: Instant::now(), state: Mutex::new(State::default()) }
+    }
+
+    // `elapsed` is the time spent reading the lines, or since the previous poll when following
+    pub fn record(&self, lines: usize, elapsed: Duration, newest: Option<DateTime<Local>>) {
+        let mut state = self.state.lock().unwrap();
+        state.lines += lines as u64;
+        state.lines_per_second = lines as f64 / elapsed.as_secs_f64().max(0.001);
+        state.newest = state.newest.max(newest);
+    }
+
+    // The lag is the age of the newest entry read, so it also grows while the log is quiet
+    pub fn json(&self) -> Value {
+        let state = self.state.lock().unwrap();
+        json!({
+            "status": "ok",
+            "uptime_seconds": self.started.elapsed().as_secs(),
+            "lines_parsed": state.lines,
+            "lines_per_second": (state.lines_per_second * 10.0).round() / 10.0,
+            "newest_entry": state.newest.map(|newest| newest.to_rfc3339()),
+            "lag_seconds": state.newest.map(|newest| (Local::now() - newest).num_seconds().max(0)),
+            "resident_memory_bytes": resident_memory(),
+        })
+    }
+}
+
+// VmRSS from /proc, so only known on Linux
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse::<u64>().ok().map(|kib| kib * 1024)
+}
+
+// Answers /healthz in the background for follow and daemon, which have no HTTP server of their own
+pub fn spawn(address: &str, health: Arc<Health>) -> Result<(), Box<dyn Error>> {
+    let server = tiny_http::Server::http(address).map_err(|e| format!("{}: {}", address, e))?;
+    info!("stage=health address={}", address);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let (status, body) = match request.url() {
+                "/healthz" => (200, health.json()),
+                url => (404, json!({ "error": format!("unknown endpoint: {} (expected /healthz)", url) })),
+            };
+            let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+            let response = tiny_http::Response::from_string(body.to_string()).with_status_code(status).with_header(header);
+            if let Err(e) = request.respond(response) {
+                debug!("stage=health reason={}", e);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::interval::{Alignment, Interval};
+use crate::NginxLog;
+
+// Set on entries read from a subdirectory of the log directory, e.g. web1/access.log
+pub const HOST_FIELD: &str = "server";
+
+pub struct HostStats {
+    pub host: String,
+    pub requests: u64,
+    pub bytes: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub clients: usize,
+}
+
+impl HostStats {
+    pub fn rate(&self, count: u64) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            count as f64 / self.requests as f64 * 100.0
+        }
+    }
+}
+
+pub fn host_dirs(path: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with('.') && entry.file_type()?.is_dir() {
+            dirs.push((name, entry.path()));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+pub fn host_of(log: &NginxLog) -> &str {
+    log.extra.get(HOST_FIELD).map(String::as_str).unwrap_or("-")
+}
+
+pub fn host_stats(logs: &[NginxLog]) -> Vec<HostStats> {
+    let mut hosts: HashMap<&str, (HostStats, HashSet<&str>)> = HashMap::new();
+    for log in logs {
+        let host = host_of(log);
+        let (stats, clients) = hosts.entry(host).or_insert_with(|| {
+            (HostStats { host: host.to_string(), requests: 0, bytes: 0, client_errors: 0, server_errors: 0, clients: 0 }, HashSet::new())
+        });
+        stats.requests += 1;
+        stats.bytes += log.body_bytes_sent;
+        stats.client_errors += u64::from((400..500).contains(&log.status));
+        stats.server_errors += u64::from(log.status >= 500);
+        clients.insert(&log.remote_addr);
+    }
+    let mut hosts: Vec<HostStats> = hosts
+        .into_values()
+        .map(|(mut stats, clients)| {
+            stats.clients = clients.len();
+            stats
+        })
+        .collect();
+    hosts.sort_by(|a, b| a.host.cmp(&b.host));
+    hosts
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct HostBucket {
+    pub requests: u64,
+    pub errors: u64,
+}
+
+impl HostBucket {
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64 * 100.0
+        }
+    }
+}
+
+pub fn upstream_of(log: &NginxLog) -> String {
+    // with retries nginx lists every upstream tried; the last one served the response
+    let addrs = log.field("upstream_addr").unwrap_or_default();
+    match addrs.rsplit(',').next().map(str::trim) {
+        Some(addr) if !addr.is_empty() => addr.to_string(),
+        _ => String::from("-"),
+    }
+}
+
+pub fn balance_by<F>(logs: &[NginxLog], interval: Interval, align: Alignment, key: F) -> BTreeMap<DateTime<Local>, BTreeMap<String, HostBucket>>
+where
+    F: Fn(&NginxLog) -> String,
+{
+    let mut periods: BTreeMap<DateTime<Local>, BTreeMap<String, HostBucket>> = BTreeMap::new();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return periods,
+    };
+    for log in logs {
+        let bucket = periods.entry(interval.bucket_start(log.request_time, align, origin)).or_default().entry(key(log)).or_default();
+        bucket.requests += 1;
+        bucket.errors += u64::from(log.status >= 500);
+    }
+    periods
+}
+
+// Busiest host's requests relative to an even split; 1.0 is perfectly balanced
+pub fn skew(hosts: &BTreeMap<String, HostBucket>) -> Option<f64> {
+    let total: u64 = hosts.values().map(|bucket| bucket.requests).sum();
+    let busiest = hosts.values().map(|bucket| bucket.requests).max()?;
+    if hosts.len() < 2 || total == 0 {
+        return None;
+    }
+    Some(busiest as f64 * hosts.len() as f64 / total as f64)
+}
+
+// Hosts whose 5xx rate is `factor` times that of all other hosts together
+pub fn misbehaving(hosts: &BTreeMap<String, HostBucket>, factor: f64, min_errors: u64) -> Vec<&str> {
+    let total = hosts.values().fold(HostBucket::default(), |sum, bucket| HostBucket { requests: sum.requests + bucket.requests, errors: sum.errors + bucket.errors });
+    hosts
+        .iter()
+        .filter(|(_, bucket)| bucket.errors >= min_errors)
+        .filter(|(_, bucket)| {
+            let rest = HostBucket { requests: total.requests - bucket.requests, errors: total.errors - bucket.errors };
+            rest.requests > 0 && bucket.error_rate() > rest.error_rate() * factor
+        })
+        .map(|(host, _)| host.as_str())
+        .collect()
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::graph::{most_common_referer_host, referer_host_path};
+use crate::NginxLog;
+
+const MEDIA_EXTENSIONS: [&str; 16] = ["jpg", "jpeg", "png", "gif", "webp", "avif", "svg", "bmp", "ico", "mp4", "webm", "mov", "m4v", "mp3", "ogg", "pdf"];
+
+#[derive(Default)]
+pub struct Hotlink {
+    pub key: String,
+    pub requests: u64,
+    pub bytes: u64,
+    // assets per site, sites per asset
+    pub distinct: usize,
+}
+
+#[derive(Default)]
+pub struct Hotlinks {
+    pub requests: u64,
+    pub bytes: u64,
+    pub sites: Vec<Hotlink>,
+    pub assets: Vec<Hotlink>,
+    pub extensions: BTreeSet<String>,
+}
+
+pub fn media_extension(path: &str) -> Option<String> {
+    let path = path.split('?').next().unwrap_or(path);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    if MEDIA_EXTENSIONS.contains(&extension.as_str()) {
+        Some(extension)
+    } else {
+        None
+    }
+}
+
+// --site values, else every logged host, else the most common referer host
+pub fn own_sites(logs: &[NginxLog], sites: &[String]) -> Vec<String> {
+    if !sites.is_empty() {
+        return sites.iter().map(|site| site.to_ascii_lowercase()).collect();
+    }
+    let logged: BTreeSet<String> = logs.iter().filter_map(|log| log.field("host")).map(|host| host.split(':').next().unwrap_or(&host).to_ascii_lowercase()).filter(|host| !host.is_empty() && host != "-").collect();
+    if logged.is_empty() {
+        most_common_referer_host(logs).into_iter().collect()
+    } else {
+        logged.into_iter().collect()
+    }
+}
+
+// www.example.com, example.com and cdn.example.com all belong to example.com
+fn is_own(host: &str, own: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    own.iter().any(|site| {
+        let site = site.strip_prefix("www.").unwrap_or(site);
+        host == site || host.ends_with(&format!(".{}", site))
+    })
+}
+
+pub fn hotlinks(logs: &[NginxLog], own: &[String]) -> Hotlinks {
+    let mut report = Hotlinks::default();
+    let mut sites: HashMap<&str, (Hotlink, HashSet<&str>)> = HashMap::new();
+    let mut assets: HashMap<&str, (Hotlink, HashSet<&str>)> = HashMap::new();
+    for log in logs.iter().filter(|log| log.status < 400) {
+        let path = match log.path() {
+            Some(path) => path.split('?').next().unwrap_or(path),
+            None => continue,
+        };
+        let extension = match media_extension(path) {
+            Some(extension) => extension,
+            None => continue,
+        };
+        let site = match referer_host_path(log.http_referer.trim_matches('"')) {
+            Some((host, _)) if !is_own(host, own) => host,
+            _ => continue,
+        };
+        report.requests += 1;
+        report.bytes += log.body_bytes_sent;
+        report.extensions.insert(extension);
+        for (key, other, totals) in [(site, path, &mut sites), (path, site, &mut assets)] {
+            let (entry, others) = totals.entry(key).or_insert_with(|| (Hotlink { key: key.to_string(), ..Default::default() }, HashSet::new()));
+            entry.requests += 1;
+            entry.bytes += log.body_bytes_sent;
+            others.insert(other);
+        }
+    }
+    report.sites = ranked(sites);
+    report.assets = ranked(assets);
+    report
+}
+
+fn ranked(totals: HashMap<&str, (Hotlink, HashSet<&str>)>) -> Vec<Hotlink> {
+    let mut ranked: Vec<Hotlink> = totals.into_values().map(|(hotlink, others)| Hotlink { distinct: others.len(), ..hotlink }).collect();
+    ranked.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.key.cmp(&b.key)));
+    ranked
+}
+
+// Allows direct requests, referers stripped by proxies and the own sites; everything else gets a 403
+pub fn valid_referers_config(own: &[String], extensions: &BTreeSet<String>) -> String {
+    let mut names: Vec<String> = Vec::new();
+    for site in own {
+        let site = site.strip_prefix("www.").unwrap_or(site);
+        names.push(site.to_string());
+        names.push(format!("*.{}", site));
+    }
+    let extensions: Vec<&str> = extensions.iter().map(String::as_str).collect();
+    let mut config = format!("location ~* \\.({})$ {{\n", extensions.join("|"));
+    config.push_str(&format!("    valid_referers none blocked server_names {};\n", names.join(" ")));
+    config.push_str("    if ($invalid_referer) {\n        return 403;\n    }\n}\n");
+    config
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::NginxLog;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    Seconds(i64),
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Calendar,
+    First,
+}
+
+impl Interval {
+    pub fn parse(spec: &str) -> Result<Interval, String> {
+        let spec = spec.trim();
+        let split = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+        let (count, unit) = spec.split_at(split);
+        let count = if count.is_empty() {
+            1
+        } else {
+            count.parse::<u32>().map_err(|_| format!("invalid interval: {}", spec))?
+        };
+        if count == 0 {
+            return Err(format!("interval must be greater than zero: {}", spec));
+        }
+        match unit {
+            "s" => Ok(Interval::Seconds(count as i64)),
+            "m" => Ok(Interval::Seconds(count as i64 * 60)),
+            "h" => Ok(Interval::Seconds(count as i64 * 3600)),
+            "d" => Ok(Interval::Days(count)),
+            "w" => Ok(Interval::Weeks(count)),
+            "mo" => Ok(Interval::Months(count)),
+            _ => Err(format!("unknown interval unit in {} (expected s, m, h, d, w or mo)", spec)),
+        }
+    }
+
+    // Fixed length of the interval; months vary and have none
+    pub fn duration(&self) -> Option<Duration> {
+        match *self {
+            Interval::Seconds(n) => Some(Duration::seconds(n)),
+            Interval::Days(n) => Some(Duration::days(n as i64)),
+            Interval::Weeks(n) => Some(Duration::weeks(n as i64)),
+            Interval::Months(_) => None,
+        }
+    }
+
+    pub fn bucket_start(&self, time: DateTime<Local>, align: Alignment, origin: DateTime<Local>) -> DateTime<Local> {
+        match align {
+            Alignment::Calendar => self.calendar_start(time),
+            Alignment::First => self.relative_start(time, origin),
+        }
+    }
+
+    pub fn next(&self, start: DateTime<Local>) -> DateTime<Local> {
+        let naive = start.naive_local();
+        match *self {
+            Interval::Seconds(n) => start + Duration::seconds(n),
+            Interval::Days(n) => local_time(naive + Duration::days(n as i64)),
+            Interval::Weeks(n) => local_time(naive + Duration::days(7 * n as i64)),
+            Interval::Months(n) => local_time(naive.checked_add_months(Months::new(n)).unwrap_or(naive)),
+        }
+    }
+
+    pub fn before(&self, time: DateTime<Local>) -> DateTime<Local> {
+        let naive = time.naive_local();
+        match *self {
+            Interval::Seconds(n) => time - Duration::seconds(n),
+            Interval::Days(n) => local_time(naive - Duration::days(n as i64)),
+            Interval::Weeks(n) => local_time(naive - Duration::days(7 * n as i64)),
+            Interval::Months(n) => local_time(naive.checked_sub_months(Months::new(n)).unwrap_or(naive)),
+        }
+    }
+
+    pub fn label(&self, start: DateTime<Local>, align: Alignment) -> String {
+        match (*self, align) {
+            (Interval::Seconds(n), _) if n % 60 == 0 => start.format("%Y-%m-%d %H:%M").to_string(),
+            (Interval::Seconds(_), _) => start.format("%Y-%m-%d %H:%M:%S").to_string(),
+            (Interval::Weeks(_), Alignment::Calendar) => start.format("%G-W%V").to_string(),
+            (Interval::Months(_), Alignment::Calendar) => start.format("%Y-%m").to_string(),
+            _ => start.format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    fn calendar_start(&self, time: DateTime<Local>) -> DateTime<Local> {
+        let date = time.date_naive();
+        match *self {
+            Interval::Seconds(n) => {
+                let midnight = local_midnight(date);
+                let elapsed = (time - midnight).num_seconds();
+                midnight + Duration::seconds(elapsed - elapsed.rem_euclid(n))
+            }
+            Interval::Days(n) => {
+                let days = date.num_days_from_ce() as i64;
+                local_midnight(date - Duration::days(days.rem_euclid(n as i64)))
+            }
+            Interval::Weeks(n) => {
+                let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                let offset = (date.iso_week().week() - 1) % n;
+                local_midnight(monday - Duration::days(7 * offset as i64))
+            }
+            Interval::Months(n) => {
+                let month0 = date.month0() - date.month0() % n;
+                local_midnight(NaiveDate::from_ymd_opt(date.year(), month0 + 1, 1).unwrap())
+            }
+        }
+    }
+
+    fn relative_start(&self, time: DateTime<Local>, origin: DateTime<Local>) -> DateTime<Local> {
+        let base = origin.naive_local();
+        let naive = time.naive_local();
+        match *self {
+            Interval::Seconds(n) => {
+                let elapsed = (time - origin).num_seconds();
+                origin + Duration::seconds(elapsed - elapsed.rem_euclid(n))
+            }
+            Interval::Days(n) | Interval::Weeks(n) => {
+                let step = if let Interval::Weeks(_) = self { 7 * n as i64 } else { n as i64 };
+                let days = (naive - base).num_days();
+                local_time(base + Duration::days(days - days.rem_euclid(step)))
+            }
+            Interval::Months(n) => {
+                let mut months = (naive.year() - base.year()) * 12 + naive.month() as i32 - base.month() as i32;
+                if months > 0 && add_months(base, months as u32) > naive {
+                    months -= 1;
+                }
+                let months = months.max(0) as u32;
+                local_time(add_months(base, months - months % n))
+            }
+        }
+    }
+}
+
+pub fn trend_analysis(logs: &[NginxLog], interval: Interval, align: Alignment) -> BTreeMap<DateTime<Local>, u64> {
+    trend_matching(logs, interval, align, |_| true)
+}
+
+pub fn trend_matching<F>(logs: &[NginxLog], interval: Interval, align: Alignment, predicate: F) -> BTreeMap<DateTime<Local>, u64>
+where
+    F: Fn(&NginxLog) -> bool,
+{
+    let mut trends = BTreeMap::new();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return trends,
+    };
+    for log in logs {
+        let key = interval.bucket_start(log.request_time, align, origin);
+        *trends.entry(key).or_insert(0) += predicate(log) as u64;
+    }
+    if let (Some(&first), Some(&last)) = (trends.keys().next(), trends.keys().next_back()) {
+        let mut bucket = interval.next(first);
+        while bucket < last {
+            trends.entry(bucket).or_insert(0);
+            bucket = interval.next(bucket);
+        }
+    }
+    trends
+}
+
+pub fn trend_by<F>(logs: &[NginxLog], interval: Interval, align: Alignment, key: F) -> BTreeMap<DateTime<Local>, BTreeMap<String, u64>>
+where
+    F: Fn(&NginxLog) -> String,
+{
+    let mut trends: BTreeMap<DateTime<Local>, BTreeMap<String, u64>> = trend_analysis(logs, interval, align).into_keys().map(|start| (start, BTreeMap::new())).collect();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return trends,
+    };
+    for log in logs {
+        let bucket = interval.bucket_start(log.request_time, align, origin);
+        *trends.entry(bucket).or_default().entry(key(log)).or_insert(0) += 1;
+    }
+    trends
+}
+
+fn add_months(time: NaiveDateTime, months: u32) -> NaiveDateTime {
+    time.checked_add_months(Months::new(months)).unwrap_or(time)
+}
+
+fn local_time(naive: NaiveDateTime) -> DateTime<Local> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&naive))
+}
+
+fn local_midnight(date: NaiveDate) -> DateTime<Local> {
+    local_time(date.and_hms_opt(0, 0, 0).unwrap())
+}
@@ -0,0 +1,90 @@
+#[cfg(feature = "arrow")]
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::Write;
+
+use crate::NginxLog;
+
+// Rows per record batch; readers can start on the first batch before the rest is written
+#[cfg(feature = "arrow")]
+const BATCH_ROWS: usize = 8192;
+
+// The entry time is "time" because $request_time, the duration, is usually an extra field
+#[cfg(feature = "arrow")]
+const FIXED_COLUMNS: [&str; 11] = ["remote_addr", "remote_user", "time", "request", "method", "path", "status", "body_bytes_sent", "http_referer", "http_user_agent", "request_id"];
+
+#[cfg(feature = "arrow")]
+fn extra_columns(logs: &[NginxLog]) -> Vec<String> {
+    let columns: BTreeSet<&String> = logs.iter().flat_map(|log| log.extra.keys()).filter(|name| !FIXED_COLUMNS.contains(&name.as_str())).collect();
+    columns.into_iter().cloned().collect()
+}
+
+#[cfg(feature = "arrow")]
+pub fn write_stream<W: Write>(out: W, logs: &[NginxLog]) -> Result<(), Box<dyn Error>> {
+    let (schema, batches) = record_batches(logs)?;
+    let mut writer = arrow::ipc::writer::StreamWriter::try_new(out, &schema)?;
+    for batch in &batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// The entries as Arrow batches: fixed columns first, then every extra field as a nullable string
+#[cfg(feature = "arrow")]
+pub fn record_batches(logs: &[NginxLog]) -> Result<(arrow::datatypes::SchemaRef, Vec<arrow::record_batch::RecordBatch>), Box<dyn Error>> {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+
+    let columns = extra_columns(logs);
+    let types = [
+        (DataType::Utf8, false),
+        (DataType::Utf8, false),
+        (DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        (DataType::Utf8, false),
+        (DataType::Utf8, true),
+        (DataType::Utf8, true),
+        (DataType::UInt16, false),
+        (DataType::UInt64, false),
+        (DataType::Utf8, false),
+        (DataType::Utf8, false),
+        (DataType::Utf8, true),
+    ];
+    let mut fields: Vec<Field> = FIXED_COLUMNS.iter().zip(types).map(|(name, (data_type, nullable))| Field::new(*name, data_type, nullable)).collect();
+    fields.extend(columns.iter().map(|column| Field::new(column.as_str(), DataType::Utf8, true)));
+    let schema = Arc::new(Schema::new(fields));
+    let batches = logs.chunks(BATCH_ROWS).map(|chunk| batch(schema.clone(), chunk, &columns)).collect::<Result<_, _>>()?;
+    Ok((schema, batches))
+}
+
+#[cfg(feature = "arrow")]
+fn batch(schema: std::sync::Arc<arrow::datatypes::Schema>, logs: &[NginxLog], columns: &[String]) -> Result<arrow::record_batch::RecordBatch, Box<dyn Error>> {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, StringArray, TimestampMicrosecondArray, UInt16Array, UInt64Array};
+
+    let strings = |value: fn(&NginxLog) -> &str| -> ArrayRef { Arc::new(logs.iter().map(value).map(Some).collect::<StringArray>()) };
+    let mut arrays: Vec<ArrayRef> = vec![
+        strings(|log| log.remote_addr.as_str()),
+        strings(|log| log.remote_user.as_str()),
+        Arc::new(TimestampMicrosecondArray::from(logs.iter().map(|log| log.request_time.timestamp_micros()).collect::<Vec<i64>>()).with_timezone("UTC")),
+        strings(|log| log.request.trim_matches('"')),
+        Arc::new(logs.iter().map(|log| log.method()).collect::<StringArray>()),
+        Arc::new(logs.iter().map(|log| log.path()).collect::<StringArray>()),
+        Arc::new(UInt16Array::from(logs.iter().map(|log| log.status).collect::<Vec<u16>>())),
+        Arc::new(UInt64Array::from(logs.iter().map(|log| log.body_bytes_sent).collect::<Vec<u64>>())),
+        strings(|log| log.http_referer.trim_matches('"')),
+        strings(|log| log.http_user_agent.trim_matches('"')),
+        Arc::new(logs.iter().map(|log| log.request_id.as_deref()).collect::<StringArray>()),
+    ];
+    for column in columns {
+        arrays.push(Arc::new(logs.iter().map(|log| log.extra.get(column).map(String::as_str)).collect::<StringArray>()));
+    }
+    Ok(arrow::record_batch::RecordBatch::try_new(schema, arrays)?)
+}
+
+#[cfg(not(feature = "arrow"))]
+pub fn write_stream<W: Write>(_out: W, _logs: &[NginxLog]) -> Result<(), Box<dyn Error>> {
+    Err("--output arrow: built without Arrow support; rebuild with --features arrow".into())
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use ipnet::IpNet;
+
+use crate::NginxLog;
+
+#[derive(Default)]
+pub struct KnownIps {
+    entries: Vec<(IpNet, String)>,
+}
+
+impl KnownIps {
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (network, label) = match line.split_once(char::is_whitespace) {
+                Some((network, label)) => (network, label.trim()),
+                None => (line, "known"),
+            };
+            self.insert(network, label)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn add_spec(&mut self, spec: &str) -> Result<(), Box<dyn Error>> {
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (network, label) = item.split_once('=').unwrap_or((item, "known"));
+            self.insert(network, label)?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, network: &str, label: &str) -> Result<(), String> {
+        let network = match network.parse::<IpNet>() {
+            Ok(network) => network,
+            Err(_) => IpNet::from(network.parse::<IpAddr>().map_err(|_| format!("invalid IP or CIDR: {}", network))?),
+        };
+        self.entries.push((network, label.to_string()));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn label(&self, addr: &str) -> Option<&str> {
+        let ip: IpAddr = addr.parse().ok()?;
+        self.entries
+            .iter()
+            .filter(|(network, _)| network.contains(&ip))
+            .max_by_key(|(network, _)| network.prefix_len())
+            .map(|(_, label)| label.as_str())
+    }
+
+    pub fn apply(&self, logs: &mut Vec<NginxLog>, exclude: bool) {
+        for log in logs.iter_mut() {
+            log.known_as = self.label(&log.remote_addr).map(String::from);
+        }
+        if exclude {
+            logs.retain(|log| log.known_as.is_none());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Local};
+
+use crate::digest::path_template;
+use crate::interval::{Alignment, Interval};
+use crate::sessions::is_static_asset;
+use crate::NginxLog;
+
+pub const BUCKETS_MS: [u64; 8] = [10, 50, 100, 250, 500, 1000, 2500, 5000];
+
+pub struct Histogram {
+    pub endpoint: String,
+    pub counts: [u64; BUCKETS_MS.len() + 1],
+    pub requests: u64,
+}
+
+pub fn request_seconds(log: &NginxLog) -> Option<f64> {
+    log.extra.get("request_time").and_then(|value| parse_seconds(value))
+}
+
+pub fn upstream_seconds(log: &NginxLog) -> Option<f64> {
+    log.extra.get("upstream_response_time").and_then(|value| parse_seconds(value))
+}
+
+// $upstream_header_time is the time to the backend's first byte; static files only have $request_time
+pub fn first_byte_seconds(log: &NginxLog) -> Option<f64> {
+    log.extra.get("upstream_header_time").and_then(|value| parse_seconds(value)).or_else(|| request_seconds(log))
+}
+
+pub fn parse_duration(spec: &str) -> Result<f64, String> {
+    let (number, scale) = if let Some(number) = spec.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = spec.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = spec.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (spec, 1.0)
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok(value * scale),
+        _ => Err(format!("invalid duration: {} (expected e.g. 500ms, 2s or 1.5)", spec)),
+    }
+}
+
+fn parse_seconds(value: &str) -> Option<f64> {
+    let times: Vec<f64> = value.split([',', ':']).map(str::trim).filter(|part| *part != "-").filter_map(|part| part.parse().ok()).collect();
+    if times.is_empty() {
+        return None;
+    }
+    Some(times.iter().sum())
+}
+
+pub fn bucket_label(index: usize) -> String {
+    let format = |ms: u64| if ms >= 1000 { format!("{}s", ms as f64 / 1000.0) } else { format!("{}ms", ms) };
+    match index {
+        0 => format!("0-{}", format(BUCKETS_MS[0])),
+        _ if index == BUCKETS_MS.len() => format!("{}+", format(BUCKETS_MS[index - 1])),
+        _ => format!("{}-{}", format(BUCKETS_MS[index - 1]), format(BUCKETS_MS[index])),
+    }
+}
+
+pub fn latency_histograms(logs: &[NginxLog]) -> Vec<Histogram> {
+    let mut histograms: HashMap<String, Histogram> = HashMap::new();
+    for log in logs {
+        let seconds = match request_seconds(log) {
+            Some(seconds) => seconds,
+            None => continue,
+        };
+        let endpoint = format!("{} {}", log.method().unwrap_or("-"), path_template(log.path().unwrap_or("-")));
+        let histogram = histograms.entry(endpoint.clone()).or_insert_with(|| Histogram { endpoint, counts: Default::default(), requests: 0 });
+        let ms = (seconds * 1000.0).round() as u64;
+        let index = BUCKETS_MS.iter().position(|limit| ms < *limit).unwrap_or(BUCKETS_MS.len());
+        histogram.counts[index] += 1;
+        histogram.requests += 1;
+    }
+    let mut histograms: Vec<Histogram> = histograms.into_values().collect();
+    histograms.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.endpoint.cmp(&b.endpoint)));
+    histograms
+}
+
+#[derive(Default)]
+pub struct ClassLatency {
+    pub assets: Vec<f64>,
+    pub app: Vec<f64>,
+}
+
+pub fn latency_by_class(logs: &[NginxLog], interval: Interval, align: Alignment) -> BTreeMap<DateTime<Local>, ClassLatency> {
+    let mut periods: BTreeMap<DateTime<Local>, ClassLatency> = BTreeMap::new();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return periods,
+    };
+    for log in logs {
+        let seconds = match first_byte_seconds(log) {
+            Some(seconds) => seconds,
+            None => continue,
+        };
+        let period = periods.entry(interval.bucket_start(log.request_time, align, origin)).or_default();
+        if is_static_asset(log.path().unwrap_or("")) {
+            period.assets.push(seconds);
+        } else {
+            period.app.push(seconds);
+        }
+    }
+    for period in periods.values_mut() {
+        period.assets.sort_by(f64::total_cmp);
+        period.app.sort_by(f64::total_cmp);
+    }
+    periods
+}
+
+pub fn percentile(sorted: &[f64], percent: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
@@ -0,0 +1,82 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use chrono::{DateTime, Local};
+
+use crate::NginxLog;
+
+pub const CHECKS: [&str; 4] = ["out-of-order", "future-dated", "gap", "duplicate-burst"];
+
+pub struct Issue {
+    pub check: &'static str,
+    pub source: String,
+    pub entry: usize,
+    pub time: DateTime<Local>,
+    pub detail: String,
+}
+
+pub struct LintOptions {
+    pub tolerance: i64,
+    pub max_gap: i64,
+    pub min_duplicates: usize,
+    pub now: DateTime<Local>,
+}
+
+fn signature(log: &NginxLog) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (&log.remote_addr, log.request_time.timestamp(), &log.request, log.status, log.body_bytes_sent, &log.http_referer, &log.http_user_agent).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn human_duration(seconds: i64) -> String {
+    match seconds {
+        0..=119 => format!("{}s", seconds),
+        120..=7199 => format!("{}m", seconds / 60),
+        7200..=172_799 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+pub fn lint(logs: &[NginxLog], options: &LintOptions) -> Vec<Issue> {
+    let mut sources: BTreeMap<&str, Vec<&NginxLog>> = BTreeMap::new();
+    for log in logs {
+        sources.entry(log.extra.get("source").map(String::as_str).unwrap_or("-")).or_default().push(log);
+    }
+    let mut issues = Vec::new();
+    for (source, entries) in sources {
+        let issue = |check, entry: usize, log: &NginxLog, detail: String| Issue { check, source: source.to_string(), entry: entry + 1, time: log.request_time, detail };
+        let mut latest: Option<DateTime<Local>> = None;
+        let mut seen = HashSet::new();
+        let mut run: Vec<usize> = Vec::new();
+        for (index, log) in entries.iter().enumerate() {
+            let time = log.request_time;
+            let ahead = (time - options.now).num_seconds();
+            if ahead > options.tolerance {
+                issues.push(issue("future-dated", index, log, format!("{} ahead of now", human_duration(ahead))));
+            } else if let Some(previous) = latest {
+                let step = (time - previous).num_seconds();
+                if -step > options.tolerance {
+                    issues.push(issue("out-of-order", index, log, format!("{} before an earlier entry", human_duration(-step))));
+                } else if step > options.max_gap {
+                    issues.push(issue("gap", index, log, format!("no entries for {}", human_duration(step))));
+                }
+            }
+            if ahead <= options.tolerance {
+                latest = latest.max(Some(time));
+            }
+            if seen.insert(signature(log)) {
+                if run.len() >= options.min_duplicates {
+                    issues.push(issue("duplicate-burst", run[0], entries[run[0]], format!("{} entries repeated verbatim", run.len())));
+                }
+                run.clear();
+            } else {
+                run.push(index);
+            }
+        }
+        if run.len() >= options.min_duplicates {
+            issues.push(issue("duplicate-burst", run[0], entries[run[0]], format!("{} entries repeated verbatim", run.len())));
+        }
+    }
+    issues
+}
@@ -0,0 +1,23 @@
+use std::error::Error;
+
+use lettre::message::{Mailbox, MultiPart};
+use lettre::{Message, SmtpTransport, Transport};
+
+pub struct Report<'a> {
+    pub from: &'a str,
+    pub to: Vec<&'a str>,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+pub fn send(smtp: &str, report: Report) -> Result<(), Box<dyn Error>> {
+    let mut message = Message::builder().from(report.from.parse::<Mailbox>().map_err(|e| format!("invalid --from address {}: {}", report.from, e))?);
+    for to in &report.to {
+        message = message.to(to.parse::<Mailbox>().map_err(|e| format!("invalid --email address {}: {}", to, e))?);
+    }
+    let message = message.subject(report.subject).multipart(MultiPart::alternative_plain_html(report.text, report.html))?;
+    let transport = SmtpTransport::from_url(smtp).map_err(|e| format!("invalid --smtp URL: {}", e))?.build();
+    transport.send(&message).map_err(|e| format!("sending report via SMTP failed: {}", e))?;
+    Ok(())
+}
@@ -1,19 +1,149 @@
-use std::collections::{HashMap, HashSet};
+mod abuse;
+mod aggregate;
+mod alb;
+mod anomaly;
+mod anonymize;
+mod bench;
+mod campaigns;
+mod canary;
+mod cardinality;
+mod chart;
+mod cli;
+mod compare;
+mod config;
+mod connections;
+mod correlate;
+mod crawlers;
+mod csv_input;
+mod cve;
+mod detect;
+mod digest;
+mod endpoints;
+mod excerpt;
+mod export;
+mod extract;
+mod firstseen;
+mod follow;
+mod format;
+#[cfg(any(test, feature = "fuzz"))]
+mod fuzz;
+mod generate;
+mod geo;
+mod graph;
+mod health;
+mod hosts;
+mod hotlink;
+mod interval;
+mod ipc;
+mod known;
+mod latency;
+mod lint;
+mod mail;
+mod normalize;
+mod notify;
+mod otlp;
+mod params;
+mod parser;
+mod peaks;
+mod pipeline;
+mod plan;
+mod plugin;
+mod prune;
+mod ratelimit;
+mod ranges;
+mod redact;
+mod replay;
+mod robots;
+mod rollup;
+mod schedule;
+mod script;
+mod server;
+mod sessions;
+mod sparkline;
+mod sql;
+mod summary;
+mod suspicious;
+mod time_display;
+mod uploads;
+mod useragent;
+mod w3c;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use chrono::{DateTime, Local};
-use clap::{App, Arg};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
 use colored::*;
 use fern::colors::{Color, ColoredLevelConfig};
-use prettytable::{cell, row, table};
+use flate2::read::MultiGzDecoder;
+use abuse::abuse_scores;
+use aggregate::{parse_size, GroupField, SpillingCounter, Totals};
+use anomaly::{detect, hourly_buckets};
+use anonymize::Anonymizer;
+use campaigns::campaign_report;
+use canary::Thresholds;
+use chart::{build_chart, ChartKind};
+use compare::compare;
+use config::{Config, Enricher, Sink, SinkFormat};
+use connections::connection_reuse;
+use correlate::{correlate, MatchBy};
+use crawlers::crawl_report;
+use csv_input::CsvFormat;
+use cve::{cve_scan, default_rules, load_rules, percent_decode};
+use detect::detect_format;
+use digest::error_digest;
+use endpoints::{unknown_endpoints, Allowlist};
+use excerpt::Excerpt;
+use extract::ExtractRule;
+use firstseen::{newcomers, Baseline};
+use follow::Tail;
+use format::LogFormat;
+use geo::{geo_counts, write_geojson, GeoDb, GeoLevel};
+use graph::{navigation_edges, write_dot};
+use health::Health;
+use hosts::{balance_by, host_dirs, host_of, host_stats, misbehaving, skew, upstream_of, HOST_FIELD};
+use hotlink::{hotlinks, own_sites, valid_referers_config};
+use interval::{trend_analysis, trend_by, trend_matching, Alignment, Interval};
+use log::{debug, info, trace, LevelFilter};
+use known::KnownIps;
+use latency::{bucket_label, latency_by_class, latency_histograms, parse_duration, percentile, request_seconds, upstream_seconds};
+use normalize::PageRules;
+use otlp::OtlpExporter;
+use params::query_params;
+use parser::{glob_match, is_extra_field, parse_log_time, parse_status, unescape, FormatSpec, LineParser, ParseError, COMBINED_FORMAT};
+use peaks::{find_peak, parse_window};
+use plan::{human_size, plan_file};
+use plugin::Plugin;
+use ranges::{downloads, inferred_size, range_clients, ranged_files};
+use redact::Redactor;
+use replay::{parse_rate, Replayer};
+use robots::Robots;
+use schedule::Job;
+use script::Script;
+use sessions::{entry_exit, funnel, is_static_asset, parse_steps, sessions};
+use sparkline::{bar, sparkline};
+use suspicious::{suspicious_requests, Limits};
+use time_display::{DisplayZone, TimeDisplay, TimeFormat};
+use uploads::ingress_by;
+use useragent::{browser_version_trend, fingerprint_stats, is_bot};
+use w3c::W3cFormat;
+use prettytable::{cell, row, table, Cell, Row, Table};
 use regex::Regex;
-use tar::Archive;
+use serde::Serialize;
 
-const NGINX_LOG_FORMAT: &str = r#"^(?P<remote_addr>[\d\.]+) (?P<remote_user>\S+) (?P<request_time>\[[^\]]+\]) (?P<request>\"[^\"]+\") (?P<status>\d+) (?P<body_bytes_sent>\d+) (?P<http_referer>\"[^\"]+\") (?P<http_user_agent>\"[^\"]+\")"#;
+// Bytes read from the end of a log to find its last entries
+const TAIL_SIZE: u64 = 64 * 1024;
 
-#[derive(Debug)]
+const FINGERPRINT_FIELDS: [&str; 6] = ["ssl_ja3_hash", "ssl_ja3", "ssl_ja4", "http_x_ja3_hash", "http_x_ja3", "http_x_ja4"];
+
+const NGINX_LOG_FORMAT: &str = r#"^(?P<remote_addr>[\d\.]+) (?P<remote_user>\S+) (?P<request_time>\[[^\]]+\]) "(?P<request>(?:[^"\\]|\\.)*)" (?P<status>\d+) (?P<body_bytes_sent>\d+) "(?P<http_referer>(?:[^"\\]|\\.)*)" "(?P<http_user_agent>(?:[^"\\]|\\.)*)""#;
+
+#[derive(Debug, Clone, Default, Serialize)]
 struct NginxLog {
     remote_addr: String,
     remote_user: String,
@@ -23,9 +153,323 @@ struct NginxLog {
     body_bytes_sent: u64,
     http_referer: String,
     http_user_agent: String,
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    known_as: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    extra: HashMap<String, String>,
+    // the line as read, only kept for grep
+    #[serde(skip)]
+    raw: Option<String>,
+}
+
+impl NginxLog {
+    fn request_parts(&self) -> std::str::Split<'_, char> {
+        self.request.trim_matches('"').split(' ')
+    }
+
+    fn method(&self) -> Option<&str> {
+        self.request_parts().next().filter(|method| !method.is_empty() && *method != "-")
+    }
+
+    fn path(&self) -> Option<&str> {
+        self.request_parts().nth(1)
+    }
+
+    fn protocol(&self) -> Option<&str> {
+        self.request_parts().nth(2)
+    }
+
+    fn http_version(&self) -> String {
+        let protocol = self.extra.get("server_protocol").map(String::as_str).or_else(|| self.protocol()).unwrap_or("-");
+        match protocol {
+            "HTTP/2.0" => String::from("HTTP/2"),
+            "HTTP/3.0" => String::from("HTTP/3"),
+            _ => protocol.to_string(),
+        }
+    }
+
+    fn tls_fingerprint(&self) -> Option<&str> {
+        FINGERPRINT_FIELDS.iter().find_map(|name| self.extra.get(*name)).map(String::as_str).filter(|value| !value.is_empty() && *value != "-")
+    }
+
+    fn query(&self) -> Option<&str> {
+        self.path().and_then(|path| path.split_once('?')).map(|(_, query)| query)
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "ip" | "remote_addr" => self.remote_addr.as_str(),
+            "remote_user" => self.remote_user.as_str(),
+            "request" => self.request.trim_matches('"'),
+            "method" => self.method()?,
+            "path" => self.path()?,
+            "query" => self.query()?,
+            "protocol" => self.protocol()?,
+            "status" => return Some(self.status.to_string()),
+            "body_bytes_sent" => return Some(self.body_bytes_sent.to_string()),
+            "referer" | "http_referer" => self.http_referer.trim_matches('"'),
+            "user_agent" | "user-agent" | "http_user_agent" => self.http_user_agent.trim_matches('"'),
+            "request_id" => self.request_id.as_deref()?,
+            "host" => ["http_host", "host", "server_name"].iter().find_map(|name| self.extra.get(*name))?,
+            "ja3" | "tls_fingerprint" | "tls-fingerprint" => self.tls_fingerprint()?,
+            _ => match self.extra.get(name) {
+                Some(value) => value,
+                None if name.starts_with("cookie_") => return self.cookie(&name["cookie_".len()..]),
+                None => return name.strip_prefix("arg_").and_then(|arg| self.query_param(arg)),
+            },
+        };
+        Some(value.to_string())
+    }
+
+    fn query_param(&self, name: &str) -> Option<String> {
+        self.query()?
+            .split('&')
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .find(|(key, _)| percent_decode(key) == name)
+            .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+    }
+
+    fn cookie(&self, name: &str) -> Option<String> {
+        self.extra
+            .get("http_cookie")?
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+    }
+
+    fn client(&self) -> String {
+        match &self.known_as {
+            Some(label) => format!("{} [{}]", self.remote_addr, label),
+            None => self.remote_addr.clone(),
+        }
+    }
+}
+
+// Reports about the query string itself, which keep it even with --ignore-query
+const QUERY_REPORTS: [&str; 7] = ["campaigns", "cve-scan", "export", "extract", "grep", "params", "replay"];
+
+struct Enrichment {
+    normalize_paths: bool,
+    ignore_query: bool,
+    pages: Option<PageRules>,
+    known: KnownIps,
+    exclude_known: bool,
+    extract_rules: Vec<ExtractRule>,
+    plugins: Vec<Plugin>,
+    script: Option<Script>,
+    redactor: Option<Redactor>,
+    anonymizer: Option<Anonymizer>,
+}
+
+impl Enrichment {
+    fn from_matches(matches: &clap::ArgMatches) -> Result<Enrichment, Box<dyn Error>> {
+        let mut known = KnownIps::default();
+        for path in matches.values_of("known_ips").into_iter().flatten() {
+            known.load_file(path)?;
+        }
+        for spec in matches.values_of("known_cidrs").into_iter().flatten() {
+            known.add_spec(spec)?;
+        }
+        Ok(Enrichment {
+            normalize_paths: matches.is_present("normalize_paths"),
+            ignore_query: matches.is_present("ignore_query") && !matches.subcommand_name().is_some_and(|name| QUERY_REPORTS.contains(&name)),
+            pages: if matches.is_present("merge_pages") { Some(PageRules::new(matches.values_of("index_files").into_iter().flatten())) } else { None },
+            known,
+            exclude_known: matches.is_present("exclude_known"),
+            extract_rules: matches.values_of("extract").into_iter().flatten().map(ExtractRule::parse).collect::<Result<_, _>>()?,
+            plugins: matches.values_of("plugin").into_iter().flatten().map(Plugin::load).collect::<Result<_, _>>()?,
+            script: matches.value_of("script").map(Script::load).transpose()?,
+            redactor: Redactor::new(matches.values_of("redact_params").into_iter().flatten(), matches.values_of("redact_segments").into_iter().flatten()),
+            anonymizer: matches.value_of("anonymize").map(|mode| Anonymizer::parse(mode, matches.value_of("anonymize_salt"))).transpose()?,
+        })
+    }
+
+    // Commands that print the original lines can't honor these
+    fn masks(&self) -> bool {
+        self.anonymizer.is_some() || self.redactor.is_some()
+    }
+
+    // In the order apply runs them
+    fn describe(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if !self.known.is_empty() {
+            steps.push(String::from(if self.exclude_known { "drop entries from known addresses" } else { "label known addresses" }));
+        }
+        if self.normalize_paths {
+            steps.push(String::from("normalize request paths"));
+        }
+        if self.ignore_query {
+            steps.push(String::from("drop query strings"));
+        }
+        if let Some(pages) = &self.pages {
+            steps.push(pages.describe());
+        }
+        steps.extend(self.extract_rules.iter().map(|rule| format!("extract {}", rule.describe())));
+        steps.extend(self.plugins.iter().map(|plugin| format!("plugin {}", plugin.path())));
+        if let Some(script) = &self.script {
+            steps.push(format!("script {}", script.describe()));
+        }
+        if self.redactor.is_some() {
+            steps.push(String::from("redact query parameters and path segments"));
+        }
+        match &self.anonymizer {
+            Some(Anonymizer::Mask) => steps.push(String::from("mask client addresses")),
+            Some(Anonymizer::Hash(_)) => steps.push(String::from("hash client addresses")),
+            None => {}
+        }
+        steps
+    }
+
+    fn apply(&self, logs: &mut Vec<NginxLog>) -> Result<(), Box<dyn Error>> {
+        if !self.known.is_empty() {
+            self.known.apply(logs, self.exclude_known);
+        }
+        let mut kept = Vec::with_capacity(logs.len());
+        for mut log in logs.drain(..) {
+            if self.normalize_paths {
+                normalize::apply(&mut log);
+            }
+            if self.ignore_query {
+                normalize::strip_query(&mut log);
+            }
+            if let Some(pages) = &self.pages {
+                pages.apply(&mut log);
+            }
+            for rule in &self.extract_rules {
+                rule.apply(&mut log);
+            }
+            for plugin in &self.plugins {
+                plugin.enrich(&mut log)?;
+            }
+            if let Some(script) = &self.script {
+                if !script.apply(&mut log)? {
+                    continue;
+                }
+            }
+            if let Some(redactor) = &self.redactor {
+                redactor.apply(&mut log);
+            }
+            if let Some(anonymizer) = &self.anonymizer {
+                anonymizer.apply(&mut log);
+            }
+            kept.push(log);
+        }
+        *logs = kept;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Filters<'a> {
+    start: Option<DateTime<Local>>,
+    end: Option<DateTime<Local>>,
+    status: Option<u16>,
+    referer: Option<&'a str>,
+    path: Option<&'a str>,
+    request_id: Option<&'a str>,
+    fingerprints: Vec<&'a str>,
+    hosts: Vec<&'a str>,
+    fields: Vec<(&'a str, &'a str)>,
+    context_window: Option<chrono::Duration>,
 }
 
-fn filter_logs(logs: &[NginxLog], start_date: Option<DateTime<Local>>, end_date: Option<DateTime<Local>>, status: Option<u16>, referer: Option<&str>, path: Option<&str>) -> Vec<NginxLog> {
+impl<'a> Filters<'a> {
+    fn from_matches(matches: &'a clap::ArgMatches, start: Option<DateTime<Local>>) -> Result<Filters<'a>, Box<dyn Error>> {
+        Ok(Filters {
+            start,
+            end: matches.value_of("end").map(parse_date_arg).transpose()?,
+            status: matches.value_of("status").map(|s| s.parse::<u16>()).transpose()?,
+            referer: matches.value_of("referer"),
+            path: matches.value_of("request_path"),
+            request_id: matches.value_of("request_id"),
+            fingerprints: matches.values_of("ja3").into_iter().flatten().collect(),
+            hosts: matches.values_of("host").into_iter().flatten().flat_map(|hosts| hosts.split(',')).collect(),
+            fields: matches
+                .values_of("field")
+                .into_iter()
+                .flatten()
+                .map(|spec| spec.split_once('=').ok_or_else(|| format!("invalid --field filter: {} (expected NAME=TEXT)", spec)))
+                .collect::<Result<_, _>>()?,
+            context_window: match matches.value_of("context_window") {
+                Some(spec) => Some(Interval::parse(spec)?.duration().ok_or_else(|| format!("invalid --context-window: {} (use s, m, h, d or w)", spec))?),
+                None => None,
+            },
+        })
+    }
+
+    // In the order apply checks them
+    fn describe(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if let Some(start) = self.start {
+            steps.push(format!("time >= {}", start.to_rfc3339()));
+        }
+        if let Some(end) = self.end {
+            steps.push(format!("time <= {}", end.to_rfc3339()));
+        }
+        if let Some(status) = self.status {
+            steps.push(format!("status = {}", status));
+        }
+        if let Some(referer) = self.referer {
+            steps.push(format!("referer contains \"{}\"", referer));
+        }
+        if let Some(path) = self.path {
+            steps.push(format!("request contains \"{}\"", path));
+        }
+        if let Some(request_id) = self.request_id {
+            steps.push(format!("request_id = {}", request_id));
+        }
+        if !self.fingerprints.is_empty() {
+            steps.push(format!("TLS fingerprint in {}", self.fingerprints.join(", ")));
+        }
+        if !self.hosts.is_empty() {
+            steps.push(format!("host directory in {}", self.hosts.join(", ")));
+        }
+        steps.extend(self.fields.iter().map(|(name, text)| format!("{} contains \"{}\"", name, text)));
+        if let Some(window) = self.context_window {
+            steps.push(format!("plus entries from the same IP within {}s of a match", window.num_seconds()));
+        }
+        steps
+    }
+
+    fn apply(&self, logs: &[NginxLog]) -> Vec<NginxLog> {
+        let mut filtered = filter_logs(logs, self.start, self.end, self.status, self.referer, self.path, self.request_id);
+        if !self.fingerprints.is_empty() {
+            filtered.retain(|log| log.tls_fingerprint().is_some_and(|fingerprint| self.fingerprints.contains(&fingerprint)));
+        }
+        if !self.hosts.is_empty() {
+            filtered.retain(|log| log.extra.get(HOST_FIELD).is_some_and(|host| self.hosts.contains(&host.as_str())));
+        }
+        filtered.retain(|log| self.fields.iter().all(|(name, text)| log.field(name).is_some_and(|value| value.contains(text))));
+        match self.context_window {
+            Some(window) => with_context(logs, &filtered, window),
+            None => filtered,
+        }
+    }
+}
+
+// Every entry from a matched IP within `window` of one of its matches, in the original order
+fn with_context(logs: &[NginxLog], matched: &[NginxLog], window: chrono::Duration) -> Vec<NginxLog> {
+    let mut times: HashMap<&str, Vec<DateTime<Local>>> = HashMap::new();
+    for log in matched {
+        times.entry(log.remote_addr.as_str()).or_default().push(log.request_time);
+    }
+    for list in times.values_mut() {
+        list.sort();
+    }
+    logs.iter()
+        .filter(|log| {
+            times.get(log.remote_addr.as_str()).is_some_and(|list| {
+                let first = list.partition_point(|time| *time < log.request_time - window);
+                list.get(first).is_some_and(|time| *time <= log.request_time + window)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+fn filter_logs(logs: &[NginxLog], start_date: Option<DateTime<Local>>, end_date: Option<DateTime<Local>>, status: Option<u16>, referer: Option<&str>, path: Option<&str>, request_id: Option<&str>) -> Vec<NginxLog> {
     logs.into_iter()
         .filter(|log| {
             if let Some(start_date) = start_date {
@@ -53,108 +497,2716 @@ fn filter_logs(logs: &[NginxLog], start_date: Option<DateTime<Local>>, end_date:
                     return false;
                 }
             }
+            if let Some(request_id) = request_id {
+                if log.request_id.as_deref() != Some(request_id) {
+                    return false;
+                }
+            }
             true
         })
         .cloned()
         .collect()
 }
 
-fn parse_nginx_log_line(line: &str) -> Option<NginxLog> {
-    let re = Regex::new(NGINX_LOG_FORMAT).unwrap();
-    let captures = match re.captures(line) {
-        Some(captures) => captures,
-        None => return None,
-    };
-    let request_time = DateTime::parse_from_str(&captures["request_time"], "%d/%b/%Y:%H:%M:%S %z")
-        .unwrap();
-    let status = captures["status"].parse::<u16>().unwrap();
-    let body_bytes_sent = captures["body_bytes_sent"].parse::<u64>().unwrap();
-    Some(NginxLog {
+fn parse_nginx_log_line(line: &str) -> Result<NginxLog, ParseError> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(NGINX_LOG_FORMAT).unwrap());
+    let captures = re.captures(line).ok_or(ParseError::NoMatch("regex"))?;
+    let request_time = parse_log_time(&captures["request_time"])?;
+    let status = parse_status(&captures["status"])?;
+    let body_bytes_sent = captures["body_bytes_sent"].parse::<u64>().map_err(|_| ParseError::invalid("body_bytes_sent", &captures["body_bytes_sent"]))?;
+    Ok(NginxLog {
         remote_addr: captures["remote_addr"].to_string(),
         remote_user: captures["remote_user"].to_string(),
         request_time,
-        request: captures["request"].to_string(),
+        request: format!("\"{}\"", &captures["request"]),
         status,
         body_bytes_sent,
-        http_referer: captures["http_referer"].to_string(),
-        http_user_agent: captures["http_user_agent"].to_string(),
+        http_referer: format!("\"{}\"", unescape(&captures["http_referer"])),
+        http_user_agent: format!("\"{}\"", unescape(&captures["http_user_agent"])),
+        ..Default::default()
     })
 }
 
-fn print_logs(logs: &[NginxLog]) {
+fn print_logs(logs: &[NginxLog], display: &TimeDisplay, truncate: Option<usize>) {
+    print_table(logs_table(logs, display), truncate);
+}
+
+fn print_table(mut table: Table, truncate: Option<usize>) {
+    if let Some(width) = truncate {
+        for row in table.row_iter_mut() {
+            for cell in row.iter_mut() {
+                let content = cell.get_content();
+                if content.lines().any(|line| line.chars().count() > width) {
+                    let lines: Vec<String> = content.lines().map(|line| truncate_value(line, width)).collect();
+                    *cell = Cell::new(&lines.join("\n"));
+                }
+            }
+        }
+    }
+    table.printstd();
+}
+
+fn truncate_value(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_string();
+    }
+    let mut short: String = value.chars().take(width.saturating_sub(1)).collect();
+    short.push('…');
+    short
+}
+
+fn logs_table(logs: &[NginxLog], display: &TimeDisplay) -> Table {
+    let mut columns: Vec<&String> = logs.iter().flat_map(|log| log.extra.keys()).collect();
+    columns.sort();
+    columns.dedup();
     let mut table = table!([bFg -> "Remote Address", "Remote User", "Request Time", "Request", "Status", "Body Bytes Sent", "HTTP Referer", "HTTP User Agent"]);
+    for column in &columns {
+        table.get_mut_row(0).unwrap().add_cell(Cell::new(column).style_spec("bFg"));
+    }
     for log in logs {
-        table.add_row(row![
-            log.remote_addr,
+        let mut row = row![
+            log.client(),
             log.remote_user,
-            log.request_time.to_string(),
+            display.render(log.request_time),
             log.request,
             log.status,
             log.body_bytes_sent,
             log.http_referer,
             log.http_user_agent,
-        ]);
+        ];
+        for column in &columns {
+            row.add_cell(Cell::new(log.extra.get(*column).map(String::as_str).unwrap_or("-")));
+        }
+        table.add_row(row);
     }
-    table.printstd();
+    table
+}
+
+fn logs_json(logs: &[NginxLog], display: &TimeDisplay) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut values = Vec::with_capacity(logs.len());
+    for log in logs {
+        let mut value = serde_json::to_value(log)?;
+        value["request_time"] = display.json(log.request_time);
+        values.push(value);
+    }
+    Ok(serde_json::Value::Array(values))
 }
 
+fn print_logs_json(logs: &[NginxLog], display: &TimeDisplay) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(&logs_json(logs, display)?)?);
+    Ok(())
+}
 
-fn read_nginx_logs<P: AsRef<Path>>(path: P) -> Result<Vec<NginxLog>, Box<dyn Error>> {
-    let path = path.as_ref();
+
+fn print_logs_csv(logs: &[NginxLog], display: &TimeDisplay) {
+    let mut columns: Vec<&String> = logs.iter().flat_map(|log| log.extra.keys()).collect();
+    columns.sort();
+    columns.dedup();
+    let mut header: Vec<String> = ["remote_addr", "remote_user", "request_time", "request", "status", "body_bytes_sent", "http_referer", "http_user_agent", "request_id"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    header.extend(columns.iter().map(|column| column.to_string()));
+    println!("{}", csv_row(&header));
+    for log in logs {
+        let mut row = vec![
+            log.remote_addr.clone(),
+            log.remote_user.clone(),
+            display.render(log.request_time),
+            log.request.trim_matches('"').to_string(),
+            log.status.to_string(),
+            log.body_bytes_sent.to_string(),
+            log.http_referer.trim_matches('"').to_string(),
+            log.http_user_agent.trim_matches('"').to_string(),
+            log.request_id.clone().unwrap_or_default(),
+        ];
+        row.extend(columns.iter().map(|column| log.extra.get(*column).cloned().unwrap_or_default()));
+        println!("{}", csv_row(&row));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ReadOptions<'a> {
+    parser: LineParser<'a>,
+    formats: &'a [(&'a str, LineParser<'a>)],
+    max_files: Option<usize>,
+    members: &'a [&'a str],
+    newest_first: bool,
+    chronological: bool,
+    since: Option<DateTime<Local>>,
+    // a SQL time bound: files are skipped only when their last entries are older too
+    entries_since: Option<DateTime<Local>>,
+    max_errors: Option<ErrorLimit>,
+    track_source: bool,
+    keep_raw: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ErrorLimit {
+    Count(u64),
+    Percent(f64),
+}
+
+#[derive(Default)]
+struct ReadStats {
+    lines: u64,
+    failed: u64,
+}
+
+impl ErrorLimit {
+    fn parse(value: &str) -> Result<ErrorLimit, Box<dyn Error>> {
+        match value.strip_suffix('%') {
+            Some(percent) => Ok(ErrorLimit::Percent(percent.trim().parse()?)),
+            None => Ok(ErrorLimit::Count(value.trim().parse()?)),
+        }
+    }
+
+    fn exceeded(&self, stats: &ReadStats) -> bool {
+        match *self {
+            ErrorLimit::Count(max) => stats.failed > max,
+            ErrorLimit::Percent(max) => stats.lines > 0 && stats.failed as f64 * 100.0 / stats.lines as f64 > max,
+        }
+    }
+}
+
+// Its own type so main can tell it apart and exit with EXIT_PARSE_ERRORS
+#[derive(Debug)]
+struct TooManyErrors(String);
+
+impl fmt::Display for TooManyErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for TooManyErrors {}
+
+// Names the file an error came from and keeps the error itself as the source, so main still sees an io::Error or TooManyErrors
+#[derive(Debug)]
+struct PathError {
+    path: String,
+    source: Box<dyn Error>,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.source)
+    }
+}
+
+impl Error for PathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+fn in_path(path: impl fmt::Display, error: impl Into<Box<dyn Error>>) -> Box<dyn Error> {
+    Box::new(PathError { path: path.to_string(), source: error.into() })
+}
+
+fn too_many_errors(limit: ErrorLimit, stats: &ReadStats) -> Box<dyn Error> {
+    let limit = match limit {
+        ErrorLimit::Count(max) => format!("{} lines", max),
+        ErrorLimit::Percent(max) => format!("{}%", max),
+    };
+    Box::new(TooManyErrors(format!(
+        "{} of {} lines could not be parsed (limit {}); the logs probably do not match the expected format, check --log-format",
+        stats.failed, stats.lines, limit
+    )))
+}
+
+impl ReadOptions<'_> {
+    fn for_file(&self, path: &Path) -> ReadOptions<'_> {
+        let parser = self.format_for(path).map(|(_, parser)| *parser).unwrap_or(self.parser);
+        ReadOptions { parser, ..*self }
+    }
+
+    fn format_for(&self, path: &Path) -> Option<&(&str, LineParser<'_>)> {
+        let path = path.to_string_lossy();
+        let name = pipeline::base_name(&path);
+        self.formats.iter().filter(|(pattern, _)| glob_match(pattern, name)).max_by_key(|(pattern, _)| pattern.len())
+    }
+}
+
+fn read_nginx_logs<P: AsRef<Path>>(path: P, options: &ReadOptions) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    let started = Instant::now();
+    let mut stats = ReadStats::default();
+    let logs = read_log_path(path.as_ref(), options, &mut stats)?;
+    info!(
+        "stage=read path={} entries={} lines={} failed={} elapsed_ms={}",
+        path.as_ref().display(),
+        logs.len(),
+        stats.lines,
+        stats.failed,
+        started.elapsed().as_millis()
+    );
+    if let Some(limit) = options.max_errors {
+        if limit.exceeded(&stats) {
+            return Err(too_many_errors(limit, &stats));
+        }
+    }
+    Ok(logs)
+}
+
+fn source_files(sources: &[Source], options: &ReadOptions) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for source in sources {
+        let path = Path::new(&source.path);
+        if path.is_dir() {
+            files.append(&mut scan_dir(path, options)?);
+            for (_, dir) in host_dirs(path)? {
+                files.append(&mut scan_dir(&dir, options)?);
+            }
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+fn scan_dir(path: &Path, options: &ReadOptions) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    Ok(plan_dir(path, options)?.into_iter().filter(|(_, skipped)| skipped.is_none()).map(|(path, _)| path).collect())
+}
+
+// Every candidate file in read order, with the reason it is skipped if it is
+fn plan_dir(path: &Path, options: &ReadOptions) -> Result<Vec<(PathBuf, Option<&'static str>)>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "log" || ext == "gz" || ext == "tgz" || ext == "zip") {
+            files.push((fs::metadata(&path)?.modified()?, path));
+        }
+    }
+    files.sort();
+    if options.newest_first {
+        files.reverse();
+    }
+    debug!("stage=scan dir={} candidates={}", path.display(), files.len());
+    let mut planned = Vec::new();
+    let mut selected = 0;
+    let mut stopped = None;
+    for (modified, path) in files {
+        if stopped.is_some() {
+            planned.push((path, stopped));
+            continue;
+        }
+        if options.max_files.is_some_and(|max_files| selected >= max_files) {
+            debug!("stage=scan dir={} reason=max-files stopping", path.display());
+            stopped = Some("beyond --max-files");
+            planned.push((path, stopped));
+            continue;
+        }
+        if let Some(since) = options.since {
+            if DateTime::<Local>::from(modified) < since {
+                debug!("stage=scan file={} reason=older-than-start skipped", path.display());
+                if options.newest_first {
+                    stopped = Some("older than --start");
+                }
+                planned.push((path, Some("older than --start")));
+                continue;
+            }
+        }
+        if let Some(since) = options.entries_since {
+            if DateTime::<Local>::from(modified) < since && last_entry(&path, options).is_some_and(|last| last < since) {
+                debug!("stage=scan file={} reason=older-than-query skipped", path.display());
+                planned.push((path, Some("older than the query's time bound")));
+                continue;
+            }
+        }
+        selected += 1;
+        planned.push((path, None));
+    }
+    Ok(planned)
+}
+
+#[derive(Clone)]
+struct Source {
+    label: Option<String>,
+    path: String,
+}
+
+impl Source {
+    fn from_matches(matches: &clap::ArgMatches) -> Result<Vec<Source>, Box<dyn Error>> {
+        let mut sources: Vec<Source> = matches.value_of("path").map(|path| Source { label: None, path: local_path(path) }).into_iter().collect();
+        for spec in matches.values_of("label").into_iter().flatten() {
+            let (label, path) = spec.split_once('=').filter(|(label, path)| !label.is_empty() && !path.is_empty()).ok_or_else(|| format!("invalid --label: {} (expected NAME=PATH)", spec))?;
+            sources.push(Source { label: Some(label.to_string()), path: local_path(path) });
+        }
+        Ok(sources)
+    }
+}
+
+fn read_sources(sources: &[Source], options: &ReadOptions) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    let mut streams = Vec::new();
+    for source in sources {
+        let mut read = read_nginx_logs(&source.path, options)?;
+        if let Some(label) = &source.label {
+            for log in &mut read {
+                log.extra.insert(String::from("source"), label.clone());
+            }
+        }
+        streams.push(read);
+    }
+    Ok(pipeline::combine(streams, options))
+}
+
+fn read_log_path(path: &Path, options: &ReadOptions, stats: &mut ReadStats) -> Result<Vec<NginxLog>, Box<dyn Error>> {
     let logs = if path.is_dir() {
-        let mut logs = Vec::new();
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                if ext == "log" || ext == "gz" {
-                    if ext == "gz" {
-                        let temp_dir = tempfile::tempdir()?;
-                        let temp_path = temp_dir.path().join(path.file_name().unwrap());
-                        extract_tar(&path, &temp_path)?;
-                        logs.append(&mut read_nginx_logs(temp_path)?);
-                        fs::remove_file(temp_path)?;
-                    } else {
-                        logs.append(&mut read_nginx_logs(path)?);
-                    }
+        let mut streams = Vec::new();
+        for path in scan_dir(path, options)? {
+            streams.push(read_log_path(&path, options, stats)?);
+        }
+        for (host, dir) in host_dirs(path)? {
+            for path in scan_dir(&dir, options)? {
+                let mut logs = read_log_path(&path, options, stats)?;
+                for log in &mut logs {
+                    log.extra.insert(String::from(HOST_FIELD), host.clone());
                 }
+                streams.push(logs);
             }
         }
-        logs
+        pipeline::combine(streams, options)
+    } else if path.extension().is_some_and(|ext| ext == "gz" || ext == "tgz") {
+        pipeline::read_gzip(path, &options.for_file(path), stats)?
+    } else if path.extension().is_some_and(|ext| ext == "zip") {
+        pipeline::read_zip(path, options, stats)?
     } else {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let options = &options.for_file(path);
+        debug!("stage=open file={} parser={}", path.display(), options.parser.name());
+        let mut reader = BufReader::new(File::open(path)?);
         let mut logs = Vec::new();
-        for line in reader.lines() {
-            let line = line?;
-            if let Some(log) = parse_nginx_log_line(&line) {
-                logs.push(log);
-            }
+        read_lines(&mut reader, path, options, stats, |log| {
+            logs.push(log);
+            Ok(())
+        })?;
+        if options.track_source {
+            pipeline::tag_file(&mut logs, &path.display().to_string());
         }
         logs
     };
     Ok(logs)
 }
 
-fn trend_analysis(logs: &[NginxLog]) -> HashMap<String, u64> {
-    let mut trends = HashMap::new();
-    for log in logs {
-        let key = log.request_time.format("%Y-%m-%d").to_string();
-        *trends.entry(key).or_insert(0) += 1;
+// Hands every entry to `visit` as it is parsed, so only the current line is held. Archives are still
+// read one at a time into memory, and files come in read order rather than merged by time
+fn stream_sources(sources: &[Source], options: &ReadOptions, mut visit: impl FnMut(NginxLog) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    for source in sources {
+        let path = Path::new(&source.path);
+        let mut files: Vec<(PathBuf, Option<String>)> = Vec::new();
+        if path.is_dir() {
+            files.extend(scan_dir(path, options)?.into_iter().map(|file| (file, None)));
+            for (host, dir) in host_dirs(path)? {
+                files.extend(scan_dir(&dir, options)?.into_iter().map(|file| (file, Some(host.clone()))));
+            }
+        } else {
+            files.push((path.to_path_buf(), None));
+        }
+        let mut stats = ReadStats::default();
+        for (file, host) in files {
+            let mut tagged = |mut log: NginxLog| {
+                if let Some(host) = &host {
+                    log.extra.insert(String::from(HOST_FIELD), host.clone());
+                }
+                if let Some(label) = &source.label {
+                    log.extra.insert(String::from("source"), label.clone());
+                }
+                visit(log)
+            };
+            if pipeline::is_tarball(&file) || file.extension().is_some_and(|ext| ext == "zip") {
+                for log in read_log_path(&file, options, &mut stats)? {
+                    tagged(log)?;
+                }
+                continue;
+            }
+            let options = options.for_file(&file);
+            debug!("stage=open file={} parser={}", file.display(), options.parser.name());
+            let input = File::open(&file).map_err(|e| in_path(file.display(), e))?;
+            let mut reader: Box<dyn BufRead> = if file.extension().is_some_and(|ext| ext == "gz") { Box::new(BufReader::new(MultiGzDecoder::new(input))) } else { Box::new(BufReader::new(input)) };
+            read_lines(&mut reader, &file, &options, &mut stats, &mut tagged).map_err(|e| in_path(file.display(), e))?;
+        }
+        info!("stage=read path={} lines={} failed={} streamed=true", source.path, stats.lines, stats.failed);
+        if let Some(limit) = options.max_errors {
+            if limit.exceeded(&stats) {
+                return Err(too_many_errors(limit, &stats));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Parses a plain stream line by line, following #Fields: directives in W3C logs, and hands over each entry
+fn read_lines<R: BufRead>(reader: &mut R, path: &Path, options: &ReadOptions, stats: &mut ReadStats, mut visit: impl FnMut(NginxLog) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    let mut number = 0;
+    // the columns of the file's last #Fields: directive, for W3C logs
+    let mut fields: Option<W3cFormat> = None;
+    while let Some(line) = pipeline::read_lossy_line(reader, &mut buffer)? {
+        number += 1;
+        let line = line.trim_matches('\0');
+        if let LineParser::W3c(base) = options.parser {
+            if let Some(format) = pipeline::w3c_directive(base, line) {
+                fields = Some(format);
+            }
+        }
+        let parser = fields.as_ref().map_or(options.parser, LineParser::W3c);
+        if parser.skips(line) {
+            continue;
+        }
+        stats.lines += 1;
+        match parser.parse_line(line) {
+            Ok(mut log) => {
+                if options.track_source {
+                    log.extra.insert(String::from(pipeline::LINE_FIELD), number.to_string());
+                }
+                if options.keep_raw {
+                    log.raw = Some(line.to_string());
+                }
+                visit(log)?;
+            }
+            Err(error) => {
+                trace!("stage=parse file={} line={} skipped reason={}", path.display(), number, error);
+                stats.failed += 1;
+                if let Some(limit @ ErrorLimit::Count(_)) = options.max_errors {
+                    if limit.exceeded(stats) {
+                        return Err(too_many_errors(limit, stats));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Latest entry in the tail of a plain log file; None for compressed files or when nothing there parses
+fn last_entry(path: &Path, options: &ReadOptions) -> Option<DateTime<Local>> {
+    if !path.extension().is_some_and(|ext| ext == "log") {
+        return None;
     }
-    trends
+    let mut file = File::open(path).ok()?;
+    let start = file.metadata().ok()?.len().saturating_sub(TAIL_SIZE);
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    let parser = options.for_file(path).parser;
+    // the first line is cut short unless the tail is the whole file
+    String::from_utf8_lossy(&tail)
+        .lines()
+        .skip(usize::from(start > 0))
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !parser.skips(line))
+        .filter_map(|line| parser.parse(line))
+        .map(|log| log.request_time)
+        .max()
 }
 
+fn first_line(path: &Path, wanted: impl Fn(&str) -> bool) -> Result<Option<String>, Box<dyn Error>> {
+    let path = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)?.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_file()).collect();
+        files.sort();
+        match files.into_iter().next() {
+            Some(file) => file,
+            None => return Ok(None),
+        }
+    } else {
+        path.to_path_buf()
+    };
+    let file = File::open(&path).map_err(|e| in_path(path.display(), e))?;
+    let mut reader: Box<dyn BufRead> = if path.extension().is_some_and(|ext| ext == "gz") { Box::new(BufReader::new(MultiGzDecoder::new(file))) } else { Box::new(BufReader::new(file)) };
+    let mut buffer = Vec::new();
+    while let Some(line) = pipeline::read_lossy_line(&mut reader, &mut buffer)? {
+        if wanted(&line) {
+            return Ok(Some(line));
+        }
+    }
+    Ok(None)
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let logs = read_nginx_logs("/path/to/logs")?;
-    let start_date = Local.ymd(2022, 1, 1).and_hms(0, 0, 0);
-    let end_date = Local.ymd(2022, 1, 31).and_hms(0, 0, 0);
-    let filtered_logs = filter_logs(&logs, Some(start_date), Some(end_date), Some(200), None, None);
-    let trends = trend_analysis(&filtered_logs);
-    for (date, count) in trends {
-        println!("{}: {}", date, count);
+fn run_trend(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, output: &str) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let split = match matches.value_of("split_by") {
+        Some("status_class") | Some("status-class") => Some(None),
+        Some(field) => Some(Some(GroupField::parse(field)?)),
+        None => None,
+    };
+    let trends = match &split {
+        Some(None) => trend_by(logs, interval, align, |log| format!("{}xx", log.status / 100)),
+        Some(Some(field)) => trend_by(logs, interval, align, |log| field.key(log)),
+        None => trend_analysis(logs, interval, align).into_iter().map(|(start, count)| (start, BTreeMap::from([(String::from("requests"), count)]))).collect(),
+    };
+    let mut columns: Vec<String> = match split {
+        Some(None) => ["2xx", "3xx", "4xx", "5xx"].iter().map(|class| class.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    for counts in trends.values() {
+        for column in counts.keys() {
+            if !columns.contains(column) {
+                columns.push(column.clone());
+            }
+        }
+    }
+    if !matches!(split, Some(None)) {
+        columns.sort();
+    }
+    let timestamps = match display.format {
+        TimeFormat::Default => TimeDisplay { format: TimeFormat::Iso8601, zone: display.zone },
+        _ => display.clone(),
+    };
+    let count = |counts: &BTreeMap<String, u64>, column: &String| counts.get(column).copied().unwrap_or(0);
+    match output {
+        "csv" => {
+            let mut header = vec![String::from("timestamp")];
+            header.extend(columns.iter().cloned());
+            println!("{}", csv_row(&header));
+            for (start, counts) in &trends {
+                let mut row = vec![timestamps.render(*start)];
+                row.extend(columns.iter().map(|column| count(counts, column).to_string()));
+                println!("{}", csv_row(&row));
+            }
+        }
+        "json" => {
+            let rows: Vec<serde_json::Value> = trends
+                .iter()
+                .map(|(start, counts)| {
+                    let mut row = serde_json::Map::new();
+                    row.insert(String::from("timestamp"), timestamps.json(*start));
+                    for column in &columns {
+                        row.insert(column.clone(), count(counts, column).into());
+                    }
+                    serde_json::Value::Object(row)
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        _ if split.is_some() => {
+            let mut titles = vec![Cell::new("Period").style_spec("bFg")];
+            titles.extend(columns.iter().map(|column| Cell::new(column).style_spec("bFg")));
+            let mut table = Table::new();
+            table.set_titles(Row::new(titles));
+            for (start, counts) in &trends {
+                let mut cells = vec![Cell::new(&interval.label(*start, align))];
+                cells.extend(columns.iter().map(|column| Cell::new(&count(counts, column).to_string())));
+                table.add_row(Row::new(cells));
+            }
+            table.printstd();
+        }
+        _ => {
+            let totals: BTreeMap<DateTime<Local>, u64> = trends.iter().map(|(start, counts)| (*start, counts.values().sum())).collect();
+            for (start, count) in &totals {
+                println!("{}: {}", interval.label(*start, align), count);
+            }
+            if !matches.is_present("no_sparklines") && !totals.is_empty() {
+                let errors = trend_matching(logs, interval, align, |log| log.status >= 500);
+                let counts: Vec<f64> = totals.values().map(|&count| count as f64).collect();
+                let rates: Vec<f64> = totals
+                    .iter()
+                    .map(|(start, &count)| errors[start] as f64 / count.max(1) as f64 * 100.0)
+                    .collect();
+                println!();
+                println!("requests    {} (max {})", sparkline(&counts), totals.values().max().unwrap());
+                println!("error rate  {} (max {:.1}% 5xx)", sparkline(&rates), rates.iter().copied().fold(0.0, f64::max));
+            }
+        }
     }
     Ok(())
 }
 
+fn csv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn run_report(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let db = matches.value_of("geoip_db").map(GeoDb::open).transpose()?;
+    let level = match matches.value_of("geo_level") {
+        Some("city") => GeoLevel::City,
+        _ => GeoLevel::Country,
+    };
+    if matches.is_present("format") || matches.is_present("email") {
+        let top: usize = matches.value_of("top").unwrap().parse()?;
+        let title = summary::title(logs);
+        let sections = summary::traffic_sections(logs, top, db.as_ref().map(|db| (db, level)));
+        let (markdown, html) = (summary::render_markdown(&title, &sections), summary::render_html(&title, &sections));
+        if let Some(to) = matches.values_of("email") {
+            let to: Vec<&str> = to.collect();
+            let subject = matches.value_of("subject").map(String::from).unwrap_or(title);
+            mail::send(matches.value_of("smtp").unwrap(), mail::Report { from: matches.value_of("from").unwrap(), to: to.clone(), subject, text: markdown, html })?;
+            println!("Sent report to {}", to.join(", "));
+        } else if matches.value_of("format") == Some("html") {
+            print!("{}", html);
+        } else {
+            print!("{}", markdown);
+        }
+        return Ok(());
+    }
+    let db = db.unwrap();
+    let counts = geo_counts(logs, &db, level);
+    if let Some(path) = matches.value_of("geojson") {
+        write_geojson(path, &counts)?;
+    }
+    let mut table = table!([bFg -> "Country", "City", "Requests"]);
+    for count in &counts {
+        table.add_row(row![count.country, count.city.as_deref().unwrap_or(""), count.count]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn otlp_exporter(matches: &clap::ArgMatches) -> Result<OtlpExporter, Box<dyn Error>> {
+    let mut exporter = OtlpExporter::new(matches.value_of("endpoint").unwrap(), matches.value_of("service_name").unwrap())
+        .batch_size(matches.value_of("batch_size").unwrap().parse()?);
+    for header in matches.values_of("header").into_iter().flatten() {
+        let (name, value) = header.split_once('=').ok_or_else(|| format!("invalid header: {} (expected NAME=VALUE)", header))?;
+        exporter = exporter.header(name, value);
+    }
+    Ok(exporter)
+}
+
+fn run_otlp(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let sent = otlp_exporter(matches)?.export(logs)?;
+    println!("Exported {} log records", sent);
+    Ok(())
+}
+
+fn run_browsers(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let trend = browser_version_trend(logs, interval, align, matches.value_of("browser"));
+    let shown = &trend.versions[..trend.versions.len().min(top)];
+    let mut titles = vec![Cell::new("Period").style_spec("bFg")];
+    titles.extend(shown.iter().map(|version| Cell::new(version).style_spec("bFg")));
+    if trend.versions.len() > shown.len() {
+        titles.push(Cell::new("Other").style_spec("bFg"));
+    }
+    let mut table = Table::new();
+    table.set_titles(Row::new(titles));
+    for (start, counts) in &trend.buckets {
+        let total: u64 = counts.values().sum();
+        let share = |count: u64| format!("{:.1}%", count as f64 * 100.0 / total as f64);
+        let mut cells = vec![Cell::new(&interval.label(*start, align))];
+        cells.extend(shown.iter().map(|version| Cell::new(&share(counts.get(version).copied().unwrap_or(0)))));
+        if trend.versions.len() > shown.len() {
+            let other: u64 = counts.iter().filter(|(version, _)| !shown.contains(version)).map(|(_, count)| count).sum();
+            cells.push(Cell::new(&share(other)));
+        }
+        table.add_row(Row::new(cells));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_cve_scan(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut rules = if matches.is_present("no_default_rules") { Vec::new() } else { default_rules() };
+    for path in matches.values_of("rules").into_iter().flatten() {
+        rules.append(&mut load_rules(path)?);
+    }
+    let mut hits = cve_scan(logs, &rules);
+    if !matches.is_present("signatures_only") {
+        let limits = Limits { request_line: matches.value_of("max_request_line").unwrap().parse()?, query: matches.value_of("max_query").unwrap().parse()? };
+        hits.append(&mut suspicious_requests(logs, &limits));
+    }
+    let mut table = table!([bFg -> "Rule", "Hits", "Non-404", "Statuses", "IPs", "Top IPs"]);
+    for hit in &hits {
+        let mut statuses: Vec<_> = hit.statuses.iter().collect();
+        statuses.sort();
+        let statuses: Vec<String> = statuses.iter().map(|(status, count)| format!("{}x{}", status, count)).collect();
+        let mut ips: Vec<_> = hit.ips.iter().collect();
+        ips.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let top: Vec<String> = ips.iter().take(5).map(|(ip, count)| format!("{} ({})", ip, count)).collect();
+        table.add_row(row![hit.id, hit.hits, hit.non_404, statuses.join(" "), hit.ips.len(), top.join("\n")]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_abuse(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let min_score: f64 = matches.value_of("min_score").unwrap().parse()?;
+    let geo = matches.value_of("geoip_db").map(GeoDb::open).transpose()?;
+    let mut table = table!([bFg -> "Client", "Score", "Requests", "Factors"]);
+    for score in abuse_scores(logs, &default_rules(), geo.as_ref()).into_iter().filter(|score| score.score >= min_score).take(top) {
+        let factors: Vec<String> = score.factors.iter().map(|(points, reason)| format!("+{:.1} {}", points, reason)).collect();
+        table.add_row(row![score.ip, format!("{:.1}", score.score), score.requests, factors.join("\n")]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_lint(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let options = lint::LintOptions {
+        tolerance: parse_window(matches.value_of("tolerance").unwrap())?,
+        max_gap: parse_window(matches.value_of("max_gap").unwrap())?,
+        min_duplicates: matches.value_of("min_duplicates").unwrap().parse()?,
+        now: Local::now(),
+    };
+    let examples: usize = matches.value_of("examples").unwrap().parse()?;
+    let issues = lint::lint(logs, &options);
+    let mut table = table!([bFg -> "Check", "Problems"]);
+    for check in lint::CHECKS {
+        let count = issues.iter().filter(|issue| issue.check == check).count();
+        table.add_row(Row::new(vec![Cell::new(check), Cell::new(&count.to_string()).style_spec(if count > 0 { "Fr" } else { "Fg" })]));
+    }
+    print_table(table, truncate);
+    if issues.is_empty() {
+        println!("{} entries checked, no problems found", logs.len());
+        return Ok(());
+    }
+    let mut table = table!([bFg -> "Check", "Source", "Entry", "Time", "Detail"]);
+    for check in lint::CHECKS {
+        for issue in issues.iter().filter(|issue| issue.check == check).take(examples) {
+            table.add_row(row![issue.check, issue.source, issue.entry, display.render(issue.time), issue.detail]);
+        }
+    }
+    print_table(table, truncate);
+    Err(format!("{} problems found in {} entries", issues.len(), logs.len()).into())
+}
+
+fn run_rollup(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let store = matches.value_of("store").unwrap();
+    let top_paths: usize = matches.value_of("paths").unwrap().parse()?;
+    let last: usize = matches.value_of("last").unwrap().parse()?;
+    let mut rollup = rollup::Rollup::load_or_default(store)?;
+    let update = rollup.update(logs, top_paths);
+    rollup.save(store)?;
+    println!("{}: added {} entries, skipped {} already rolled up", store, update.added, update.skipped);
+    let (buckets, interval) = match matches.value_of("show").unwrap() {
+        "hour" => (&rollup.hourly, Interval::Seconds(3600)),
+        _ => (&rollup.daily, Interval::Days(1)),
+    };
+    let mut table = table!([bFg -> "Period", "Requests", "Bytes", "4xx", "5xx", "Top Path"]);
+    for (start, summary) in buckets.iter().rev().take(last).rev() {
+        let top = summary.top_path().map(|(path, count)| format!("{} ({})", path, count)).unwrap_or_default();
+        table.add_row(row![interval.label(*start, Alignment::Calendar), summary.requests, human_size(summary.bytes), summary.errors(4), summary.errors(5), top]);
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn run_notify(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let webhook = matches.value_of("webhook").unwrap();
+    let digest = notify::digest(logs, matches.value_of("period").unwrap(), top);
+    let discord = match matches.value_of("kind") {
+        Some(kind) => kind == "discord",
+        None => webhook.contains("discord.com/") || webhook.contains("discordapp.com/"),
+    };
+    let payload = if discord { notify::discord_payload(&digest) } else { notify::slack_payload(&digest) };
+    if matches.is_present("print") {
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+    notify::post(webhook, &payload)?;
+    println!("Posted {} ({} requests, {} anomalies)", digest.title, digest.requests, digest.anomalies.len());
+    Ok(())
+}
+
+fn run_rate_limits(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let affect: f64 = matches.value_of("affect").unwrap().parse()?;
+    if !(0.0..100.0).contains(&affect) {
+        return Err(format!("--affect must be between 0 and 100, got {}", affect).into());
+    }
+    let burst: u32 = matches.value_of("burst").unwrap().parse()?;
+    let paths: usize = matches.value_of("paths").unwrap().parse()?;
+    let streams = ratelimit::client_streams(logs.iter());
+    if streams.is_empty() {
+        return Err("no entries to analyze".into());
+    }
+    let mut table = table!([bFg -> "Burst", "Rate", "Clients Affected"]);
+    for candidate in ratelimit::BURSTS {
+        let recommendation = ratelimit::recommend(&streams, candidate, affect);
+        table.add_row(row![candidate, ratelimit::nginx_rate(recommendation.rate), format!("{} of {}", recommendation.affected, recommendation.clients)]);
+    }
+    table.printstd();
+    let overall = ratelimit::recommend(&streams, burst, affect);
+    println!();
+    println!("# throttles {} of {} clients at burst={}", overall.affected, overall.clients, burst);
+    println!("limit_req_zone $binary_remote_addr zone=per_ip:10m rate={};", ratelimit::nginx_rate(overall.rate));
+    let mut locations = Vec::new();
+    for (path, entries) in ratelimit::top_paths(logs, paths) {
+        let recommendation = ratelimit::recommend(&ratelimit::client_streams(entries.into_iter()), burst, affect);
+        if recommendation.rate * 2.0 > overall.rate || path.contains('{') {
+            continue;
+        }
+        let zone = ratelimit::zone_name(&path);
+        println!("limit_req_zone $binary_remote_addr zone={}:10m rate={};", zone, ratelimit::nginx_rate(recommendation.rate));
+        locations.push((path, zone));
+    }
+    println!();
+    println!("server {{");
+    println!("    limit_req zone=per_ip burst={} nodelay;", burst);
+    for (path, zone) in locations {
+        println!();
+        println!("    location = {} {{", path);
+        println!("        limit_req zone=per_ip burst={} nodelay;", burst);
+        println!("        limit_req zone={} burst={} nodelay;", zone, burst);
+        println!("    }}");
+    }
+    println!("}}");
+    Ok(())
+}
+
+fn run_hosts(logs: &[NginxLog], bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let hosts = host_stats(logs);
+    let total: u64 = hosts.iter().map(|host| host.requests).sum();
+    let max = hosts.iter().map(|host| host.requests).max().unwrap_or(0);
+    let mut table = bar_table(&["Host", "Requests", "Share", "Bytes", "Clients", "4xx", "5xx"], bars);
+    for host in &hosts {
+        let row = row![
+            host.host,
+            host.requests,
+            format!("{:.1}%", host.requests as f64 / total.max(1) as f64 * 100.0),
+            human_size(host.bytes),
+            host.clients,
+            format!("{:.2}%", host.rate(host.client_errors)),
+            format!("{:.2}%", host.rate(host.server_errors))
+        ];
+        table.add_row(bar_row(row, host.requests, max, bars));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_cardinality(logs: &[NginxLog], matches: &clap::ArgMatches, json: bool, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let fields: Vec<String> = match matches.values_of("fields") {
+        Some(fields) => fields.flat_map(|fields| fields.split(',')).map(String::from).collect(),
+        None => cardinality::default_fields(logs),
+    };
+    let report = cardinality::cardinality(logs, &fields);
+    if json {
+        let rows: Vec<serde_json::Value> = report
+            .iter()
+            .map(|row| serde_json::json!({ "field": row.field, "distinct": row.distinct, "entries": row.present, "most_common": row.top.as_ref().map(|(value, count)| serde_json::json!({ "value": value, "count": count })) }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    let mut table = table!([bFg -> "Field", "Distinct", "Entries", "Distinct / Entries", "Most Common", "Share"]);
+    for field in &report {
+        let (top, share) = match &field.top {
+            Some((value, count)) => (value.clone(), format!("{:.1}%", *count as f64 / field.present as f64 * 100.0)),
+            None => (String::from("-"), String::from("-")),
+        };
+        let ratio = if field.present == 0 { String::from("-") } else { format!("{:.1}%", field.distinct as f64 / field.present as f64 * 100.0) };
+        table.add_row(row![field.field, field.distinct, field.present, ratio, top, share]);
+    }
+    print_table(table, truncate);
+    println!("{} entries", logs.len());
+    Ok(())
+}
+
+fn run_sql(logs: &[NginxLog], matches: &clap::ArgMatches, output: &str, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let (columns, rows) = sql::query(logs, matches.value_of("query").unwrap())?;
+    match output {
+        "json" => {
+            let rows: Vec<serde_json::Map<String, serde_json::Value>> = rows.iter().map(|row| columns.iter().cloned().zip(row.iter().map(|value| serde_json::Value::from(value.as_str()))).collect()).collect();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        "csv" => {
+            println!("{}", csv_row(&columns));
+            for row in &rows {
+                println!("{}", csv_row(row));
+            }
+        }
+        _ => {
+            let mut table = Table::new();
+            table.set_titles(Row::new(columns.iter().map(|column| Cell::new(column).style_spec("bFg")).collect()));
+            for row in &rows {
+                table.add_row(Row::new(row.iter().map(|value| Cell::new(value)).collect()));
+            }
+            print_table(table, truncate);
+            println!("{} rows", rows.len());
+        }
+    }
+    Ok(())
+}
+
+fn run_balance(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let max_skew: f64 = matches.value_of("max_skew").unwrap().parse()?;
+    let error_factor: f64 = matches.value_of("error_factor").unwrap().parse()?;
+    let min_errors: u64 = matches.value_of("min_errors").unwrap().parse()?;
+    let periods = match matches.value_of("by").unwrap() {
+        "upstream" => balance_by(logs, interval, align, upstream_of),
+        _ => balance_by(logs, interval, align, |log| host_of(log).to_string()),
+    };
+    let hosts: BTreeSet<&String> = periods.values().flat_map(|hosts| hosts.keys()).collect();
+    if hosts.len() < 2 {
+        return Err("need at least two hosts to compare: read a directory with one subdirectory per server, or use --by upstream with $upstream_addr in the log format".into());
+    }
+    let mut titles = vec![Cell::new("Period").style_spec("bFg"), Cell::new("Skew").style_spec("bFg")];
+    titles.extend(hosts.iter().map(|host| Cell::new(host).style_spec("bFg")));
+    let mut table = Table::new();
+    table.set_titles(Row::new(titles));
+    let mut skewed = 0;
+    let mut flagged: BTreeMap<&str, u64> = BTreeMap::new();
+    for (start, buckets) in &periods {
+        let total: u64 = buckets.values().map(|bucket| bucket.requests).sum();
+        let period_skew = skew(buckets);
+        let unbalanced = period_skew.is_some_and(|value| value > max_skew);
+        skewed += u64::from(unbalanced);
+        let failing = misbehaving(buckets, error_factor, min_errors);
+        for host in &failing {
+            *flagged.entry(*host).or_insert(0) += 1;
+        }
+        let mut cells = vec![
+            Cell::new(&interval.label(*start, align)),
+            Cell::new(&period_skew.map(|value| format!("{:.2}", value)).unwrap_or_else(|| String::from("-"))).style_spec(if unbalanced { "Fr" } else { "" }),
+        ];
+        for host in &hosts {
+            let cell = match buckets.get(*host) {
+                Some(bucket) => Cell::new(&format!("{:.1}% ({:.1}% 5xx)", bucket.requests as f64 / total as f64 * 100.0, bucket.error_rate())),
+                None => Cell::new("-"),
+            };
+            cells.push(if failing.contains(&host.as_str()) { cell.style_spec("Fr") } else { cell });
+        }
+        table.add_row(Row::new(cells));
+    }
+    table.printstd();
+    println!("{} of {} periods had a skew above {}", skewed, periods.len(), max_skew);
+    for (host, count) in flagged {
+        println!("{}: 5xx rate over {}x the other hosts in {} periods", host, error_factor, count);
+    }
+    Ok(())
+}
+
+fn run_canary(logs: &[NginxLog], matches: &clap::ArgMatches, json: bool) -> Result<(), Box<dyn Error>> {
+    let canary = matches.value_of("canary").unwrap();
+    let baseline: Vec<&str> = matches.values_of("baseline").into_iter().flatten().flat_map(|hosts| hosts.split(',')).collect();
+    let min_requests: u64 = matches.value_of("min_requests").unwrap().parse()?;
+    let thresholds = Thresholds {
+        max_5xx_increase: matches.value_of("max_5xx_increase").unwrap().parse()?,
+        max_4xx_increase: matches.value_of("max_4xx_increase").unwrap().parse()?,
+        max_latency_ratio: matches.value_of("max_latency_ratio").unwrap().parse()?,
+    };
+    let (baseline_logs, canary_logs) = canary::split(logs, &baseline, canary);
+    if (canary_logs.len() as u64) < min_requests || (baseline_logs.len() as u64) < min_requests {
+        return Err(format!(
+            "not enough traffic to judge the canary: {} has {} requests and the baseline {} while they overlap (--min-requests {})",
+            canary,
+            canary_logs.len(),
+            baseline_logs.len(),
+            min_requests
+        )
+        .into());
+    }
+    let checks = canary::checks(&canary::sample(&baseline_logs), &canary::sample(&canary_logs), &thresholds);
+    let failed: Vec<&str> = checks.iter().filter(|check| check.passed == Some(false)).map(|check| check.metric.as_str()).collect();
+    if json {
+        let rows: Vec<serde_json::Value> = checks
+            .iter()
+            .map(|check| serde_json::json!({ "metric": check.metric, "baseline": check.baseline, "canary": check.canary, "limit": check.limit, "passed": check.passed }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "canary": canary, "verdict": if failed.is_empty() { "pass" } else { "fail" }, "checks": rows }))?);
+    } else {
+        let mut table = table!([bFg -> "Metric", "Baseline", canary, "Limit", "Result"]);
+        for check in &checks {
+            let result = match check.passed {
+                Some(true) => Cell::new("pass").style_spec("Fg"),
+                Some(false) => Cell::new("FAIL").style_spec("bFr"),
+                None => Cell::new(""),
+            };
+            table.add_row(Row::new(vec![Cell::new(&check.metric), Cell::new(&check.baseline), Cell::new(&check.canary), Cell::new(&check.limit), result]));
+        }
+        table.printstd();
+    }
+    if !failed.is_empty() {
+        return Err(format!("canary {} failed: {}", canary, failed.join(", ")).into());
+    }
+    if !json {
+        println!("canary {} passed", canary);
+    }
+    Ok(())
+}
+
+fn run_replay(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let methods: Vec<&str> = matches.value_of("methods").unwrap().split(',').map(str::trim).filter(|method| !method.is_empty()).collect();
+    let unsafe_methods: Vec<&str> = methods.iter().copied().filter(|method| !["GET", "HEAD", "OPTIONS"].contains(&method.to_ascii_uppercase().as_str())).collect();
+    if !unsafe_methods.is_empty() && !matches.is_present("allow_unsafe") {
+        return Err(format!("refusing to replay {} requests without --allow-unsafe", unsafe_methods.join(", ")).into());
+    }
+    let max_requests: usize = matches.value_of("max_requests").unwrap().parse()?;
+    let timeout = parse_duration(matches.value_of("timeout").unwrap())?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let replayer = Replayer::new(matches.value_of("target").unwrap(), Duration::from_secs_f64(timeout))?
+        .rate(parse_rate(matches.value_of("rate").unwrap())?)
+        .methods(&methods)
+        .max_requests(if max_requests == 0 { None } else { Some(max_requests) });
+    let report = replayer.replay(logs);
+    let mut table = table!([bFg -> "Status", "Responses"]);
+    for (status, count) in &report.statuses {
+        table.add_row(row![status, count]);
+    }
+    table.printstd();
+    if !report.mismatches.is_empty() {
+        let mut table = table!([bFg -> "Method", "Path", "Logged", "Replayed"]);
+        for mismatch in report.mismatches.iter().take(top) {
+            let replayed = match &mismatch.replayed {
+                Ok(status) => Cell::new(&status.to_string()).style_spec(if *status >= 500 { "Fr" } else { "" }),
+                Err(error) => Cell::new(error).style_spec("Fr"),
+            };
+            table.add_row(Row::new(vec![Cell::new(&mismatch.method), Cell::new(&mismatch.path), Cell::new(&mismatch.original.to_string()), replayed]));
+        }
+        print_table(table, truncate);
+    }
+    let elapsed = report.elapsed.as_secs_f64();
+    println!(
+        "Replayed {} requests in {:.1}s ({:.1} rps): {} status mismatches, {} failed, {} entries skipped",
+        report.sent,
+        elapsed,
+        if elapsed > 0.0 { report.sent as f64 / elapsed } else { 0.0 },
+        report.mismatches.len(),
+        report.errors,
+        report.skipped
+    );
+    Ok(())
+}
+
+fn run_grep(logs: &[NginxLog], matched: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if matches.is_present("count") {
+        writeln!(out, "{}", matched.len())?;
+        out.flush()?;
+        return Ok(());
+    }
+    let context = matches.value_of("context").map(str::parse::<usize>).transpose()?;
+    let before = matches.value_of("before_context").map(str::parse::<usize>).transpose()?.or(context).unwrap_or(0);
+    let after = matches.value_of("after_context").map(str::parse::<usize>).transpose()?.or(context).unwrap_or(0);
+    // like grep, name the file on every line once more than one file was read
+    let files: HashSet<&str> = logs.iter().map(|log| pipeline::source_of(log).0).collect();
+    let with_filename = matches.is_present("with_filename") || (files.len() > 1 && !matches.is_present("no_filename"));
+    let line_number = matches.is_present("line_number");
+    let mut by_file: HashMap<&str, BTreeMap<usize, &NginxLog>> = HashMap::new();
+    if before > 0 || after > 0 {
+        for log in logs {
+            let (file, line) = pipeline::source_of(log);
+            by_file.entry(file).or_default().insert(line, log);
+        }
+    }
+    let matched_lines: HashSet<(&str, usize)> = matched.iter().map(pipeline::source_of).collect();
+    let mut printed: HashSet<(&str, usize)> = HashSet::new();
+    let mut last: Option<(&str, usize)> = None;
+    for log in matched {
+        let (file, line) = pipeline::source_of(log);
+        let window: Vec<(usize, &NginxLog)> = match by_file.get(file) {
+            Some(lines) => lines.range(line.saturating_sub(before)..=line + after).map(|(number, entry)| (*number, *entry)).collect(),
+            None => vec![(line, log)],
+        };
+        for (number, entry) in window {
+            if !printed.insert((file, number)) {
+                continue;
+            }
+            // groups of context lines are separated by -- as in grep
+            if !by_file.is_empty() && last.is_some_and(|(last_file, last_line)| last_file != file || number > last_line + 1) {
+                writeln!(out, "--")?;
+            }
+            last = Some((file, number));
+            let separator = if matched_lines.contains(&(file, number)) { ':' } else { '-' };
+            if with_filename {
+                write!(out, "{}{}", file, separator)?;
+            }
+            if line_number {
+                write!(out, "{}{}", number, separator)?;
+            }
+            writeln!(out, "{}", entry.raw.as_deref().unwrap_or_default())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn run_export(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay) -> Result<(), Box<dyn Error>> {
+    let base_url = matches.value_of("base_url");
+    if let Some(path) = matches.value_of("har") {
+        let har = serde_json::to_string_pretty(&export::har(logs, base_url))?;
+        if path == "-" {
+            println!("{}", har);
+        } else {
+            fs::write(path, har)?;
+            println!("Wrote {} entries to {}", logs.len(), path);
+        }
+        return Ok(());
+    }
+    for log in logs {
+        if let Some(command) = export::curl_command(log, base_url) {
+            println!("# {} {} {}", display.render(log.request_time), log.status, log.client());
+            println!("{}", command);
+        }
+    }
+    Ok(())
+}
+
+fn run_top_ips(logs: &[NginxLog], matches: &clap::ArgMatches, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let mut clients: HashMap<String, (u64, u64)> = HashMap::new();
+    for log in logs {
+        let entry = clients.entry(log.client()).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += log.body_bytes_sent;
+    }
+    let mut clients: Vec<_> = clients.into_iter().collect();
+    clients.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+    let max = clients.first().map(|(_, (requests, _))| *requests).unwrap_or(0);
+    let mut table = bar_table(&["Client", "Requests", "Body Bytes Sent"], bars);
+    for (client, (requests, bytes)) in clients.into_iter().take(top) {
+        table.add_row(bar_row(row![client, requests, bytes], requests, max, bars));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_latency(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let width: usize = matches.value_of("width").unwrap().parse()?;
+    let histograms = latency_histograms(logs);
+    if histograms.is_empty() {
+        return Err("no entries with $request_time; add it to log_format and pass the format with --log-format".into());
+    }
+    for histogram in histograms.iter().take(top) {
+        println!("{} ({} requests)", histogram.endpoint, histogram.requests);
+        let max = histogram.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (index, count) in histogram.counts.iter().enumerate() {
+            let bar = "█".repeat((*count as f64 / max as f64 * width as f64).round() as usize);
+            println!("  {:>11} | {:<width$} {}", bucket_label(index), bar, count, width = width);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn run_slow(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let threshold = parse_duration(matches.value_of("threshold").unwrap())?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let mut slow: Vec<(&NginxLog, f64, Option<f64>)> = logs
+        .iter()
+        .filter_map(|log| Some((log, request_seconds(log)?, upstream_seconds(log))))
+        .filter(|(_, seconds, _)| *seconds > threshold)
+        .collect();
+    match matches.value_of("sort").unwrap() {
+        "time" => slow.sort_by_key(|(log, _, _)| log.request_time),
+        "upstream" => slow.sort_by(|a, b| b.2.unwrap_or(0.0).total_cmp(&a.2.unwrap_or(0.0)).then_with(|| b.1.total_cmp(&a.1))),
+        _ => slow.sort_by(|a, b| b.1.total_cmp(&a.1)),
+    }
+    let upstream = slow.iter().any(|(_, _, upstream)| upstream.is_some());
+    let mut table = if upstream {
+        table!([bFg -> "Request Time", "Client", "Request", "Status", "Total", "Upstream", "Nginx"])
+    } else {
+        table!([bFg -> "Request Time", "Client", "Request", "Status", "Total"])
+    };
+    let seconds = |value: Option<f64>| value.map(|value| format!("{:.3}s", value)).unwrap_or_else(|| String::from("-"));
+    for (log, total, upstream_time) in slow.iter().take(top) {
+        let mut row = row![display.render(log.request_time), log.client(), log.request.trim_matches('"'), log.status, seconds(Some(*total))];
+        if upstream {
+            row.add_cell(Cell::new(&seconds(*upstream_time)));
+            row.add_cell(Cell::new(&seconds(upstream_time.map(|upstream_time| (total - upstream_time).max(0.0)))));
+        }
+        table.add_row(row);
+    }
+    print_table(table, truncate);
+    println!("{} of {} requests took longer than {}s", slow.len(), logs.len(), threshold);
+    Ok(())
+}
+
+fn run_chart(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let kind = ChartKind::parse(matches.value_of("kind").unwrap())?;
+    let size = (matches.value_of("width").unwrap().parse()?, matches.value_of("height").unwrap().parse()?);
+    let chart = build_chart(logs, kind, interval, align)?;
+    for path in matches.value_of("svg").into_iter().chain(matches.value_of("png")) {
+        chart::render(&chart, Path::new(path), size)?;
+        println!("Wrote {} chart to {}", kind.title().to_lowercase(), path);
+    }
+    Ok(())
+}
+
+fn run_ttfb(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let threshold = parse_duration(matches.value_of("threshold").unwrap())?;
+    let periods = latency_by_class(logs, interval, align);
+    if periods.is_empty() {
+        return Err("no entries with $request_time or $upstream_header_time; add them to log_format and pass the format with --log-format".into());
+    }
+    let seconds = |value: Option<f64>| value.map(|value| format!("{:.3}s", value)).unwrap_or_else(|| String::from("-"));
+    let mut table = table!([bFg -> "Period", "Assets", "Asset p50", "Asset p95", "App", "App p50", "App p95"]);
+    let mut app_slow = 0;
+    for (start, period) in &periods {
+        let asset_p95 = percentile(&period.assets, 95.0);
+        let app_p95 = percentile(&period.app, 95.0);
+        let slow = app_p95.is_some_and(|p95| p95 > threshold) && !asset_p95.is_some_and(|p95| p95 > threshold);
+        app_slow += u64::from(slow);
+        table.add_row(Row::new(vec![
+            Cell::new(&interval.label(*start, align)),
+            Cell::new(&period.assets.len().to_string()),
+            Cell::new(&seconds(percentile(&period.assets, 50.0))),
+            Cell::new(&seconds(asset_p95)),
+            Cell::new(&period.app.len().to_string()),
+            Cell::new(&seconds(percentile(&period.app, 50.0))),
+            Cell::new(&seconds(app_p95)).style_spec(if slow { "Fr" } else { "" }),
+        ]));
+    }
+    table.printstd();
+    println!("{} of {} periods had application p95 above {}s while assets stayed below it", app_slow, periods.len(), threshold);
+    Ok(())
+}
+
+fn run_connections(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let min_requests: u64 = matches.value_of("min_requests").unwrap().parse()?;
+    let stats = connection_reuse(logs).ok_or("no entries with $connection or $connection_requests; add them to log_format and pass the format with --log-format")?;
+    println!("{} requests over {} connections, {:.2} requests per connection", stats.requests, stats.connections, stats.per_connection());
+    let share = |count: u64| if stats.connections == 0 { String::from("-") } else { format!("{:.1}%", count as f64 / stats.connections as f64 * 100.0) };
+    let mut table = table!([bFg -> "Requests per Connection", "Connections", "Share"]);
+    for (index, count) in stats.buckets.iter().enumerate() {
+        table.add_row(row![connections::bucket_label(index), count, share(*count)]);
+    }
+    table.printstd();
+    let mut table = table!([bFg -> "Client Never Reusing Connections", "Requests"]);
+    let single: Vec<_> = stats.clients.iter().filter(|client| client.never_reused() && client.requests >= min_requests).collect();
+    for client in single.iter().take(top) {
+        table.add_row(row![client.client, client.requests]);
+    }
+    print_table(table, truncate);
+    println!("{} clients with at least {} requests opened a new connection for every request", single.len(), min_requests);
+    Ok(())
+}
+
+fn run_uploads(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let logs: Vec<NginxLog> = match matches.value_of("methods") {
+        Some(methods) => logs.iter().filter(|log| log.method().is_some_and(|method| methods.split(',').any(|allowed| allowed.eq_ignore_ascii_case(method)))).cloned().collect(),
+        None => logs.to_vec(),
+    };
+    let endpoints = ingress_by(&logs, uploads::endpoint);
+    if endpoints.is_empty() {
+        return Err("no entries with $request_length or $content_length; add one to log_format and pass the format with --log-format".into());
+    }
+    let clients = ingress_by(&logs, NginxLog::client);
+    for (title, totals) in [("Endpoint", endpoints), ("Client", clients)] {
+        let max = totals.first().map(|ingress| ingress.received).unwrap_or(0);
+        let mut table = bar_table(&[title, "Requests", "Received", "Largest", "Sent"], bars);
+        for ingress in totals.iter().take(top) {
+            let row = row![ingress.key, ingress.requests, human_size(ingress.received), human_size(ingress.largest), human_size(ingress.sent)];
+            table.add_row(bar_row(row, ingress.received, max, bars));
+        }
+        print_table(table, truncate);
+    }
+    Ok(())
+}
+
+fn run_ranges(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let min_size = parse_size(matches.value_of("min_size").unwrap())? as u64;
+    let small_range = parse_size(matches.value_of("small_range").unwrap())? as u64;
+    let min_requests: u64 = matches.value_of("min_requests").unwrap().parse()?;
+    let files: Vec<_> = ranged_files(logs).into_iter().filter(|file| !file.size.is_some_and(|size| size < min_size)).collect();
+    if files.is_empty() {
+        println!("No 206 Partial Content responses for files of at least {}", human_size(min_size));
+        return Ok(());
+    }
+    let range_bytes: u64 = files.iter().map(|file| file.range_bytes).sum();
+    let full_bytes: u64 = files.iter().map(|file| file.full_bytes).sum();
+    println!("{} served in ranges, {} in full downloads of the same files", human_size(range_bytes), human_size(full_bytes));
+    let mut table = table!([bFg -> "Path", "Ranges", "Range bytes", "Full", "Full bytes", "Size"]);
+    for file in files.iter().take(top) {
+        table.add_row(row![file.path, file.range_requests, human_size(file.range_bytes), file.full_downloads, human_size(file.full_bytes), file.size.map(human_size).unwrap_or_else(|| String::from("-"))]);
+    }
+    print_table(table, truncate);
+    let clients = range_clients(logs);
+    let mut table = table!([bFg -> "Client", "Ranges", "Bytes", "Average", "Files", ""]);
+    for client in clients.iter().take(top) {
+        let flag = if client.requests >= min_requests && client.average() < small_range { "many small ranges" } else { "" };
+        table.add_row(row![client.client, client.requests, human_size(client.bytes), human_size(client.average()), client.files, Fr -> flag]);
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn run_downloads(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.value_of("path").unwrap();
+    let threshold = match matches.value_of("threshold").unwrap().trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => percent / 100.0,
+        _ => return Err(format!("invalid threshold: {} (expected a percentage, e.g. 99%)", matches.value_of("threshold").unwrap()).into()),
+    };
+    let size = match matches.value_of("size") {
+        Some(size) => parse_size(size)? as u64,
+        None => inferred_size(logs, path).filter(|size| *size > 0).ok_or_else(|| format!("no complete responses for {}; pass the file size with --size", path))?,
+    };
+    println!("{}: {} ({}), complete at {:.0}% or more", path, human_size(size), if matches.is_present("size") { "given" } else { "largest response" }, threshold * 100.0);
+    let days = downloads(logs, path, size, threshold);
+    let mut table = table!([bFg -> "Day", "Complete", "Aborted", "Completion", "Ranges", "Range bytes", "Estimated"]);
+    for (day, counts) in &days {
+        let full = counts.complete + counts.aborted;
+        let completion = if full == 0 { String::from("-") } else { format!("{:.1}%", counts.complete as f64 / full as f64 * 100.0) };
+        table.add_row(row![day, counts.complete, counts.aborted, completion, counts.ranges, human_size(counts.range_bytes), format!("{:.1}", counts.estimated(size))]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_hotlinks(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let sites: Vec<String> = matches.values_of("site").into_iter().flatten().map(String::from).collect();
+    let own = own_sites(logs, &sites);
+    if own.is_empty() {
+        return Err("cannot tell which referers are external; pass the own hosts with --site".into());
+    }
+    let report = hotlinks(logs, &own);
+    if matches.is_present("nginx_config") {
+        if report.extensions.is_empty() {
+            return Err("no hotlinked assets found, nothing to protect".into());
+        }
+        print!("{}", valid_referers_config(&own, &report.extensions));
+        return Ok(());
+    }
+    println!("{} hotlinked requests for {} ({} sites, own: {})", report.requests, human_size(report.bytes), report.sites.len(), own.join(", "));
+    for (title, other, totals) in [("Referring site", "Assets", &report.sites), ("Asset", "Sites", &report.assets)] {
+        let mut table = table!([bFg -> title, "Requests", "Bandwidth", other]);
+        for hotlink in totals.iter().take(top) {
+            table.add_row(row![hotlink.key, hotlink.requests, human_size(hotlink.bytes), hotlink.distinct]);
+        }
+        print_table(table, truncate);
+    }
+    Ok(())
+}
+
+fn run_protocols(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let logs: Vec<NginxLog> = match matches.value_of("host") {
+        Some(host) => logs.iter().filter(|log| log.field("host").is_some_and(|value| value.eq_ignore_ascii_case(host))).cloned().collect(),
+        None => logs.to_vec(),
+    };
+    let trends = trend_by(&logs, interval, align, |log| log.http_version());
+    let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+    for counts in trends.values() {
+        for (protocol, count) in counts {
+            *totals.entry(protocol.clone()).or_default() += count;
+        }
+    }
+    let share = |count: u64, of: u64| if of == 0 { String::from("-") } else { format!("{:.1}%", count as f64 / of as f64 * 100.0) };
+    let all: u64 = totals.values().sum();
+    let mut table = table!([bFg -> "Protocol", "Requests", "Share"]);
+    for (protocol, count) in &totals {
+        table.add_row(row![protocol, count, share(*count, all)]);
+    }
+    table.printstd();
+    if totals.is_empty() {
+        return Ok(());
+    }
+    let mut titles = vec![Cell::new("Period").style_spec("bFg")];
+    titles.extend(totals.keys().map(|protocol| Cell::new(protocol).style_spec("bFg")));
+    let mut table = Table::new();
+    table.set_titles(Row::new(titles));
+    for (start, counts) in &trends {
+        let requests: u64 = counts.values().sum();
+        let mut cells = vec![Cell::new(&interval.label(*start, align))];
+        cells.extend(totals.keys().map(|protocol| Cell::new(&share(counts.get(protocol).copied().unwrap_or(0), requests))));
+        table.add_row(Row::new(cells));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_anomaly(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, json: bool) -> Result<(), Box<dyn Error>> {
+    let window = Interval::parse(matches.value_of("window").unwrap())?;
+    let train = Interval::parse(matches.value_of("train").unwrap())?;
+    let threshold: f64 = matches.value_of("threshold").unwrap().parse()?;
+    let buckets = hourly_buckets(logs);
+    let newest = match buckets.keys().next_back() {
+        Some(newest) => Interval::Seconds(3600).next(*newest),
+        None => return Err("no entries to check".into()),
+    };
+    let evaluate_from = window.before(newest);
+    let train_from = train.before(evaluate_from);
+    let anomalies = detect(&buckets, train_from, evaluate_from, threshold);
+    if json {
+        let rows: Vec<serde_json::Value> = anomalies
+            .iter()
+            .map(|anomaly| serde_json::json!({ "start": display.json(anomaly.start), "metric": anomaly.metric.name(), "value": anomaly.value, "expected": anomaly.expected, "deviation": anomaly.deviation }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    let mut table = table!([bFg -> "Hour", "Metric", "Value", "Expected", "Deviation", "Kind"]);
+    for anomaly in &anomalies {
+        let (kind, style) = if anomaly.deviation > 0.0 { ("spike", "Fr") } else { ("drop", "Fy") };
+        table.add_row(Row::new(vec![
+            Cell::new(&display.render(anomaly.start)),
+            Cell::new(anomaly.metric.name()),
+            Cell::new(&anomaly.metric.render(anomaly.value)),
+            Cell::new(&anomaly.metric.render(anomaly.expected)),
+            Cell::new(&format!("{:+.1} MAD", anomaly.deviation)),
+            Cell::new(kind).style_spec(style),
+        ]));
+    }
+    if !anomalies.is_empty() {
+        table.printstd();
+    }
+    println!("{} anomalies since {}", anomalies.len(), display.render(evaluate_from));
+    Ok(())
+}
+
+fn run_peaks(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let windows = matches.value_of("windows").unwrap().split(',').map(parse_window).collect::<Result<Vec<_>, _>>()?;
+    let list = |counts: &[(String, u64)]| counts.iter().map(|(value, count)| format!("{} ({})", value, count)).collect::<Vec<_>>().join("\n");
+    let mut table = table!([bFg -> "Window", "Peak Start", "Requests", "RPS", "Top Paths", "Top Clients"]);
+    for window in windows {
+        if let Some(peak) = find_peak(logs, window, top) {
+            table.add_row(row![
+                format!("{}s", window),
+                display.render(peak.start),
+                peak.requests,
+                format!("{:.2}", peak.rps()),
+                list(&peak.top_paths),
+                list(&peak.top_ips),
+            ]);
+        }
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn run_errors(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let sub = match matches.subcommand() {
+        ("digest", Some(sub)) => sub,
+        _ => return Err("errors needs a report, e.g. errors digest".into()),
+    };
+    let top: usize = sub.value_of("top").unwrap().parse()?;
+    let min_status: u16 = sub.value_of("min_status").unwrap().parse()?;
+    let mut table = table!([bFg -> "Path", "Upstream", "Status", "Count", "First Seen", "Last Seen"]);
+    for incident in error_digest(logs, min_status).into_iter().take(top) {
+        table.add_row(row![
+            incident.template,
+            incident.upstream,
+            incident.status,
+            incident.count,
+            display.render(incident.first_seen),
+            display.render(incident.last_seen),
+        ]);
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn run_funnel(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let steps = parse_steps(matches.values_of("steps").unwrap())?;
+    let timeout = parse_window(matches.value_of("session_timeout").unwrap())?;
+    let sessions = sessions(logs, timeout);
+    let reached = funnel(&sessions, &steps);
+    let percent = |count: u64, of: u64| if of == 0 { String::from("-") } else { format!("{:.1}%", count as f64 / of as f64 * 100.0) };
+    let mut table = table!([bFg -> "Step", "Sessions", "Of First", "Of Previous", "Dropped"]);
+    for (index, step) in steps.iter().enumerate() {
+        let previous = if index == 0 { reached[0] } else { reached[index - 1] };
+        table.add_row(row![
+            format!("{}. {}", index + 1, step),
+            reached[index],
+            percent(reached[index], reached[0]),
+            percent(reached[index], previous),
+            previous - reached[index],
+        ]);
+    }
+    table.printstd();
+    println!("{} sessions in total", sessions.len());
+    Ok(())
+}
+
+fn run_entry_exit(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let timeout = parse_window(matches.value_of("session_timeout").unwrap())?;
+    let sessions = sessions(logs, timeout);
+    let report = entry_exit(&sessions, matches.is_present("include_static"));
+    let total = sessions.len().max(1) as f64;
+    for (title, counts) in [("Entry Page", ranked(report.entries, top)), ("Exit Page", ranked(report.exits, top))] {
+        let mut table = table!([bFg -> title, "Sessions", "Share"]);
+        for (page, count) in counts {
+            table.add_row(row![page, count, format!("{:.1}%", count as f64 / total * 100.0)]);
+        }
+        print_table(table, truncate);
+    }
+    Ok(())
+}
+
+fn run_graph(logs: &[NginxLog], matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let sites: Vec<String> = matches.values_of("site").into_iter().flatten().map(String::from).collect();
+    let min_count: u64 = matches.value_of("min_count").unwrap().parse()?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let include_static = matches.is_present("include_static");
+    let mut edges = navigation_edges(logs, &sites);
+    edges.retain(|(from, to), count| *count >= min_count && (include_static || !(is_static_asset(from) || is_static_asset(to))));
+    let edges = ranked(edges, top);
+    let path = matches.value_of("dot").unwrap();
+    let mut out: Box<dyn Write> = match path {
+        "-" => Box::new(io::stdout()),
+        _ => Box::new(BufWriter::new(File::create(path).map_err(|e| in_path(path, e))?)),
+    };
+    write_dot(&mut out, &edges)?;
+    out.flush()?;
+    if path != "-" {
+        println!("Wrote {} edges to {}", edges.len(), path);
+    }
+    Ok(())
+}
+
+fn run_campaigns(logs: &[NginxLog], matches: &clap::ArgMatches, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let timeout = parse_window(matches.value_of("session_timeout").unwrap())?;
+    let report = campaign_report(&sessions(logs, timeout), interval, align);
+    let mut table = table!([bFg -> "Source", "Medium", "Campaign", "Sessions", "Requests", "First Seen", "Last Seen"]);
+    for (campaign, stats) in report.campaigns.iter().take(top) {
+        table.add_row(row![
+            campaign.source,
+            campaign.medium,
+            campaign.name,
+            stats.sessions,
+            stats.requests,
+            display.render(stats.first_seen),
+            display.render(stats.last_seen),
+        ]);
+    }
+    print_table(table, truncate);
+    if report.campaigns.is_empty() {
+        return Ok(());
+    }
+    let shown: Vec<_> = report.campaigns.iter().take(top).map(|(campaign, _)| campaign).collect();
+    let mut titles = vec![Cell::new("Period").style_spec("bFg")];
+    titles.extend(shown.iter().map(|campaign| Cell::new(&campaign.label()).style_spec("bFg")));
+    let mut table = Table::new();
+    table.set_titles(Row::new(titles));
+    for (start, counts) in &report.buckets {
+        let mut cells = vec![Cell::new(&interval.label(*start, align))];
+        cells.extend(shown.iter().map(|campaign| Cell::new(&counts.get(*campaign).copied().unwrap_or(0).to_string())));
+        table.add_row(Row::new(cells));
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn ranked<K: Ord>(counts: HashMap<K, u64>, top: usize) -> Vec<(K, u64)> {
+    let mut counts: Vec<(K, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(top);
+    counts
+}
+
+fn run_fingerprints(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let fingerprints = fingerprint_stats(logs);
+    if fingerprints.is_empty() {
+        return Err("no entries with a TLS fingerprint; log $ssl_ja3_hash, $ssl_ja3 or $ssl_ja4 and pass the format with --log-format".into());
+    }
+    let mut table = table!([bFg -> "Fingerprint", "Requests", "Clients", "User Agents", "Top User Agent"]);
+    for (fingerprint, stats) in fingerprints.into_iter().take(top) {
+        let distinct = stats.agents.len();
+        let (agent, _) = ranked(stats.agents, 1).remove(0);
+        table.add_row(row![fingerprint, stats.requests, stats.clients.len(), distinct, agent]);
+    }
+    print_table(table, truncate);
+    Ok(())
+}
+
+fn run_crawlers(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let (interval, align) = cli::interval_options(matches)?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let robots = matches.value_of("robots").map(Robots::load).transpose()?;
+    let report = crawl_report(logs, interval, align, robots.as_ref());
+    let mut table = table!([bFg -> "Bot", "Requests", "Unique Paths", "4xx", "5xx", "Disallowed"]);
+    for (bot, stats) in &report.bots {
+        let disallowed = if robots.is_some() { stats.disallowed.to_string() } else { String::from("-") };
+        table.add_row(row![bot, stats.requests, stats.paths.len(), stats.client_errors, stats.server_errors, disallowed]);
+    }
+    print_table(table, truncate);
+    if !report.bots.is_empty() {
+        let mut titles = vec![Cell::new("Period").style_spec("bFg")];
+        titles.extend(report.bots.keys().map(|bot| Cell::new(bot).style_spec("bFg")));
+        let mut table = Table::new();
+        table.set_titles(Row::new(titles));
+        for (start, counts) in &report.daily {
+            let mut cells = vec![Cell::new(&interval.label(*start, align))];
+            cells.extend(report.bots.keys().map(|bot| Cell::new(&counts.get(bot).copied().unwrap_or(0).to_string())));
+            table.add_row(Row::new(cells));
+        }
+        print_table(table, truncate);
+        let mut table = table!([bFg -> "Crawled Path", "Requests"]);
+        for (path, count) in ranked(report.paths, top) {
+            table.add_row(row![path, count]);
+        }
+        print_table(table, truncate);
+    }
+    if !report.errors.is_empty() {
+        let mut table = table!([bFg -> "Bot", "Status", "Path", "Requests"]);
+        for ((bot, status, path), count) in ranked(report.errors, top) {
+            table.add_row(row![bot, status, path, count]);
+        }
+        print_table(table, truncate);
+    }
+    if !report.keywords.is_empty() {
+        let mut table = table!([bFg -> "Search Engine", "Keyword", "Visits"]);
+        for ((engine, keyword), count) in ranked(report.keywords, top) {
+            table.add_row(row![engine, keyword, count]);
+        }
+        print_table(table, truncate);
+    }
+    Ok(())
+}
+
+fn run_audit_endpoints(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let allowlist = Allowlist::load(matches.value_of("allowlist").unwrap())?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let unknown = unknown_endpoints(logs, &allowlist);
+    let mut table = table!([bFg -> "Method", "Path", "Requests", "Clients", "Statuses"]);
+    for endpoint in unknown.iter().take(top) {
+        let statuses: Vec<String> = endpoint.statuses.iter().map(|(status, count)| format!("{}x{}", status, count)).collect();
+        table.add_row(row![endpoint.method, endpoint.path, endpoint.requests, endpoint.clients.len(), statuses.join(" ")]);
+    }
+    print_table(table, truncate);
+    let rejected: u64 = unknown.iter().map(|endpoint| endpoint.requests).sum();
+    println!("{} of {} requests matched none of the {} known endpoints", rejected, logs.len(), allowlist.len());
+    Ok(())
+}
+
+fn run_params(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let usage = query_params(logs, matches.is_present("exact_paths"));
+    let mut table = table!([bFg -> "Path", "Parameter", "Requests", "Values", "Note"]);
+    for param in usage.iter().filter(|param| !matches.is_present("flagged") || !param.note().is_empty()).take(top) {
+        table.add_row(Row::new(vec![
+            Cell::new(&param.path),
+            Cell::new(&param.name),
+            Cell::new(&param.requests.to_string()),
+            Cell::new(&param.values.to_string()),
+            Cell::new(param.note()).style_spec("Fy"),
+        ]));
+    }
+    print_table(table, truncate);
+    let names: HashSet<&str> = usage.iter().map(|param| param.name.as_str()).collect();
+    println!("{} distinct parameters on {} paths", names.len(), usage.iter().map(|param| param.path.as_str()).collect::<HashSet<_>>().len());
+    Ok(())
+}
+
+fn run_robots_check(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let robots = Robots::load(matches.value_of("robots").unwrap())?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let mut agents: HashMap<String, (u64, HashMap<String, u64>)> = HashMap::new();
+    let mut checked = 0;
+    for log in logs {
+        let user_agent = log.http_user_agent.trim_matches('"');
+        if !is_bot(user_agent) {
+            continue;
+        }
+        checked += 1;
+        let path = log.path().unwrap_or("-");
+        if !robots.is_allowed(user_agent, path) {
+            let entry = agents.entry(user_agent.to_string()).or_default();
+            entry.0 += 1;
+            *entry.1.entry(path.to_string()).or_default() += 1;
+        }
+    }
+    let mut agents: Vec<_> = agents.into_iter().collect();
+    agents.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+    let mut table = table!([bFg -> "User Agent", "Violations", "Paths", "Top Paths"]);
+    for (user_agent, (violations, paths)) in agents.into_iter().take(top) {
+        let count = paths.len();
+        let shown: Vec<String> = ranked(paths, 5).into_iter().map(|(path, hits)| format!("{} ({})", path, hits)).collect();
+        table.add_row(row![user_agent, violations, count, shown.join("\n")]);
+    }
+    print_table(table, truncate);
+    println!("{} bot requests checked", checked);
+    Ok(())
+}
+
+fn run_top(logs: &[NginxLog], matches: &clap::ArgMatches, truncate: Option<usize>, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let field = GroupField::parse(matches.value_of("by").unwrap())?;
+    let mut counter = SpillingCounter::new(None);
+    for log in logs {
+        counter.add(field.key(log), log.body_bytes_sent)?;
+    }
+    print_top(counter, matches, truncate, bars)
+}
+
+// With --max-memory the entries go from the reader through enrichment and filters straight into the
+// counter, so the group table, which spills to disk, is all that grows with the input
+fn run_top_streaming(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, truncate: Option<usize>, bars: Option<usize>) -> Result<bool, Box<dyn Error>> {
+    if filters.context_window.is_some() {
+        return Err("--context-window needs every entry in memory; leave out --max-memory".into());
+    }
+    let field = GroupField::parse(matches.value_of("by").unwrap())?;
+    let max_memory = parse_size(matches.value_of("max_memory").unwrap())?;
+    let mut counter = SpillingCounter::new(Some(max_memory));
+    let mut matched = 0;
+    stream_sources(sources, options, |log| {
+        let mut logs = vec![log];
+        enrichment.apply(&mut logs)?;
+        for log in filters.apply(&logs) {
+            counter.add(field.key(&log), log.body_bytes_sent)?;
+            matched += 1;
+        }
+        Ok(())
+    })?;
+    print_top(counter, matches, truncate, bars)?;
+    Ok(matched > 0)
+}
+
+fn print_top(counter: SpillingCounter, matches: &clap::ArgMatches, truncate: Option<usize>, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    if counter.spilled_runs() > 0 {
+        eprintln!("Aggregation exceeded --max-memory, wrote {} spill files", counter.spilled_runs());
+    }
+    print_table(top_table(&counter.top(top)?, bars), truncate);
+    Ok(())
+}
+
+fn top_table(entries: &[(String, Totals)], bars: Option<usize>) -> Table {
+    let max = entries.iter().map(|(_, totals)| totals.requests).max().unwrap_or(0);
+    let mut table = bar_table(&["Value", "Requests", "Body Bytes Sent"], bars);
+    for (key, totals) in entries {
+        table.add_row(bar_row(row![key, totals.requests, totals.bytes], totals.requests, max, bars));
+    }
+    table
+}
+
+fn bar_table(titles: &[&str], bars: Option<usize>) -> Table {
+    let mut cells: Vec<Cell> = titles.iter().map(|title| Cell::new(title).style_spec("bFg")).collect();
+    if bars.is_some() {
+        cells.push(Cell::new(""));
+    }
+    let mut table = Table::new();
+    table.set_titles(Row::new(cells));
+    table
+}
+
+fn bar_row(mut row: Row, value: u64, max: u64, bars: Option<usize>) -> Row {
+    if let Some(width) = bars {
+        row.add_cell(Cell::new(&bar(value as f64, max as f64, width)).style_spec("Fc"));
+    }
+    row
+}
+
+// Every pipeline runs over the same parsed entries, so parsing is paid once however many there are
+fn run_pipeline(logs: Vec<NginxLog>, matches: &clap::ArgMatches, config: &Config, display: &TimeDisplay, truncate: Option<usize>, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let names = pipeline_names(matches, config)?;
+    let heading = |index: usize, name: &str| {
+        if names.len() > 1 {
+            println!("{}== {} ==", if index > 0 { "\n" } else { "" }, name);
+        }
+    };
+    let (last, rest) = names.split_last().ok_or("no pipeline given")?;
+    for (index, name) in rest.iter().enumerate() {
+        heading(index, name);
+        run_named_pipeline(logs.clone(), name, config, display, truncate, bars)?;
+    }
+    heading(rest.len(), last);
+    run_named_pipeline(logs, last, config, display, truncate, bars)
+}
+
+fn pipeline_names(matches: &clap::ArgMatches, config: &Config) -> Result<Vec<String>, Box<dyn Error>> {
+    if matches.is_present("all") {
+        if config.pipelines.is_empty() {
+            return Err("no pipelines defined in the config file".into());
+        }
+        return Ok(config.pipelines.keys().cloned().collect());
+    }
+    let names: Vec<String> = matches.values_of("name").into_iter().flatten().map(String::from).collect();
+    for name in &names {
+        config.pipeline(name)?;
+    }
+    Ok(names)
+}
+
+fn run_named_pipeline(mut logs: Vec<NginxLog>, name: &str, config: &Config, display: &TimeDisplay, truncate: Option<usize>, bars: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let pipeline = config.pipeline(name)?;
+    let started = Instant::now();
+    let filters = pipeline.filters.iter().map(|step| step.compile()).collect::<Result<Vec<_>, _>>()?;
+    logs.retain(|log| filters.iter().all(|filter| filter.keep(log)));
+    let mut known = KnownIps::default();
+    let mut exclude_known = false;
+    let mut extract_rules = Vec::new();
+    let mut plugins = Vec::new();
+    for enricher in &pipeline.enrich {
+        match enricher {
+            Enricher::Extract(spec) => extract_rules.push(ExtractRule::parse(spec)?),
+            Enricher::KnownIps(path) => known.load_file(path)?,
+            Enricher::KnownCidrs(spec) => known.add_spec(spec)?,
+            Enricher::ExcludeKnown(exclude) => exclude_known = *exclude,
+            Enricher::Plugin(path) => plugins.push(Plugin::load(path)?),
+        }
+    }
+    if !known.is_empty() {
+        known.apply(&mut logs, exclude_known);
+    }
+    for log in &mut logs {
+        for rule in &extract_rules {
+            rule.apply(log);
+        }
+        for plugin in &plugins {
+            plugin.enrich(log)?;
+        }
+    }
+    info!("stage=pipeline name={} entries={} elapsed_ms={}", name, logs.len(), started.elapsed().as_millis());
+    let top = match &pipeline.aggregate {
+        Some(aggregate) => {
+            let field = GroupField::parse(&aggregate.by)?;
+            let mut counter = SpillingCounter::new(aggregate.max_memory.as_deref().map(parse_size).transpose()?);
+            for log in &logs {
+                counter.add(field.key(log), log.body_bytes_sent)?;
+            }
+            Some(counter.top(aggregate.top)?)
+        }
+        None => None,
+    };
+    let default_sink = [Sink { format: SinkFormat::Table, path: None }];
+    let sinks = if pipeline.sinks.is_empty() { &default_sink[..] } else { &pipeline.sinks[..] };
+    for sink in sinks {
+        let mut out: Box<dyn Write> = match &sink.path {
+            Some(path) => Box::new(BufWriter::new(File::create(path).map_err(|e| in_path(path, e))?)),
+            None => Box::new(io::stdout()),
+        };
+        match (sink.format, &top, &sink.path) {
+            (SinkFormat::Table, Some(top), None) => print_table(top_table(top, bars), truncate),
+            (SinkFormat::Table, None, None) => print_table(logs_table(&logs, display), truncate),
+            (SinkFormat::Table, Some(top), Some(_)) => top_table(top, None).print(&mut out).map(|_| ())?,
+            (SinkFormat::Table, None, Some(_)) => logs_table(&logs, display).print(&mut out).map(|_| ())?,
+            (SinkFormat::Json, Some(top), _) => {
+                let values: Vec<_> = top.iter().map(|(value, totals)| serde_json::json!({ "value": value, "requests": totals.requests, "body_bytes_sent": totals.bytes })).collect();
+                serde_json::to_writer_pretty(&mut out, &values)?;
+                writeln!(out)?;
+            }
+            (SinkFormat::Json, None, _) => {
+                serde_json::to_writer_pretty(&mut out, &logs_json(&logs, display)?)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()?;
+        debug!("stage=sink pipeline={} path={}", name, sink.path.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
+
+// Sleeps until the next job is due, then reads only the files its window can reach
+fn run_daemon(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, config: &Config) -> Result<(), Box<dyn Error>> {
+    let health = Arc::new(Health::start());
+    if let Some(address) = matches.value_of("health") {
+        health::spawn(&server::listen_address(address), Arc::clone(&health))?;
+    }
+    let jobs = config.schedules.iter().map(|(name, schedule)| Job::from_config(name, schedule)).collect::<Result<Vec<_>, _>>()?;
+    if jobs.is_empty() {
+        return Err("no schedules defined in the config file; add a [schedules.NAME] section".into());
+    }
+    let mut next = Vec::new();
+    for job in &jobs {
+        let at = job.cron.next_after(Local::now()).ok_or_else(|| format!("schedules.{}: the cron expression never matches", job.name))?;
+        eprintln!("Scheduled {} next at {}", job.name, at.format("%Y-%m-%d %H:%M %Z"));
+        next.push(at);
+    }
+    loop {
+        let at = *next.iter().min().unwrap();
+        thread::sleep((at - Local::now()).to_std().unwrap_or_default());
+        let due: Vec<usize> = (0..jobs.len()).filter(|&index| next[index] <= at).collect();
+        let window = due.iter().map(|&index| jobs[index].window).max().unwrap();
+        let started = Instant::now();
+        let result = read_sources(sources, &ReadOptions { since: options.since.max(Some(at - window)), ..*options }).and_then(|mut logs| {
+            health.record(logs.len(), started.elapsed(), logs.iter().map(|log| log.request_time).max());
+            enrichment.apply(&mut logs)?;
+            Ok(filters.apply(&logs))
+        });
+        for &index in &due {
+            let job = &jobs[index];
+            match result.as_ref().map_err(|e| e.to_string()).and_then(|logs| job.run(logs, at).map_err(|e| e.to_string())) {
+                Ok(()) => info!("stage=daemon job={} elapsed_ms={}", job.name, started.elapsed().as_millis()),
+                Err(e) => eprintln!("{}: {}", job.name, e),
+            }
+            next[index] = job.cron.next_after(at).ok_or_else(|| format!("schedules.{}: the cron expression no longer matches", job.name))?;
+        }
+    }
+}
+
+fn run_compare(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, json: bool) -> Result<(), Box<dyn Error>> {
+    let by_name = matches.value_of("by").unwrap();
+    let by = GroupField::parse(by_name)?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let baseline: Vec<Source> = match matches.value_of("baseline") {
+        Some(path) => vec![Source { label: None, path: local_path(path) }],
+        None => sources.to_vec(),
+    };
+    let baseline_filters = Filters {
+        start: matches.value_of("baseline_start").map(parse_date_arg).transpose()?.or(filters.start),
+        end: matches.value_of("baseline_end").map(parse_date_arg).transpose()?.or(filters.end),
+        ..filters.clone()
+    };
+    let baseline_options = ReadOptions { since: baseline_filters.start, ..*options };
+    let read = |sources: &[Source], options: &ReadOptions, filters: &Filters| -> Result<Vec<NginxLog>, Box<dyn Error>> {
+        let mut logs = read_sources(sources, options)?;
+        enrichment.apply(&mut logs)?;
+        Ok(filters.apply(&logs))
+    };
+    let before = read(&baseline, &baseline_options, &baseline_filters)?;
+    let after = read(sources, options, filters)?;
+    let metrics = compare(&before, &after, (by_name, &by), top);
+    if json {
+        let rows: Vec<serde_json::Value> = metrics
+            .iter()
+            .map(|metric| serde_json::json!({ "metric": metric.name, "before": metric.before, "after": metric.after, "delta": metric.delta(), "change_percent": metric.change() }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    let mut table = table!([bFg -> "Metric", "Before", "After", "Delta", "Change"]);
+    for metric in &metrics {
+        let delta = metric.delta();
+        let style = if delta > 0.0 { "Fg" } else if delta < 0.0 { "Fr" } else { "" };
+        let sign = if delta > 0.0 { "+" } else { "" };
+        let change = metric.change().map(|change| format!("{}{:.1}%", sign, change)).unwrap_or_else(|| String::from("new"));
+        table.add_row(Row::new(vec![
+            Cell::new(&metric.name),
+            Cell::new(&metric.render(metric.before)),
+            Cell::new(&metric.render(metric.after)),
+            Cell::new(&format!("{}{}", sign, metric.render(delta))).style_spec(style),
+            Cell::new(if delta == 0.0 { "0.0%" } else { &change }).style_spec(style),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_correlate(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, json: bool) -> Result<(), Box<dyn Error>> {
+    let by = MatchBy::parse(matches.value_of("by").unwrap())?;
+    let window = parse_duration(matches.value_of("window").unwrap())?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let edge_format = FormatSpec::parse(matches.value_of("edge_format").unwrap()).map_err(|e| format!("--edge-format: {}", e))?;
+    let edge_options = ReadOptions { parser: edge_format.parser(), formats: &[], ..*options };
+    let read = |sources: &[Source], options: &ReadOptions| -> Result<Vec<NginxLog>, Box<dyn Error>> {
+        let mut logs = read_sources(sources, options)?;
+        enrichment.apply(&mut logs)?;
+        Ok(filters.apply(&logs))
+    };
+    let edge = read(&[Source { label: None, path: local_path(matches.value_of("edge").unwrap()) }], &edge_options)?;
+    let origin = read(sources, options)?;
+    let correlation = correlate(&edge, &origin, by, window, top);
+    if json {
+        let missing: Vec<serde_json::Value> = correlation.missing.iter().map(|(path, count)| serde_json::json!({ "path": path, "requests": count })).collect();
+        let value = serde_json::json!({
+            "matched_by": correlation.by.name(),
+            "edge_requests": correlation.edge_requests,
+            "edge_cache_hits": correlation.cache_hits,
+            "matched": correlation.matched,
+            "origin_requests": correlation.origin_requests,
+            "origin_only": correlation.origin_only,
+            "offload_percent": correlation.offload(),
+            "bytes_offload_percent": correlation.bytes_offload(),
+            "missing_at_origin": correlation.missing_total,
+            "missing_paths": missing,
+        });
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+    let mut table = table!([bFg -> "Metric", "Value"]);
+    table.add_row(row!["Matched by", correlation.by.name()]);
+    table.add_row(row!["Edge requests", correlation.edge_requests]);
+    table.add_row(row!["Edge cache hits", correlation.cache_hits]);
+    table.add_row(row!["Matched at origin", correlation.matched]);
+    table.add_row(row!["Origin requests", correlation.origin_requests]);
+    table.add_row(row!["Origin only (not seen at edge)", correlation.origin_only]);
+    table.add_row(row!["Offload (requests)", format!("{:.2}%", correlation.offload())]);
+    table.add_row(row!["Offload (bytes)", format!("{:.2}%", correlation.bytes_offload())]);
+    table.add_row(row![Fr -> "Missing at origin", correlation.missing_total]);
+    table.printstd();
+    if !correlation.missing.is_empty() {
+        println!("Edge requests missing at the origin:");
+        let mut table = table!([bFg -> "Path", "Requests"]);
+        for (path, count) in &correlation.missing {
+            table.add_row(row![path, count]);
+        }
+        table.printstd();
+    }
+    Ok(())
+}
+
+fn run_firstseen(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, display: &TimeDisplay, truncate: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let by_name = matches.value_of("by").unwrap();
+    let by = GroupField::parse(by_name)?;
+    let top: usize = matches.value_of("top").unwrap().parse()?;
+    let baseline_path = matches.value_of("baseline");
+    if filters.start.is_none() && baseline_path.is_none() {
+        return Err("firstseen needs --start to separate the history from the query window, or a --baseline".into());
+    }
+    let history_start = matches.value_of("history_start").map(parse_date_arg).transpose()?;
+    let mut logs = read_sources(sources, &ReadOptions { since: history_start, ..*options })?;
+    enrichment.apply(&mut logs)?;
+    let mut known = match baseline_path {
+        Some(path) => Baseline::load_or_default(path, by_name)?,
+        None => Baseline { by: by_name.to_string(), ..Default::default() },
+    };
+    if let Some(start) = filters.start {
+        let mut history = Filters { start: history_start, end: None, ..filters.clone() }.apply(&logs);
+        history.retain(|log| log.request_time < start);
+        info!("stage=firstseen history={} known={}", history.len(), known.first_seen.len());
+        known.record(&history, &by);
+    }
+    let window = filters.apply(&logs);
+    let new = newcomers(&window, &by, &known);
+    if let Some(path) = matches.value_of("save_baseline") {
+        known.record(&window, &by);
+        known.save(path)?;
+    }
+    let mut table = table!([bFg -> "Value", "First Seen", "Last Seen", "Requests", "Top Path"]);
+    for newcomer in new.iter().take(top) {
+        let top_path = newcomer.top_path().map(|(path, count)| format!("{} ({})", path, count)).unwrap_or_default();
+        table.add_row(row![newcomer.value, display.render(newcomer.first_seen), display.render(newcomer.last_seen), newcomer.requests, top_path]);
+    }
+    print_table(table, truncate);
+    let distinct: HashSet<String> = window.iter().map(|log| by.key(log)).collect();
+    println!("{} of {} {} values in the window were not seen before", new.len(), distinct.len(), by_name);
+    Ok(())
+}
+
+fn run_extract(sources: &[Source], matches: &clap::ArgMatches, options: &ReadOptions, enrichment: &Enrichment, filters: &Filters) -> Result<(), Box<dyn Error>> {
+    let to = Path::new(matches.value_of("to").unwrap());
+    if enrichment.masks() {
+        return Err("extract copies the original lines and cannot mask them; leave out --anonymize, --redact-params and --redact-segments".into());
+    }
+    let files = source_files(sources, options)?;
+    if let Some(archive) = files.iter().find(|file| pipeline::is_tarball(file) || file.extension().is_some_and(|ext| ext == "zip")) {
+        return Err(format!("extract reads plain and gzip files only, unpack {} first", archive.display()).into());
+    }
+    let mut out = BufWriter::new(File::create_new(to).map_err(|e| in_path(to.display(), e))?);
+    let mut excerpt = Excerpt::default();
+    for file in &files {
+        let options = options.for_file(file);
+        debug!("stage=open file={} parser={}", file.display(), options.parser.name());
+        let input = File::open(file).map_err(|e| in_path(file.display(), e))?;
+        let mut reader: Box<dyn BufRead> = if file.extension().is_some_and(|ext| ext == "gz") { Box::new(BufReader::new(MultiGzDecoder::new(input))) } else { Box::new(BufReader::new(input)) };
+        excerpt.copy_matching(&mut reader, &mut out, options.parser, |log| {
+            let mut logs = vec![log];
+            enrichment.apply(&mut logs)?;
+            Ok(!filters.apply(&logs).is_empty())
+        })?;
+    }
+    out.flush()?;
+    println!("Wrote {} of {} lines ({}) to {}", excerpt.written, excerpt.lines, human_size(excerpt.bytes), to.display());
+    println!("sha256 {}", excerpt.sha256());
+    Ok(())
+}
+
+fn explain(sources: &[Source], options: &ReadOptions, enrichment: &Enrichment, filters: &Filters, subcommand: Option<&str>) -> Result<(), Box<dyn Error>> {
+    eprintln!("Default parser: {}", options.parser.name());
+    for source in sources {
+        let path = Path::new(&source.path);
+        let label = source.label.as_ref().map(|label| format!(" (source={})", label)).unwrap_or_default();
+        let mut groups = vec![(format!("{}{}", source.path, label), if path.is_dir() { plan_dir(path, options)? } else { vec![(path.to_path_buf(), None)] })];
+        if path.is_dir() {
+            for (host, dir) in host_dirs(path)? {
+                groups.push((format!("{} ({}={})", dir.display(), HOST_FIELD, host), plan_dir(&dir, options)?));
+            }
+        }
+        for (title, planned) in groups {
+            eprintln!("Files in {}:", title);
+            for (file, skipped) in planned {
+                match skipped {
+                    Some(reason) => eprintln!("  skip {}: {}", file.display(), reason),
+                    None => match options.format_for(&file) {
+                        Some((pattern, parser)) => eprintln!("  read {} with {} (formats.\"{}\")", file.display(), parser.name(), pattern),
+                        None => eprintln!("  read {} with {}", file.display(), options.parser.name()),
+                    },
+                }
+            }
+        }
+    }
+    match options.max_errors {
+        Some(ErrorLimit::Count(max)) => eprintln!("Abort after more than {} unparsable lines", max),
+        Some(ErrorLimit::Percent(max)) => eprintln!("Abort after more than {}% unparsable lines", max),
+        None => {}
+    }
+    let steps = [("Enrichment", enrichment.describe()), ("Filters", filters.describe())];
+    for (title, steps) in steps {
+        if steps.is_empty() {
+            eprintln!("{}: none", title);
+            continue;
+        }
+        eprintln!("{}:", title);
+        for (index, step) in steps.iter().enumerate() {
+            eprintln!("  {}. {}", index + 1, step);
+        }
+    }
+    eprintln!("Then: {}", subcommand.unwrap_or("list matching entries"));
+    Ok(())
+}
+
+fn run_dry_run(sources: &[Source], options: &ReadOptions, matches: &clap::ArgMatches, config: &Config) -> Result<(), Box<dyn Error>> {
+    let files = source_files(sources, options)?;
+    let mut table = table!([bFg -> "File", "Size", "Uncompressed", "Est. Lines", "Parser"]);
+    let (mut size, mut lines) = (0, 0);
+    for file in &files {
+        let planned = plan_file(file).map_err(|e| in_path(file.display(), e))?;
+        size += planned.size;
+        lines += planned.estimated_lines;
+        table.add_row(row![
+            planned.path.display(),
+            human_size(planned.size),
+            planned.uncompressed.map(human_size).unwrap_or_else(|| String::from("-")),
+            format!("~{}", planned.estimated_lines),
+            options.for_file(file).parser.name(),
+        ]);
+    }
+    println!("Would read {} files ({}, ~{} lines):", files.len(), human_size(size), lines);
+    table.printstd();
+    match matches.subcommand() {
+        ("otlp", Some(sub)) => {
+            let exporter = otlp_exporter(sub)?;
+            println!("Would export to {} ({})", exporter.endpoint(), exporter.describe());
+            println!("Record attributes: {}", otlp::ATTRIBUTES.join(", "));
+        }
+        ("run-pipeline", Some(sub)) => {
+            for name in pipeline_names(sub, config)? {
+                let pipeline = config.pipeline(&name)?;
+                println!("Would run pipeline {} with {} filters and {} enrichers", name, pipeline.filters.len(), pipeline.enrich.len());
+                if let Some(aggregate) = &pipeline.aggregate {
+                    println!("Aggregate: top {} by {}", aggregate.top, aggregate.by);
+                }
+                for sink in &pipeline.sinks {
+                    let format = match sink.format {
+                        SinkFormat::Table => "table",
+                        SinkFormat::Json => "json",
+                    };
+                    println!("Sink: {} to {}", format, sink.path.as_deref().unwrap_or("stdout"));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn run_follow(path: &str, parser: LineParser, enrichment: &Enrichment, filters: &Filters, display: &TimeDisplay, json: bool, matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let poll = Duration::from_millis(matches.value_of("poll_ms").unwrap().parse()?);
+    let mut tail = Tail::open(path, matches.is_present("from_start")).map_err(|e| in_path(path, e))?;
+    info!("stage=follow file={} poll_ms={}", path, poll.as_millis());
+    let health = Arc::new(Health::start());
+    if let Some(address) = matches.value_of("health") {
+        health::spawn(&server::listen_address(address), Arc::clone(&health))?;
+    }
+    let mut polled = Instant::now();
+    loop {
+        let lines = tail.poll()?;
+        let mut logs = Vec::with_capacity(lines.len());
+        for line in &lines {
+            match parser.parse_line(line) {
+                Ok(log) => logs.push(log),
+                Err(error) => trace!("stage=parse file={} skipped reason={}", path, error),
+            }
+        }
+        health.record(lines.len(), polled.elapsed(), logs.iter().map(|log| log.request_time).max());
+        polled = Instant::now();
+        enrichment.apply(&mut logs)?;
+        for log in filters.apply(&logs) {
+            if json {
+                let mut value = serde_json::to_value(&log)?;
+                value["request_time"] = display.json(log.request_time);
+                println!("{}", value);
+            } else {
+                println!("{} {} {} \"{}\" {}", display.render(log.request_time), log.client(), log.status, log.request.trim_matches('"'), log.body_bytes_sent);
+            }
+        }
+        thread::sleep(poll);
+    }
+}
+
+fn run_prune(path: &str, matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(path);
+    if !dir.is_dir() {
+        return Err(format!("prune needs a log directory, {} is not one", path).into());
+    }
+    let retention = matches.value_of("older_than").unwrap();
+    let cutoff = Interval::parse(retention)?.before(Local::now());
+    let archive_dir = matches.value_of("archive").map(Path::new);
+    if let Some(archive_dir) = archive_dir {
+        if !archive_dir.is_dir() {
+            return Err(format!("--archive: {} is not a directory", archive_dir.display()).into());
+        }
+    }
+    let delete = matches.is_present("delete");
+    let dry_run = matches.is_present("dry_run") || (!delete && archive_dir.is_none());
+    let verb = match (archive_dir, delete, dry_run) {
+        (Some(_), _, true) => "would archive",
+        (Some(_), _, false) => "archived",
+        (None, true, true) => "would delete",
+        (None, true, false) => "deleted",
+        (None, false, _) => "expired",
+    };
+    let expired = prune::expired_files(dir, cutoff)?;
+    let mut table = table!([bFg -> "File", "Size", "Modified", "Action"]);
+    let (mut count, mut size) = (0, 0);
+    for file in &expired {
+        let action = match &file.kept {
+            Some(reason) => format!("kept: {}", reason),
+            None if dry_run => verb.to_string(),
+            None => {
+                let done = match archive_dir {
+                    Some(archive_dir) => prune::archive(&file.path, archive_dir).map(|_| ()),
+                    None => fs::remove_file(&file.path),
+                };
+                done.map_err(|e| in_path(file.path.display(), e))?;
+                info!("stage=prune file={} action={}", file.path.display(), verb);
+                verb.to_string()
+            }
+        };
+        if file.kept.is_none() {
+            count += 1;
+            size += file.size;
+        }
+        let style = if file.kept.is_some() { "Fy" } else { "" };
+        table.add_row(Row::new(vec![
+            Cell::new(&file.path.display().to_string()),
+            Cell::new(&human_size(file.size)),
+            Cell::new(&file.modified.format("%Y-%m-%d %H:%M").to_string()),
+            Cell::new(&action).style_spec(style),
+        ]));
+    }
+    if !expired.is_empty() {
+        table.printstd();
+    }
+    println!("{} {} rotated files ({}) older than {}", verb, count, human_size(size), retention);
+    Ok(())
+}
+
+fn run_generate(path: &str, matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let template = generate::template(matches.value_of("format").unwrap())?;
+    let end = Local::now();
+    let profile = generate::Profile {
+        lines: generate::parse_count(matches.value_of("lines").unwrap())?,
+        start: Interval::parse(matches.value_of("span").unwrap())?.before(end),
+        end,
+        paths: matches.value_of("paths").unwrap().parse::<usize>()?.max(1),
+        clients: matches.value_of("clients").unwrap().parse::<usize>()?.max(1),
+        error_rate: generate::parse_rate(matches.value_of("error_rate").unwrap())?,
+        client_error_rate: generate::parse_rate(matches.value_of("client_error_rate").unwrap())?,
+    };
+    let seed: u64 = matches.value_of("seed").unwrap().parse()?;
+    if path == "-" {
+        let stdout = io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        generate::generate(&mut out, &profile, template.as_deref(), seed)?;
+        out.flush()?;
+        return Ok(());
+    }
+    if Path::new(path).exists() && !matches.is_present("force") {
+        return Err(format!("{} already exists; pass --force to overwrite it", path).into());
+    }
+    let started = Instant::now();
+    let mut out = BufWriter::new(File::create(path)?);
+    generate::generate(&mut out, &profile, template.as_deref(), seed)?;
+    out.flush()?;
+    eprintln!("Wrote {} lines to {} in {:.1}s", profile.lines, path, started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+fn run_detect_format(path: &str, matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let max_lines = matches.value_of("lines").unwrap().parse::<usize>()?;
+    let lines = bench::load_sample(path, Some(max_lines))?;
+    let detection = detect_format(&lines).ok_or("could not detect a log format; the sample has no recognizable timestamp")?;
+    println!("{} of {} sample lines match", detection.matched, detection.sampled);
+    match &detection.log_format {
+        Some(log_format) => {
+            println!();
+            println!("nginx:");
+            println!("    log_format detected '{}';", log_format);
+            println!();
+            println!("config ({}):", config::DEFAULT_CONFIG);
+            println!("    log_format = '{}'", log_format);
+            if detection.parser == "split" {
+                println!("    parser = \"split\"");
+            }
+            println!();
+            println!("command line:");
+            println!("    --log-format '{}'", log_format);
+        }
+        None => {
+            println!();
+            println!("config ({}):", config::DEFAULT_CONFIG);
+            println!("    parser = \"{}\"", detection.parser);
+        }
+    }
+    let extra: Vec<&String> = detection.fields.iter().filter(|field| is_extra_field(field)).collect();
+    println!();
+    println!("fields: {}", detection.fields.join(", "));
+    if !extra.is_empty() {
+        println!("extra fields: {}", extra.iter().map(|field| field.as_str()).collect::<Vec<_>>().join(", "));
+    }
+    Ok(())
+}
+
+fn run_bench(path: &str, format: Option<&LogFormat>, matches: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let max_lines = matches.value_of("lines").map(|s| s.parse::<usize>()).transpose()?;
+    let lines = bench::load_sample(path, max_lines)?;
+    let combined = LogFormat::compile(COMBINED_FORMAT)?;
+    let format = format.unwrap_or(&combined);
+    let mut table = table!([bFg -> "Backend", "Lines", "Parsed", "Lines/s", "MB/s"]);
+    for parser in [LineParser::Regex, LineParser::Format(format), LineParser::Split, LineParser::Json] {
+        let result = bench::bench_parser(parser, &lines);
+        table.add_row(row![
+            result.backend,
+            result.lines,
+            format!("{:.1}%", result.parsed as f64 * 100.0 / result.lines.max(1) as f64),
+            format!("{:.0}", result.lines_per_sec()),
+            format!("{:.1}", result.mb_per_sec()),
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn find_error_log_lines<P: AsRef<Path>>(path: P, needle: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let mut lines = Vec::new();
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "log") {
+                lines.append(&mut find_error_log_lines(path, needle)?);
+            }
+        }
+    } else {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut buffer = Vec::new();
+        while let Some(line) = pipeline::read_lossy_line(&mut reader, &mut buffer)? {
+            if line.contains(needle) {
+                lines.push(line);
+            }
+        }
+    }
+    Ok(lines)
+}
+
+fn local_path(path: &str) -> String {
+    if path.contains('\\') && !Path::new(path).exists() {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+fn parse_date_arg(value: &str) -> Result<DateTime<Local>, Box<dyn Error>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date: {} (expected YYYY-MM-DD or RFC 3339)", value))?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .ok_or_else(|| format!("invalid local date: {}", value).into())
+}
+
+// The time before which files can be skipped for a SQL query, a day of slack below its time bound
+// because the literal may be UTC while file times are local. The bound is only a hint: a timestamp
+// without an offset counts from its date, and anything else DataFusion accepts prunes nothing
+fn sql_since(query: &str) -> Option<DateTime<Local>> {
+    let bound = sql::time_lower_bound(query)?;
+    let since = parse_date_arg(&bound).ok().or_else(|| bound.get(..10).and_then(|date| parse_date_arg(date).ok()))?;
+    Some(since - chrono::Duration::days(1))
+}
+
+fn setup_logging(level: LevelFilter) -> Result<(), fern::InitError> {
+    let colors = ColoredLevelConfig::new()
+        .error(Color::Red)
+        .warn(Color::Yellow)
+        .info(Color::Green)
+        .debug(Color::Blue)
+        .trace(Color::Magenta);
+    fern::Dispatch::new()
+        .format(move |out, message, record| {
+            out.finish(format_args!(
+                "{} {:5} {}: {}",
+                Local::now().format("%H:%M:%S%.3f"),
+                colors.color(record.level()),
+                record.target(),
+                message
+            ))
+        })
+        .level(level)
+        .chain(std::io::stderr())
+        .apply()?;
+    Ok(())
+}
+
+const EXIT_NO_MATCHES: i32 = 1;
+const EXIT_PARSE_ERRORS: i32 = 2;
+const EXIT_IO_ERROR: i32 = 3;
+const EXIT_FAILURE: i32 = 4;
+
+fn main() {
+    // Under `cargo afl fuzz` with the fuzz feature, test cases replace the logs
+    #[cfg(feature = "fuzz")]
+    if std::env::args().nth(1).as_deref() == Some("--fuzz-parsers") {
+        afl::fuzz!(|data: &[u8]| fuzz::parse_all(data));
+        return;
+    }
+    let code = match run() {
+        Ok(true) => 0,
+        Ok(false) => EXIT_NO_MATCHES,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            exit_code(error.as_ref())
+        }
+    };
+    std::process::exit(code);
+}
+
+// Errors wrapped with the file they came from are classified by what they wrap
+fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if error.is::<TooManyErrors>() {
+            return EXIT_PARSE_ERRORS;
+        }
+        if error.is::<io::Error>() {
+            return EXIT_IO_ERROR;
+        }
+        current = error.source();
+    }
+    EXIT_FAILURE
+}
+
+// Ok(false) when no entry matched the filters
+fn run() -> Result<bool, Box<dyn Error>> {
+    let matches = match cli::app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(error) if error.use_stderr() => {
+            eprintln!("{}", error.message);
+            std::process::exit(EXIT_FAILURE);
+        }
+        Err(error) => error.exit(),
+    };
+    let level = match matches.value_of("log_level") {
+        Some(level) => level.parse::<LevelFilter>()?,
+        None => match matches.occurrences_of("verbose") {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        },
+    };
+    setup_logging(level)?;
+
+    let config = Config::load_or_default(matches.value_of("config"))?;
+    let sources = Source::from_matches(&matches)?;
+    let path = &sources[0].path;
+    if let ("detect-format", Some(sub)) = matches.subcommand() {
+        return run_detect_format(path, sub).map(|_| true);
+    }
+    if let ("prune", Some(sub)) = matches.subcommand() {
+        return run_prune(path, sub).map(|_| true);
+    }
+    if let ("generate", Some(sub)) = matches.subcommand() {
+        return run_generate(path, sub).map(|_| true);
+    }
+    let format = matches.value_of("log_format").or(config.log_format.as_deref()).map(LogFormat::compile).transpose()?;
+    let csv_format = match matches.values_of("csv_map") {
+        Some(specs) => {
+            let header = match matches.value_of("csv_header") {
+                Some(header) => Some(header.to_string()),
+                None => first_line(Path::new(path), |line| !line.trim().is_empty())?,
+            };
+            let delimiter = match matches.value_of("csv_delimiter").unwrap() {
+                "tab" | "\\t" => '\t',
+                delimiter if delimiter.chars().count() == 1 => delimiter.chars().next().unwrap(),
+                delimiter => return Err(format!("invalid --csv-delimiter: {} (expected one character or tab)", delimiter).into()),
+            };
+            Some(CsvFormat::compile(&specs.collect::<Vec<_>>(), delimiter, header.as_deref())?)
+        }
+        None => None,
+    };
+    let w3c_format = match matches.value_of("parser").or(config.parser.as_deref()) {
+        Some("w3c") => {
+            let directive = match matches.value_of("w3c_fields") {
+                Some(fields) => fields.to_string(),
+                None => first_line(Path::new(path), |line| line.starts_with("#Fields:"))?.ok_or("--parser w3c: no #Fields: directive in the input, pass --w3c-fields")?,
+            };
+            Some(W3cFormat::compile(&directive)?)
+        }
+        Some("cloudfront") => {
+            let directive = match matches.value_of("w3c_fields") {
+                Some(fields) => Some(fields.to_string()),
+                None => first_line(Path::new(path), |line| line.starts_with("#Fields:"))?,
+            };
+            Some(W3cFormat::cloudfront(directive.as_deref())?)
+        }
+        Some("iis") => {
+            let directive = match matches.value_of("w3c_fields") {
+                Some(fields) => Some(fields.to_string()),
+                None => first_line(Path::new(path), |line| line.starts_with("#Fields:"))?,
+            };
+            Some(W3cFormat::iis(directive.as_deref())?)
+        }
+        _ => None,
+    };
+    let parser = match (matches.value_of("parser").or(config.parser.as_deref()), format.as_ref()) {
+        (Some("csv"), _) => LineParser::Csv(csv_format.as_ref().ok_or("--parser csv needs --csv-map")?),
+        (Some("w3c" | "cloudfront" | "iis"), _) => LineParser::W3c(w3c_format.as_ref().unwrap()),
+        (Some("alb"), _) => LineParser::Alb,
+        (Some("split"), _) => LineParser::Split,
+        (Some("json"), _) => LineParser::Json,
+        (_, Some(format)) => LineParser::Format(format),
+        _ => LineParser::Regex,
+    };
+    if let ("bench", Some(sub)) = matches.subcommand() {
+        return run_bench(path, format.as_ref(), sub).map(|_| true);
+    }
+    let start_date = matches.value_of("start").map(parse_date_arg).transpose()?;
+    let entries_since = match matches.subcommand() {
+        ("sql", Some(sub)) => sql_since(sub.value_of("query").unwrap()),
+        _ => None,
+    };
+    let format_specs = config
+        .formats
+        .iter()
+        .map(|(pattern, spec)| FormatSpec::parse(spec).map(|spec| (pattern.as_str(), spec)).map_err(|e| format!("formats.\"{}\": {}", pattern, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let formats: Vec<(&str, LineParser)> = format_specs.iter().map(|(pattern, spec)| (*pattern, spec.parser())).collect();
+    let members: Vec<&str> = matches.values_of("members").into_iter().flatten().collect();
+    let grep = matches.subcommand_name() == Some("grep");
+    let read_options = ReadOptions {
+        parser,
+        formats: &formats,
+        max_files: matches.value_of("max_files").map(|s| s.parse::<usize>()).transpose()?,
+        members: &members,
+        newest_first: matches.is_present("newest_first"),
+        chronological: matches.is_present("chronological"),
+        since: start_date,
+        entries_since,
+        max_errors: matches.value_of("max_errors").map(ErrorLimit::parse).transpose()?,
+        track_source: matches.is_present("show_source") || grep,
+        keep_raw: grep,
+    };
+    let enrichment = Enrichment::from_matches(&matches)?;
+    if grep && enrichment.masks() {
+        return Err("grep prints the original lines and cannot mask them; leave out --anonymize, --redact-params and --redact-segments, or use --output csv".into());
+    }
+    let filters = Filters::from_matches(&matches, start_date)?;
+    let output = matches.value_of("output").unwrap();
+    let json = output == "json";
+    let display = TimeDisplay {
+        format: matches.value_of("time_format").map(TimeFormat::parse).transpose()?.unwrap_or(TimeFormat::Default),
+        zone: matches.value_of("display_timezone").map(DisplayZone::parse).transpose()?.unwrap_or(DisplayZone::Local),
+    };
+    let truncate = if matches.is_present("full") { None } else { Some(matches.value_of("truncate").unwrap().parse::<usize>()?.max(1)) };
+    let bars = if matches.is_present("bars") { Some(matches.value_of("bar_width").unwrap().parse::<usize>()?) } else { None };
+    if matches.is_present("explain") {
+        explain(&sources, &read_options, &enrichment, &filters, matches.subcommand_name())?;
+    }
+    if let ("compare", Some(sub)) = matches.subcommand() {
+        return run_compare(&sources, sub, &read_options, &enrichment, &filters, json).map(|_| true);
+    }
+    if let ("correlate", Some(sub)) = matches.subcommand() {
+        return run_correlate(&sources, sub, &read_options, &enrichment, &filters, json).map(|_| true);
+    }
+    if let ("firstseen", Some(sub)) = matches.subcommand() {
+        return run_firstseen(&sources, sub, &read_options, &enrichment, &filters, &display, truncate).map(|_| true);
+    }
+    if let ("extract", Some(sub)) = matches.subcommand() {
+        return run_extract(&sources, sub, &read_options, &enrichment, &filters).map(|_| true);
+    }
+    if matches.subcommand().1.is_some_and(|sub| sub.is_present("dry_run")) {
+        return run_dry_run(&sources, &read_options, &matches, &config).map(|_| true);
+    }
+    if let ("daemon", Some(sub)) = matches.subcommand() {
+        return run_daemon(&sources, sub, &read_options, &enrichment, &filters, &config).map(|_| true);
+    }
+    if let ("follow", Some(sub)) = matches.subcommand() {
+        return run_follow(path, parser, &enrichment, &filters, &display, json, sub).map(|_| true);
+    }
+    if let ("top", Some(sub)) = matches.subcommand() {
+        if sub.is_present("max_memory") {
+            return run_top_streaming(&sources, sub, &read_options, &enrichment, &filters, truncate, bars);
+        }
+    }
+    let reading = Instant::now();
+    let mut logs = read_sources(&sources, &read_options)?;
+    let read_elapsed = reading.elapsed();
+    enrichment.apply(&mut logs)?;
+    let started = Instant::now();
+    let filtered_logs = filters.apply(&logs);
+    info!("stage=filter matched={} total={} elapsed_ms={}", filtered_logs.len(), logs.len(), started.elapsed().as_millis());
+    let started = Instant::now();
+    let found = !filtered_logs.is_empty();
+
+    match matches.subcommand() {
+        ("trend", Some(sub)) => run_trend(&filtered_logs, sub, &display, output)?,
+        ("report", Some(sub)) => run_report(&filtered_logs, sub)?,
+        ("otlp", Some(sub)) => run_otlp(&filtered_logs, sub)?,
+        ("browsers", Some(sub)) => run_browsers(&filtered_logs, sub)?,
+        ("cve-scan", Some(sub)) => run_cve_scan(&filtered_logs, sub)?,
+        ("abuse", Some(sub)) => run_abuse(&filtered_logs, sub)?,
+        ("notify", Some(sub)) => run_notify(&filtered_logs, sub)?,
+        ("rollup", Some(sub)) => run_rollup(&filtered_logs, sub, truncate)?,
+        ("lint", Some(sub)) => run_lint(&filtered_logs, sub, &display, truncate)?,
+        ("fingerprints", Some(sub)) => run_fingerprints(&filtered_logs, sub, truncate)?,
+        ("rate-limits", Some(sub)) => run_rate_limits(&filtered_logs, sub)?,
+        ("top-ips", Some(sub)) => run_top_ips(&filtered_logs, sub, bars)?,
+        ("hosts", Some(_)) => run_hosts(&filtered_logs, bars)?,
+        ("cardinality", Some(sub)) => run_cardinality(&filtered_logs, sub, json, truncate)?,
+        ("sql", Some(sub)) => run_sql(&filtered_logs, sub, output, truncate)?,
+        ("balance", Some(sub)) => run_balance(&filtered_logs, sub)?,
+        ("canary", Some(sub)) => run_canary(&filtered_logs, sub, json)?,
+        ("replay", Some(sub)) => run_replay(&filtered_logs, sub, truncate)?,
+        ("grep", Some(sub)) => run_grep(&logs, &filtered_logs, sub)?,
+        ("export", Some(sub)) => run_export(&filtered_logs, sub, &display)?,
+        ("top", Some(sub)) => run_top(&filtered_logs, sub, truncate, bars)?,
+        ("latency", Some(sub)) => run_latency(&filtered_logs, sub)?,
+        ("slow", Some(sub)) => run_slow(&filtered_logs, sub, &display, truncate)?,
+        ("chart", Some(sub)) => run_chart(&filtered_logs, sub)?,
+        ("uploads", Some(sub)) => run_uploads(&filtered_logs, sub, truncate, bars)?,
+        ("ranges", Some(sub)) => run_ranges(&filtered_logs, sub, truncate)?,
+        ("downloads", Some(sub)) => run_downloads(&filtered_logs, sub)?,
+        ("connections", Some(sub)) => run_connections(&filtered_logs, sub, truncate)?,
+        ("ttfb", Some(sub)) => run_ttfb(&filtered_logs, sub)?,
+        ("protocols", Some(sub)) => run_protocols(&filtered_logs, sub)?,
+        ("anomaly", Some(sub)) => run_anomaly(&filtered_logs, sub, &display, json)?,
+        ("peaks", Some(sub)) => run_peaks(&filtered_logs, sub, &display, truncate)?,
+        ("errors", Some(sub)) => run_errors(&filtered_logs, sub, &display, truncate)?,
+        ("funnel", Some(sub)) => run_funnel(&filtered_logs, sub)?,
+        ("entry-exit", Some(sub)) => run_entry_exit(&filtered_logs, sub, truncate)?,
+        ("graph", Some(sub)) => run_graph(&filtered_logs, sub)?,
+        ("hotlinks", Some(sub)) => run_hotlinks(&filtered_logs, sub, truncate)?,
+        ("campaigns", Some(sub)) => run_campaigns(&filtered_logs, sub, &display, truncate)?,
+        ("crawlers", Some(sub)) => run_crawlers(&filtered_logs, sub, truncate)?,
+        ("audit-endpoints", Some(sub)) => run_audit_endpoints(&filtered_logs, sub, truncate)?,
+        ("params", Some(sub)) => run_params(&filtered_logs, sub, truncate)?,
+        ("robots-check", Some(sub)) => run_robots_check(&filtered_logs, sub, truncate)?,
+        ("run-pipeline", Some(sub)) => run_pipeline(filtered_logs, sub, &config, &display, truncate, bars)?,
+        ("serve", Some(sub)) => {
+            let tokens = config.api_tokens.iter().map(|(name, token)| server::Access::from_config(name, token)).collect::<Result<Vec<_>, _>>()?;
+            let health = Health::start();
+            health.record(logs.len(), read_elapsed, logs.iter().map(|log| log.request_time).max());
+            server::serve(&server::listen_address(sub.value_of("api").unwrap()), &server::Api::new(filtered_logs, &display, tokens, health))?
+        }
+        _ => match (matches.value_of("request_id"), matches.value_of("error_log")) {
+            (Some(request_id), Some(error_log)) => {
+                let errors = find_error_log_lines(error_log, request_id)?;
+                if json {
+                    let report = serde_json::json!({ "request_id": request_id, "access": logs_json(&filtered_logs, &display)?, "errors": errors });
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else {
+                    print_logs(&filtered_logs, &display, truncate);
+                    for line in errors {
+                        println!("{}", line);
+                    }
+                }
+            }
+            _ if json => print_logs_json(&filtered_logs, &display)?,
+            _ if output == "csv" => print_logs_csv(&filtered_logs, &display),
+            _ if output == "arrow" => ipc::write_stream(io::stdout().lock(), &filtered_logs)?,
+            _ => print_logs(&filtered_logs, &display, truncate),
+        },
+    }
+    info!("stage={} elapsed_ms={}", matches.subcommand_name().unwrap_or("list"), started.elapsed().as_millis());
+    Ok(found)
+}
@@ -0,0 +1,138 @@
+use crate::NginxLog;
+
+pub const DEFAULT_INDEX_FILES: [&str; 3] = ["index.html", "index.htm", "index.php"];
+
+// Escapes that would change how the request line or path is split, or hide the payload, stay encoded
+const KEEP_ENCODED: [u8; 4] = [b'%', b'?', b'#', b' '];
+
+pub fn apply(log: &mut NginxLog) {
+    let request = log.request.trim_matches('"');
+    let mut parts: Vec<String> = request.split(' ').map(String::from).collect();
+    let target = match parts.get_mut(1) {
+        Some(target) => target,
+        None => return,
+    };
+    let normalized = match target.split_once('?') {
+        Some((path, query)) => format!("{}?{}", normalize_path(path), query),
+        None => normalize_path(target),
+    };
+    if normalized == *target {
+        return;
+    }
+    *target = normalized;
+    let raw = request.to_string();
+    log.request = if log.request.starts_with('"') { format!("\"{}\"", parts.join(" ")) } else { parts.join(" ") };
+    log.extra.insert(String::from("raw_request"), raw);
+}
+
+// Drops the query string so /page?utm_source=a and /page?utm_source=b count as one path
+pub fn strip_query(log: &mut NginxLog) {
+    let request = log.request.trim_matches('"');
+    let mut parts: Vec<&str> = request.split(' ').collect();
+    let path = match parts.get(1).and_then(|target| target.split_once('?')) {
+        Some((path, _)) => path,
+        None => return,
+    };
+    parts[1] = path;
+    let stripped = parts.join(" ");
+    let raw = request.to_string();
+    log.request = if log.request.starts_with('"') { format!("\"{}\"", stripped) } else { stripped };
+    log.extra.entry(String::from("raw_request")).or_insert(raw);
+}
+
+// Counts /docs, /docs/ and /docs/index.html as the same page, like web analytics tools do
+pub struct PageRules {
+    index_files: Vec<String>,
+}
+
+impl PageRules {
+    pub fn new<'a>(index_files: impl Iterator<Item = &'a str>) -> PageRules {
+        let mut index_files: Vec<String> = index_files.flat_map(|value| value.split(',')).map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect();
+        if index_files.is_empty() {
+            index_files = DEFAULT_INDEX_FILES.iter().map(|name| name.to_string()).collect();
+        }
+        PageRules { index_files }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("merge trailing slashes and index pages ({})", self.index_files.join(", "))
+    }
+
+    pub fn page<'a>(&self, path: &'a str) -> &'a str {
+        let mut page = path;
+        if let Some((dir, file)) = page.rsplit_once('/') {
+            if self.index_files.iter().any(|index| index.eq_ignore_ascii_case(file)) {
+                page = if dir.is_empty() { "/" } else { dir };
+            }
+        }
+        if page.len() > 1 {
+            page = page.trim_end_matches('/');
+        }
+        if page.is_empty() {
+            "/"
+        } else {
+            page
+        }
+    }
+
+    pub fn apply(&self, log: &mut NginxLog) {
+        let request = log.request.trim_matches('"');
+        let mut parts: Vec<String> = request.split(' ').map(String::from).collect();
+        let target = match parts.get_mut(1) {
+            Some(target) => target,
+            None => return,
+        };
+        let merged = match target.split_once('?') {
+            Some((path, query)) => format!("{}?{}", self.page(path), query),
+            None => self.page(target).to_string(),
+        };
+        if merged == *target {
+            return;
+        }
+        *target = merged;
+        let raw = request.to_string();
+        log.request = if log.request.starts_with('"') { format!("\"{}\"", parts.join(" ")) } else { parts.join(" ") };
+        log.extra.entry(String::from("raw_request")).or_insert(raw);
+    }
+}
+
+pub fn normalize_path(path: &str) -> String {
+    let decoded = decode(path, true).unwrap_or_else(|| decode(path, false).unwrap_or_else(|| path.to_string()));
+    let mut collapsed = String::with_capacity(decoded.len());
+    for c in decoded.chars() {
+        if c == '/' && collapsed.ends_with('/') {
+            continue;
+        }
+        collapsed.push(c);
+    }
+    collapsed
+}
+
+fn decode(path: &str, non_ascii: bool) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() { hex_byte(bytes[i + 1], bytes[i + 2]) } else { None };
+        match escaped {
+            Some(byte) if KEEP_ENCODED.contains(&byte) || byte.is_ascii_control() || (byte >= 0x80 && !non_ascii) => {
+                decoded.extend(format!("%{:02X}", byte).bytes());
+                i += 3;
+            }
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn hex_byte(high: u8, low: u8) -> Option<u8> {
+    let digit = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+    Some(digit(high)? << 4 | digit(low)?)
+}
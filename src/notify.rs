@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use chrono::Duration;
+use serde_json::{json, Value};
+
+use crate::interval::{trend_analysis, trend_matching, Alignment, Interval};
+use crate::summary::Section;
+use crate::NginxLog;
+
+const SPIKE_SIGMAS: f64 = 3.0;
+const MIN_ERROR_REQUESTS: u64 = 20;
+
+pub struct Digest {
+    pub title: String,
+    pub requests: usize,
+    pub client_error_rate: f64,
+    pub server_error_rate: f64,
+    pub top_pages: Vec<(String, u64)>,
+    pub anomalies: Vec<String>,
+}
+
+pub fn digest(logs: &[NginxLog], period: &str, top: usize) -> Digest {
+    let window = if period == "week" { Duration::days(7) } else { Duration::days(1) };
+    let logs: Vec<NginxLog> = match logs.iter().map(|log| log.request_time).max() {
+        Some(last) => logs.iter().filter(|log| log.request_time > last - window).cloned().collect(),
+        None => Vec::new(),
+    };
+    let title = match logs.iter().map(|log| log.request_time).max() {
+        Some(last) if period == "week" => format!("Traffic for the 7 days to {}", last.format("%Y-%m-%d %H:%M")),
+        Some(last) => format!("Traffic for the 24 hours to {}", last.format("%Y-%m-%d %H:%M")),
+        None => String::from("Traffic digest"),
+    };
+    let rate = |class: u16| match logs.len() {
+        0 => 0.0,
+        total => logs.iter().filter(|log| log.status / 100 == class).count() as f64 * 100.0 / total as f64,
+    };
+    let mut pages: HashMap<&str, u64> = HashMap::new();
+    for log in &logs {
+        *pages.entry(log.path().unwrap_or("-")).or_default() += 1;
+    }
+    let mut top_pages: Vec<(String, u64)> = pages.into_iter().map(|(page, count)| (page.to_string(), count)).collect();
+    top_pages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_pages.truncate(top);
+    Digest { title, requests: logs.len(), client_error_rate: rate(4), server_error_rate: rate(5), top_pages, anomalies: anomalies(&logs) }
+}
+
+fn anomalies(logs: &[NginxLog]) -> Vec<String> {
+    let hour = Interval::Seconds(3600);
+    let requests = trend_analysis(logs, hour, Alignment::Calendar);
+    let errors = trend_matching(logs, hour, Alignment::Calendar, |log| log.status >= 500);
+    let counts: Vec<f64> = requests.values().map(|count| *count as f64).collect();
+    let mut anomalies = Vec::new();
+    if counts.len() >= 3 {
+        let mean = counts.iter().sum::<f64>() / counts.len() as f64;
+        let deviation = (counts.iter().map(|count| (count - mean).powi(2)).sum::<f64>() / counts.len() as f64).sqrt();
+        for (start, count) in &requests {
+            if deviation > 0.0 && (*count as f64 - mean) / deviation >= SPIKE_SIGMAS {
+                anomalies.push(format!("Traffic spike at {}: {} requests ({:.1}x the hourly average)", start.format("%Y-%m-%d %H:00"), count, *count as f64 / mean));
+            }
+        }
+    }
+    let total_errors: u64 = errors.values().sum();
+    let overall = total_errors as f64 / logs.len().max(1) as f64;
+    for (start, count) in &requests {
+        let failed = errors.get(start).copied().unwrap_or(0);
+        let rate = failed as f64 / (*count).max(1) as f64;
+        if *count >= MIN_ERROR_REQUESTS && rate >= 0.05 && rate >= overall * 3.0 {
+            anomalies.push(format!("5xx spike at {}: {} of {} requests failed ({:.1}%)", start.format("%Y-%m-%d %H:00"), failed, count, rate * 100.0));
+        }
+    }
+    anomalies
+}
+
+fn pages_text(digest: &Digest, bullet: &str) -> String {
+    let lines: Vec<String> = digest.top_pages.iter().map(|(page, count)| format!("{} `{}` ({})", bullet, page, count)).collect();
+    if lines.is_empty() { String::from("-") } else { lines.join("\n") }
+}
+
+fn anomalies_text(digest: &Digest, bullet: &str) -> String {
+    let lines: Vec<String> = digest.anomalies.iter().map(|anomaly| format!("{} {}", bullet, anomaly)).collect();
+    if lines.is_empty() { String::from("None detected") } else { lines.join("\n") }
+}
+
+// The digest as report sections, for the Markdown and HTML renderers of `report`
+pub fn sections(digest: &Digest) -> Vec<Section> {
+    vec![
+        Section {
+            title: "Overview",
+            headers: vec!["Metric", "Value"],
+            rows: vec![
+                vec![String::from("Requests"), digest.requests.to_string()],
+                vec![String::from("5xx rate"), format!("{:.2}%", digest.server_error_rate)],
+                vec![String::from("4xx rate"), format!("{:.2}%", digest.client_error_rate)],
+            ],
+        },
+        Section { title: "Top pages", headers: vec!["Page", "Requests"], rows: digest.top_pages.iter().map(|(page, count)| vec![page.clone(), count.to_string()]).collect() },
+        Section { title: "Anomalies", headers: vec!["Anomaly"], rows: digest.anomalies.iter().map(|anomaly| vec![anomaly.clone()]).collect() },
+    ]
+}
+
+pub fn slack_payload(digest: &Digest) -> Value {
+    let fields = json!([
+        { "type": "mrkdwn", "text": format!("*Requests*\n{}", digest.requests) },
+        { "type": "mrkdwn", "text": format!("*5xx rate*\n{:.2}%", digest.server_error_rate) },
+        { "type": "mrkdwn", "text": format!("*4xx rate*\n{:.2}%", digest.client_error_rate) },
+        { "type": "mrkdwn", "text": format!("*Anomalies*\n{}", digest.anomalies.len()) },
+    ]);
+    json!({
+        "text": format!("{}: {} requests, {:.2}% 5xx", digest.title, digest.requests, digest.server_error_rate),
+        "blocks": [
+            { "type": "header", "text": { "type": "plain_text", "text": digest.title } },
+            { "type": "section", "fields": fields },
+            { "type": "section", "text": { "type": "mrkdwn", "text": format!("*Top pages*\n{}", pages_text(digest, "•")) } },
+            { "type": "section", "text": { "type": "mrkdwn", "text": format!("*Anomalies*\n{}", anomalies_text(digest, "•")) } },
+        ],
+    })
+}
+
+pub fn discord_payload(digest: &Digest) -> Value {
+    let color = if digest.anomalies.is_empty() { 0x2e_cc71 } else { 0xe7_4c3c };
+    json!({
+        "embeds": [{
+            "title": digest.title,
+            "color": color,
+            "fields": [
+                { "name": "Requests", "value": digest.requests.to_string(), "inline": true },
+                { "name": "5xx rate", "value": format!("{:.2}%", digest.server_error_rate), "inline": true },
+                { "name": "4xx rate", "value": format!("{:.2}%", digest.client_error_rate), "inline": true },
+                { "name": "Top pages", "value": pages_text(digest, "-") },
+                { "name": "Anomalies", "value": anomalies_text(digest, "-") },
+            ],
+        }],
+    })
+}
+
+pub fn post(webhook: &str, payload: &Value) -> Result<(), Box<dyn Error>> {
+    ureq::post(webhook).set("Content-Type", "application/json").send_string(&payload.to_string()).map_err(|e| format!("posting to webhook failed: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+use serde_json::{json, Value};
+
+use crate::NginxLog;
+
+pub const ATTRIBUTES: [&str; 9] = [
+    "client.address",
+    "http.status_code",
+    "http.response_content_length",
+    "http.method",
+    "http.target",
+    "http.flavor",
+    "http.user_agent",
+    "http.referer",
+    "http.request_id",
+];
+
+pub struct OtlpExporter {
+    endpoint: String,
+    service_name: String,
+    headers: Vec<(String, String)>,
+    batch_size: usize,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: &str, service_name: &str) -> OtlpExporter {
+        let endpoint = endpoint.trim_end_matches('/');
+        let endpoint = if endpoint.ends_with("/v1/logs") {
+            endpoint.to_string()
+        } else {
+            format!("{}/v1/logs", endpoint)
+        };
+        OtlpExporter {
+            endpoint,
+            service_name: service_name.to_string(),
+            headers: Vec::new(),
+            batch_size: 512,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> OtlpExporter {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> OtlpExporter {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn describe(&self) -> String {
+        format!("service.name={}, {} records per request, {} extra headers", self.service_name, self.batch_size, self.headers.len())
+    }
+
+    pub fn export(&self, logs: &[NginxLog]) -> Result<usize, Box<dyn Error>> {
+        let mut sent = 0;
+        for batch in logs.chunks(self.batch_size) {
+            let mut request = ureq::post(&self.endpoint).set("Content-Type", "application/json");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            request.send_string(&self.payload(batch).to_string())?;
+            sent += batch.len();
+        }
+        Ok(sent)
+    }
+
+    fn payload(&self, logs: &[NginxLog]) -> Value {
+        let records: Vec<Value> = logs.iter().map(log_record).collect();
+        json!({
+            "resourceLogs": [{
+                "resource": { "attributes": [attribute("service.name", json!({ "stringValue": self.service_name }))] },
+                "scopeLogs": [{
+                    "scope": { "name": "nginx-log-explorer" },
+                    "logRecords": records,
+                }],
+            }],
+        })
+    }
+}
+
+fn log_record(log: &NginxLog) -> Value {
+    let (severity_number, severity_text) = match log.status {
+        500..=599 => (17, "ERROR"),
+        400..=499 => (13, "WARN"),
+        _ => (9, "INFO"),
+    };
+    let mut attributes = vec![
+        attribute("client.address", string_value(&log.remote_addr)),
+        attribute("http.status_code", json!({ "intValue": log.status.to_string() })),
+        attribute("http.response_content_length", json!({ "intValue": log.body_bytes_sent.to_string() })),
+    ];
+    if let Some(method) = log.method() {
+        attributes.push(attribute("http.method", string_value(method)));
+    }
+    if let Some(path) = log.path() {
+        attributes.push(attribute("http.target", string_value(path)));
+    }
+    if let Some(protocol) = log.protocol() {
+        attributes.push(attribute("http.flavor", string_value(protocol.trim_start_matches("HTTP/"))));
+    }
+    for (key, value) in [("http.user_agent", &log.http_user_agent), ("http.referer", &log.http_referer)] {
+        let value = value.trim_matches('"');
+        if !value.is_empty() && value != "-" {
+            attributes.push(attribute(key, string_value(value)));
+        }
+    }
+    if let Some(request_id) = &log.request_id {
+        attributes.push(attribute("http.request_id", string_value(request_id)));
+    }
+    let time = log.request_time.timestamp_nanos_opt().unwrap_or_default().to_string();
+    json!({
+        "timeUnixNano": time,
+        "observedTimeUnixNano": time,
+        "severityNumber": severity_number,
+        "severityText": severity_text,
+        "body": string_value(log.request.trim_matches('"')),
+        "attributes": attributes,
+    })
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn string_value(value: &str) -> Value {
+    json!({ "stringValue": value })
+}
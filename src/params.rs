@@ -0,0 +1,58 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cve::percent_decode;
+use crate::digest::path_template;
+use crate::NginxLog;
+
+const SECRET_PARTS: [&str; 10] = ["token", "secret", "password", "passwd", "api_key", "apikey", "auth", "session", "signature", "jwt"];
+const SECRET_NAMES: [&str; 5] = ["key", "sig", "sid", "pwd", "code"];
+const CACHE_BUSTER_MIN_REQUESTS: u64 = 20;
+
+pub struct ParamUsage {
+    pub path: String,
+    pub name: String,
+    pub requests: u64,
+    pub values: usize,
+}
+
+impl ParamUsage {
+    pub fn note(&self) -> &'static str {
+        if looks_secret(&self.name) {
+            "possible secret"
+        } else if self.requests >= CACHE_BUSTER_MIN_REQUESTS && self.values as f64 >= self.requests as f64 * 0.9 {
+            "unique per request"
+        } else {
+            ""
+        }
+    }
+}
+
+pub fn query_params(logs: &[NginxLog], exact_paths: bool) -> Vec<ParamUsage> {
+    let mut usage: HashMap<(String, String), (u64, HashSet<String>)> = HashMap::new();
+    for log in logs {
+        let (path, query) = match log.path().and_then(|path| path.split_once('?')) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let path = if exact_paths { path.to_string() } else { path_template(path) };
+        let mut seen = HashSet::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = percent_decode(name);
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let entry = usage.entry((path.clone(), name)).or_default();
+            entry.0 += 1;
+            entry.1.insert(value.to_string());
+        }
+    }
+    let mut usage: Vec<ParamUsage> = usage.into_iter().map(|((path, name), (requests, values))| ParamUsage { path, name, requests, values: values.len() }).collect();
+    usage.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.path.cmp(&b.path)).then_with(|| a.name.cmp(&b.name)));
+    usage
+}
+
+fn looks_secret(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_NAMES.contains(&name.as_str()) || SECRET_PARTS.iter().any(|part| name.contains(part))
+}
@@ -0,0 +1,404 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde_json::{Map, Value};
+
+use crate::alb::parse_alb_line;
+use crate::csv_input::CsvFormat;
+use crate::format::LogFormat;
+use crate::w3c::W3cFormat;
+use crate::{parse_nginx_log_line, NginxLog};
+
+// --parser values, also accepted as parser in the config file
+pub const PARSER_NAMES: [&str; 8] = ["regex", "split", "json", "csv", "w3c", "iis", "cloudfront", "alb"];
+
+pub const COMBINED_FORMAT: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent""#;
+
+// Longer lines are rejected before parsing; nginx truncates its own log lines well below this
+pub const MAX_LINE_BYTES: usize = 64 * 1024;
+
+// Tried in order after RFC 3339; $time_local comes first as by far the most common
+const ZONED_FORMATS: [&str; 4] = ["%d/%b/%Y:%H:%M:%S %z", "%d/%b/%Y:%H:%M:%S%.f %z", "%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f %z"];
+// Times without an offset are read as local time
+const NAIVE_FORMATS: [&str; 5] = ["%d/%b/%Y:%H:%M:%S", "%d/%b/%Y:%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%d/%m/%Y %H:%M:%S"];
+
+#[derive(Debug)]
+pub enum ParseError {
+    TooLong(usize),
+    NoMatch(&'static str),
+    Missing(&'static str),
+    Invalid { field: &'static str, value: String },
+}
+
+impl ParseError {
+    pub fn invalid(field: &'static str, value: &str) -> ParseError {
+        // keep hostile input out of the logs, only the start of the value is useful
+        let end = value.char_indices().nth(64).map(|(index, _)| index).unwrap_or(value.len());
+        ParseError::Invalid { field, value: value[..end].to_string() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::TooLong(bytes) => write!(f, "line of {} bytes exceeds the {} byte limit", bytes, MAX_LINE_BYTES),
+            ParseError::NoMatch(parser) => write!(f, "line does not match the {} parser", parser),
+            ParseError::Missing(field) => write!(f, "missing {}", field),
+            ParseError::Invalid { field, value } => write!(f, "invalid {}: {:?}", field, value),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+pub fn is_extra_field(name: &str) -> bool {
+    let header = ["http_", "sent_http_", "cookie_", "upstream_", "ssl_"].iter().any(|prefix| name.starts_with(prefix));
+    (header || matches!(name, "request_time" | "host" | "server_name" | "server_protocol")) && !matches!(name, "http_referer" | "http_user_agent" | "http_x_request_id")
+}
+
+#[derive(Clone, Copy)]
+pub enum LineParser<'a> {
+    Regex,
+    Format(&'a LogFormat),
+    Split,
+    Json,
+    Csv(&'a CsvFormat),
+    W3c(&'a W3cFormat),
+    Alb,
+}
+
+impl<'a> LineParser<'a> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineParser::Regex => "regex",
+            LineParser::Format(_) => "log-format",
+            LineParser::Split => "split",
+            LineParser::Json => "json",
+            LineParser::Csv(_) => "csv",
+            LineParser::W3c(_) => "w3c",
+            LineParser::Alb => "alb",
+        }
+    }
+
+    pub fn skips(&self, line: &str) -> bool {
+        match self {
+            LineParser::Csv(format) => line.trim().is_empty() || format.is_header(line),
+            // IIS can start a file with a byte order mark before #Software:
+            LineParser::W3c(_) => line.trim().is_empty() || line.trim_start_matches('\u{feff}').starts_with('#'),
+            _ => line.trim().is_empty(),
+        }
+    }
+
+    pub fn parse(&self, line: &str) -> Option<NginxLog> {
+        self.parse_line(line).ok()
+    }
+
+    pub fn parse_line(&self, line: &str) -> Result<NginxLog, ParseError> {
+        if line.len() > MAX_LINE_BYTES {
+            return Err(ParseError::TooLong(line.len()));
+        }
+        let parsed = match self {
+            LineParser::Regex => return parse_nginx_log_line(line),
+            LineParser::Split => return parse_combined_split(line),
+            LineParser::Format(format) => return format.parse_line(line),
+            LineParser::Json => return parse_json_line(line),
+            LineParser::Csv(format) => return format.parse_line(line),
+            LineParser::W3c(format) => format.parse_line(line),
+            LineParser::Alb => parse_alb_line(line),
+        };
+        parsed.ok_or(ParseError::NoMatch(self.name()))
+    }
+}
+
+pub enum FormatSpec {
+    Parser(LineParser<'static>),
+    Format(LogFormat),
+    W3c(W3cFormat),
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, String> {
+        match spec {
+            "regex" => Ok(FormatSpec::Parser(LineParser::Regex)),
+            "split" | "combined" => Ok(FormatSpec::Parser(LineParser::Split)),
+            "json" => Ok(FormatSpec::Parser(LineParser::Json)),
+            "alb" => Ok(FormatSpec::Parser(LineParser::Alb)),
+            "cloudfront" => W3cFormat::cloudfront(None).map(FormatSpec::W3c),
+            "iis" => W3cFormat::iis(None).map(FormatSpec::W3c),
+            _ if spec.starts_with("#Fields:") => W3cFormat::compile(spec).map(FormatSpec::W3c),
+            _ if spec.contains('$') => LogFormat::compile(spec).map(FormatSpec::Format),
+            _ => Err(format!("unknown format: {} (expected regex, split, json, alb, cloudfront, iis, a log_format string or a W3C #Fields: line)", spec)),
+        }
+    }
+
+    pub fn parser(&self) -> LineParser<'_> {
+        match self {
+            FormatSpec::Parser(parser) => *parser,
+            FormatSpec::Format(format) => LineParser::Format(format),
+            FormatSpec::W3c(format) => LineParser::W3c(format),
+        }
+    }
+}
+
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Decodes nginx's \xHH escapes (used for ", \ and bytes outside printable ASCII) and \" / \\
+pub fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = match (bytes[index], bytes.get(index + 1)) {
+            (b'\\', Some(b'x')) => bytes
+                .get(index + 2..index + 4)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                .map(|byte| (byte, 4)),
+            (b'\\', Some(&byte)) if byte == b'"' || byte == b'\\' => Some((byte, 2)),
+            _ => None,
+        };
+        match escape {
+            Some((byte, len)) => {
+                decoded.push(byte);
+                index += len;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+// Anything that is not a number up to 999 means the line was not split where expected
+pub fn parse_status(value: &str) -> Result<u16, ParseError> {
+    value.parse::<u16>().ok().filter(|status| *status <= 999).ok_or_else(|| ParseError::invalid("status", value))
+}
+
+// Accepts $time_local with or without offset or milliseconds, $time_iso8601 and $msec
+pub fn parse_log_time(value: &str) -> Result<DateTime<Local>, ParseError> {
+    let value = value.trim().trim_matches(|c| c == '[' || c == ']');
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Local));
+    }
+    if let Some(time) = ZONED_FORMATS.iter().find_map(|format| DateTime::parse_from_str(value, format).ok()) {
+        return Ok(time.with_timezone(&Local));
+    }
+    if let Ok(epoch) = value.parse::<f64>() {
+        if epoch.is_finite() && epoch > 0.0 {
+            let millis = if epoch > 1e11 { epoch as i64 } else { (epoch * 1000.0) as i64 };
+            if let Some(time) = Local.timestamp_millis_opt(millis).single() {
+                return Ok(time);
+            }
+        }
+    }
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .ok_or_else(|| ParseError::invalid("time", value))
+}
+
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn token(&mut self) -> Option<&'a str> {
+        let end = self.rest.find(' ').unwrap_or(self.rest.len());
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest.strip_prefix(' ').unwrap_or(rest);
+        Some(token)
+    }
+
+    // nginx writes " inside quoted fields as \x22 and other tools as \"; a raw " only
+    // closes the field when a space or the end of the line follows it
+    fn quoted(&mut self) -> Option<&'a str> {
+        let inner = self.rest.strip_prefix('"')?;
+        let bytes = inner.as_bytes();
+        let mut escaped = false;
+        for (index, byte) in bytes.iter().enumerate() {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' if matches!(bytes.get(index + 1), None | Some(b' ')) => {
+                    let rest = &inner[index + 1..];
+                    self.rest = rest.strip_prefix(' ').unwrap_or(rest);
+                    return Some(&inner[..index]);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn delimited(&mut self, open: char, close: char) -> Option<&'a str> {
+        let inner = self.rest.strip_prefix(open)?;
+        let end = inner.find(close)?;
+        let value = &inner[..end];
+        let rest = &inner[end + close.len_utf8()..];
+        self.rest = rest.strip_prefix(' ').unwrap_or(rest);
+        Some(value)
+    }
+}
+
+fn parse_combined_split(line: &str) -> Result<NginxLog, ParseError> {
+    let mut cursor = Cursor { rest: line };
+    let remote_addr = cursor.token().filter(|addr| !addr.is_empty()).ok_or(ParseError::Missing("remote_addr"))?;
+    cursor.token();
+    let remote_user = cursor.token().unwrap_or("-");
+    let time = cursor.delimited('[', ']').ok_or(ParseError::Missing("[time_local]"))?;
+    let request = cursor.quoted().ok_or(ParseError::Missing("\"request\""))?;
+    let status = parse_status(cursor.token().unwrap_or_default())?;
+    let body_bytes_sent = cursor.token().and_then(|bytes| bytes.parse().ok()).unwrap_or(0);
+    let http_referer = cursor.quoted().unwrap_or("-");
+    let http_user_agent = cursor.quoted().unwrap_or("-");
+    let request_time = parse_log_time(time)?;
+    Ok(NginxLog {
+        remote_addr: remote_addr.to_string(),
+        remote_user: remote_user.to_string(),
+        request_time,
+        request: request.to_string(),
+        status,
+        body_bytes_sent,
+        http_referer: unescape(http_referer).into_owned(),
+        http_user_agent: unescape(http_user_agent).into_owned(),
+        ..Default::default()
+    })
+}
+
+fn parse_json_line(line: &str) -> Result<NginxLog, ParseError> {
+    let value: Value = serde_json::from_str(line.trim()).map_err(|_| ParseError::NoMatch("json"))?;
+    let object = value.as_object().ok_or(ParseError::NoMatch("json"))?;
+    let time = json_str(object, &["time_local", "time_iso8601", "@timestamp", "time", "timestamp", "msec"]).ok_or(ParseError::Missing("time"))?;
+    let request_time = parse_log_time(&time)?;
+    let request = match json_str(object, &["request"]) {
+        Some(request) => request,
+        None => format!(
+            "{} {} {}",
+            json_str(object, &["request_method", "method"]).ok_or(ParseError::Missing("request_method"))?,
+            json_str(object, &["request_uri", "uri"]).ok_or(ParseError::Missing("request_uri"))?,
+            json_str(object, &["server_protocol", "protocol"]).unwrap_or_else(|| String::from("-"))
+        ),
+    };
+    let status = json_str(object, &["status"]).map(|status| parse_status(&status)).transpose()?.unwrap_or(0);
+    let field = |names: &[&str]| json_str(object, names).unwrap_or_else(|| String::from("-"));
+    Ok(NginxLog {
+        remote_addr: field(&["remote_addr", "client_ip"]),
+        remote_user: field(&["remote_user"]),
+        request_time,
+        request,
+        status,
+        body_bytes_sent: field(&["body_bytes_sent", "bytes_sent"]).parse().unwrap_or(0),
+        http_referer: field(&["http_referer", "referer"]),
+        http_user_agent: field(&["http_user_agent", "user_agent"]),
+        request_id: json_str(object, &["request_id", "http_x_request_id"]).filter(|id| !id.is_empty() && id != "-"),
+        extra: object
+            .keys()
+            .filter(|name| is_extra_field(name))
+            .filter_map(|name| Some((name.clone(), json_str(object, &[name])?)))
+            .filter(|(_, value)| !value.is_empty() && value != "-")
+            .collect(),
+        ..Default::default()
+    })
+}
+
+fn json_str(object: &Map<String, Value>, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| match object.get(*name)? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::fuzz::parse_all;
+
+    const LINE: &str = r#"203.0.113.7 - - [10/Oct/2024:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 512 "-" "curl/8.0""#;
+
+    proptest! {
+        #[test]
+        fn no_byte_sequence_panics(data in proptest::collection::vec(any::<u8>(), 0..4096)) {
+            parse_all(&data);
+        }
+
+        #[test]
+        fn mutated_lines_do_not_panic(index in 0..LINE.len(), byte in any::<u8>()) {
+            let mut data = LINE.as_bytes().to_vec();
+            data[index] = byte;
+            parse_all(&data);
+        }
+
+        #[test]
+        fn escaped_quotes_in_user_agents_round_trip(agent in "[ -~]{0,80}") {
+            // nginx writes " and \ inside quoted variables as \x22 and \x5C
+            let line = LINE.replace("curl/8.0", &agent.replace('\\', "\\x5C").replace('"', "\\x22"));
+            prop_assert_eq!(LineParser::Split.parse_line(&line).unwrap().http_user_agent, agent.clone());
+            prop_assert_eq!(LineParser::Regex.parse_line(&line).unwrap().http_user_agent, format!("\"{}\"", agent));
+        }
+    }
+
+    // A raw " only ends the field when a space or the end of the line follows
+    #[test]
+    fn embedded_quotes_stay_in_the_field() {
+        let line = LINE.replace("curl/8.0", r#"say \"hi\" "there"#);
+        assert_eq!(LineParser::Split.parse_line(&line).unwrap().http_user_agent, r#"say "hi" "there"#);
+    }
+
+    #[test]
+    fn huge_lines_are_rejected() {
+        let line = format!("{}{}", LINE, " ".repeat(MAX_LINE_BYTES));
+        assert!(matches!(LineParser::Split.parse_line(&line), Err(ParseError::TooLong(_))));
+    }
+
+    #[test]
+    fn invalid_status_is_an_error() {
+        let format = LogFormat::compile(COMBINED_FORMAT).unwrap();
+        let csv = CsvFormat::compile(&["time=1,path=2,status=3"], ',', None).unwrap();
+        let line = LINE.replace(" 200 ", " 2x0 ");
+        for parser in [LineParser::Regex, LineParser::Split, LineParser::Format(&format)] {
+            assert!(matches!(parser.parse_line(&line), Err(ParseError::Invalid { field: "status", .. }) | Err(ParseError::NoMatch(_))), "{}", parser.name());
+        }
+        assert!(matches!(LineParser::Split.parse_line(&LINE.replace(" 200 ", " 70000 ")), Err(ParseError::Invalid { field: "status", .. })));
+        assert!(matches!(LineParser::Format(&format).parse_line(&line), Err(ParseError::Invalid { field: "status", .. })));
+        let json = r#"{"time_iso8601":"2024-10-10T13:55:36+00:00","request":"GET / HTTP/1.1","status":"abc"}"#;
+        assert!(matches!(LineParser::Json.parse_line(json), Err(ParseError::Invalid { field: "status", .. })));
+        assert!(matches!(LineParser::Csv(&csv).parse_line("2024-10-10T13:55:36Z,/,-"), Err(ParseError::Invalid { field: "status", .. })));
+    }
+
+    #[test]
+    fn missing_status_is_not_an_error() {
+        let json = r#"{"time_iso8601":"2024-10-10T13:55:36+00:00","request":"GET / HTTP/1.1"}"#;
+        assert_eq!(LineParser::Json.parse_line(json).unwrap().status, 0);
+    }
+}
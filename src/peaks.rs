@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+
+use crate::interval::Interval;
+use crate::NginxLog;
+
+pub struct Peak {
+    pub window: i64,
+    pub start: DateTime<Local>,
+    pub requests: u64,
+    pub top_paths: Vec<(String, u64)>,
+    pub top_ips: Vec<(String, u64)>,
+}
+
+impl Peak {
+    pub fn rps(&self) -> f64 {
+        self.requests as f64 / self.window as f64
+    }
+}
+
+pub fn parse_window(spec: &str) -> Result<i64, String> {
+    match Interval::parse(spec)? {
+        Interval::Seconds(seconds) => Ok(seconds),
+        Interval::Days(days) => Ok(days as i64 * 86400),
+        _ => Err(format!("peak window must be given in s, m, h or d: {}", spec)),
+    }
+}
+
+pub fn find_peak(logs: &[NginxLog], window: i64, top: usize) -> Option<Peak> {
+    let mut times: Vec<i64> = logs.iter().map(|log| log.request_time.timestamp()).collect();
+    times.sort_unstable();
+    let mut best: Option<(i64, usize)> = None;
+    let mut first = 0;
+    for (last, &time) in times.iter().enumerate() {
+        while times[first] <= time - window {
+            first += 1;
+        }
+        let count = last - first + 1;
+        if best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((times[first], count));
+        }
+    }
+    let (start, requests) = best?;
+    let during: Vec<&NginxLog> = logs
+        .iter()
+        .filter(|log| (start..start + window).contains(&log.request_time.timestamp()))
+        .collect();
+    let start = during.iter().map(|log| log.request_time).min()?;
+    Some(Peak {
+        window,
+        start,
+        requests: requests as u64,
+        top_paths: top_counts(during.iter().map(|log| log.path().unwrap_or("-").to_string()), top),
+        top_ips: top_counts(during.iter().map(|log| log.client()), top),
+    })
+}
+
+fn top_counts<I: Iterator<Item = String>>(values: I, top: usize) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(top);
+    counts
+}
@@ -0,0 +1,445 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::mpsc::{channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Local};
+use flate2::read::MultiGzDecoder;
+use log::debug;
+use tar::Archive;
+use zip::ZipArchive;
+
+use crate::parser::{glob_match, LineParser};
+use crate::w3c::W3cFormat;
+use crate::{in_path, too_many_errors, ErrorLimit, NginxLog, ReadOptions, ReadStats};
+
+const CHUNK_SIZE: usize = 1 << 20;
+const CHANNEL_DEPTH: usize = 4;
+
+// Set with --show-source; archive members are named archive.tar.gz:member
+pub const FILE_FIELD: &str = "file";
+pub const LINE_FIELD: &str = "line";
+
+// Lines in file order; `fields` holds the columns of the last W3C #Fields: directive before them
+struct LineBatch {
+    index: usize,
+    first_line: usize,
+    lines: Vec<String>,
+    fields: Option<Arc<W3cFormat>>,
+}
+
+struct ParsedBatch {
+    index: usize,
+    logs: Vec<NginxLog>,
+    lines: u64,
+    failed: u64,
+}
+
+pub fn read_gzip(path: &Path, options: &ReadOptions, stats: &mut ReadStats) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    debug!("stage=open file={} decoder=gzip", path.display());
+    let file = File::open(path)?;
+    let decoder = MultiGzDecoder::new(BufReader::new(file));
+    if !is_tarball(path) {
+        let mut logs = read_parallel(decoder, options, stats).map_err(|e| in_path(path.display(), e))?;
+        if options.track_source {
+            tag_file(&mut logs, &path.display().to_string());
+        }
+        return Ok(logs);
+    }
+    let mut members = Vec::new();
+    let mut archive = Archive::new(decoder);
+    for entry in archive.entries().map_err(|e| in_path(path.display(), e))? {
+        let entry = entry.map_err(|e| in_path(path.display(), e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path().map(|name| name.display().to_string()).unwrap_or_default();
+        members.push(read_member(path, &name, entry, options, stats)?);
+    }
+    Ok(combine(members, options))
+}
+
+pub fn read_zip(path: &Path, options: &ReadOptions, stats: &mut ReadStats) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    debug!("stage=open file={} decoder=zip", path.display());
+    let mut archive = ZipArchive::new(BufReader::new(File::open(path)?)).map_err(|e| in_path(path.display(), e))?;
+    let mut members = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|e| in_path(path.display(), e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        members.push(read_member(path, &name, entry, options, stats)?);
+    }
+    Ok(combine(members, options))
+}
+
+fn read_member<R: Read>(archive: &Path, name: &str, member: R, options: &ReadOptions, stats: &mut ReadStats) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    if !options.members.is_empty() && !options.members.iter().any(|pattern| glob_match(pattern, name) || glob_match(pattern, base_name(name))) {
+        debug!("stage=open file={} member={} skipped", archive.display(), name);
+        return Ok(Vec::new());
+    }
+    let member_options = options.for_file(Path::new(name));
+    debug!("stage=open file={} member={} parser={}", archive.display(), name, member_options.parser.name());
+    let read = if name.ends_with(".gz") {
+        read_parallel(MultiGzDecoder::new(member), &member_options, stats)
+    } else {
+        read_parallel(member, &member_options, stats)
+    };
+    let mut logs = read.map_err(|e| in_path(format!("{}:{}", archive.display(), name), e))?;
+    if options.track_source {
+        tag_file(&mut logs, &format!("{}:{}", archive.display(), name));
+    }
+    Ok(logs)
+}
+
+pub fn source_of(log: &NginxLog) -> (&str, usize) {
+    let file = log.extra.get(FILE_FIELD).map(String::as_str).unwrap_or("-");
+    (file, log.extra.get(LINE_FIELD).and_then(|line| line.parse().ok()).unwrap_or(0))
+}
+
+pub fn tag_file(logs: &mut [NginxLog], file: &str) {
+    for log in logs {
+        log.extra.insert(String::from(FILE_FIELD), file.to_string());
+    }
+}
+
+pub fn combine(streams: Vec<Vec<NginxLog>>, options: &ReadOptions) -> Vec<NginxLog> {
+    if options.chronological {
+        merge_chronological(streams)
+    } else {
+        streams.into_iter().flatten().collect()
+    }
+}
+
+fn merge_chronological(streams: Vec<Vec<NginxLog>>) -> Vec<NginxLog> {
+    let total = streams.iter().map(Vec::len).sum();
+    let mut streams: Vec<_> = streams
+        .into_iter()
+        .map(|mut stream| {
+            stream.sort_by_key(|log| log.request_time);
+            stream.into_iter().peekable()
+        })
+        .collect();
+    let mut heap: BinaryHeap<Reverse<(DateTime<Local>, usize)>> = streams
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(index, stream)| stream.peek().map(|log| Reverse((log.request_time, index))))
+        .collect();
+    let mut merged = Vec::with_capacity(total);
+    while let Some(Reverse((_, index))) = heap.pop() {
+        merged.extend(streams[index].next());
+        if let Some(next) = streams[index].peek() {
+            heap.push(Reverse((next.request_time, index)));
+        }
+    }
+    merged
+}
+
+pub fn read_lossy_line<R: BufRead>(reader: &mut R, buffer: &mut Vec<u8>) -> io::Result<Option<String>> {
+    buffer.clear();
+    if reader.read_until(b'\n', buffer)? == 0 {
+        return Ok(None);
+    }
+    let line = buffer.strip_suffix(b"\n").unwrap_or(buffer);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    Ok(Some(String::from_utf8_lossy(line).into_owned()))
+}
+
+pub fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+pub fn is_tarball(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+pub fn read_parallel<R: Read>(mut source: R, options: &ReadOptions, stats: &mut ReadStats) -> Result<Vec<NginxLog>, Box<dyn Error>> {
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).saturating_sub(2).max(1);
+    debug!("stage=pipeline parser_threads={}", workers);
+    let parser = options.parser;
+    let (track_source, keep_raw) = (options.track_source, options.keep_raw);
+    let (chunk_tx, chunk_rx) = sync_channel::<Vec<u8>>(CHANNEL_DEPTH);
+    let w3c = match parser {
+        LineParser::W3c(format) => Some(format),
+        _ => None,
+    };
+    let (batch_tx, batch_rx) = sync_channel::<LineBatch>(CHANNEL_DEPTH * workers);
+    let batch_rx = Arc::new(Mutex::new(batch_rx));
+    let (result_tx, result_rx) = channel::<ParsedBatch>();
+
+    let (mut batches, decompressed) = thread::scope(|scope| {
+        scope.spawn(move || {
+            let (mut index, mut first_line) = (0, 1);
+            let mut fields: Option<Arc<W3cFormat>> = None;
+            // false once the workers are gone
+            let mut send = |lines: Vec<String>| {
+                let batches = match w3c {
+                    Some(base) => w3c_batches(lines, base, &mut fields),
+                    None => vec![(lines, None)],
+                };
+                for (lines, fields) in batches {
+                    let count = lines.len();
+                    if batch_tx.send(LineBatch { index, first_line, lines, fields }).is_err() {
+                        return false;
+                    }
+                    index += 1;
+                    first_line += count;
+                }
+                true
+            };
+            let mut pending: Vec<u8> = Vec::new();
+            for chunk in chunk_rx {
+                pending.extend_from_slice(&chunk);
+                let cut = match pending.iter().rposition(|&byte| byte == b'\n') {
+                    Some(position) => position + 1,
+                    None => continue,
+                };
+                let rest = pending.split_off(cut);
+                let lines = String::from_utf8_lossy(&pending).lines().map(String::from).collect();
+                pending = rest;
+                if !send(lines) {
+                    return;
+                }
+            }
+            if !pending.is_empty() {
+                send(String::from_utf8_lossy(&pending).lines().map(String::from).collect());
+            }
+        });
+
+        for _ in 0..workers {
+            let batch_rx = Arc::clone(&batch_rx);
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                let received = batch_rx.lock().unwrap().recv();
+                let LineBatch { index, first_line, lines, fields } = match received {
+                    Ok(batch) => batch,
+                    Err(_) => return,
+                };
+                let parser = match &fields {
+                    Some(format) => LineParser::W3c(format),
+                    None => parser,
+                };
+                let mut batch = ParsedBatch { index, logs: Vec::with_capacity(lines.len()), lines: 0, failed: 0 };
+                for (offset, line) in lines.iter().map(|line| line.trim_end_matches('\r').trim_matches('\0')).enumerate().filter(|(_, line)| !parser.skips(line)) {
+                    batch.lines += 1;
+                    match parser.parse(line) {
+                        Some(mut log) => {
+                            if track_source {
+                                log.extra.insert(String::from(LINE_FIELD), (first_line + offset).to_string());
+                            }
+                            if keep_raw {
+                                log.raw = Some(line.to_string());
+                            }
+                            batch.logs.push(log);
+                        }
+                        None => batch.failed += 1,
+                    }
+                }
+                if result_tx.send(batch).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(result_tx);
+        let collector = scope.spawn(move || result_rx.iter().collect::<Vec<ParsedBatch>>());
+
+        let decompressed = (|| -> io::Result<()> {
+            loop {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let read = fill(&mut source, &mut chunk)?;
+                if read == 0 {
+                    return Ok(());
+                }
+                chunk.truncate(read);
+                if chunk_tx.send(chunk).is_err() {
+                    return Ok(());
+                }
+            }
+        })();
+        drop(chunk_tx);
+        (collector.join().unwrap(), decompressed)
+    });
+    decompressed?;
+
+    batches.sort_by_key(|batch| batch.index);
+    let mut logs = Vec::with_capacity(batches.iter().map(|batch| batch.logs.len()).sum());
+    for batch in batches {
+        stats.lines += batch.lines;
+        stats.failed += batch.failed;
+        logs.extend(batch.logs);
+    }
+    if let Some(limit @ ErrorLimit::Count(_)) = options.max_errors {
+        if limit.exceeded(stats) {
+            return Err(too_many_errors(limit, stats));
+        }
+    }
+    Ok(logs)
+}
+
+// The columns a #Fields: line switches a W3C log to; None for any other line
+pub fn w3c_directive(base: &W3cFormat, line: &str) -> Option<W3cFormat> {
+    let directive = line.trim_start_matches('\u{feff}').trim_end();
+    if !directive.starts_with("#Fields:") {
+        return None;
+    }
+    base.with_fields(directive).map_err(|e| debug!("stage=parse directive skipped reason={}", e)).ok()
+}
+
+// Cuts the lines wherever a #Fields: directive changes the columns, as IIS writes one after each restart
+fn w3c_batches(lines: Vec<String>, base: &W3cFormat, current: &mut Option<Arc<W3cFormat>>) -> Vec<(Vec<String>, Option<Arc<W3cFormat>>)> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    for line in lines {
+        if let Some(format) = w3c_directive(base, &line) {
+            if !batch.is_empty() {
+                batches.push((std::mem::take(&mut batch), current.clone()));
+            }
+            *current = Some(Arc::new(format));
+        }
+        batch.push(line);
+    }
+    batches.push((batch, current.clone()));
+    batches
+}
+
+fn fill<R: Read>(source: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match source.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // One file as a Windows host can leave it: CRLF endings, then UTF-8, Latin-1 and bytes valid in neither
+    fn mixed_file() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"203.0.113.1 - - [10/Oct/2024:13:55:36 +0000] \"GET /caf\xc3\xa9 HTTP/1.1\" 200 5 \"-\" \"Mozilla/5.0\"\r\n");
+        data.extend_from_slice(b"203.0.113.2 - - [10/Oct/2024:13:55:37 +0000] \"GET /caf\xe9 HTTP/1.1\" 200 5 \"-\" \"Mozilla/5.0\"\r\n");
+        data.extend_from_slice(b"203.0.113.3 - - [10/Oct/2024:13:55:38 +0000] \"GET /\xff\xfe\x80 HTTP/1.1\" 404 0 \"-\" \"bot \xc3\"\r\n");
+        data
+    }
+
+    #[test]
+    fn mixed_encodings_are_decoded_lossily() {
+        let mut reader = Cursor::new(mixed_file());
+        let mut buffer = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(line) = read_lossy_line(&mut reader, &mut buffer).unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("GET /café HTTP/1.1"));
+        assert!(lines[1].contains("GET /caf\u{fffd} HTTP/1.1"));
+        assert!(lines[2].contains("GET /\u{fffd}\u{fffd}\u{fffd} HTTP/1.1"));
+        assert!(lines.iter().all(|line| !line.ends_with('\r')));
+    }
+
+    #[test]
+    fn every_line_of_a_mixed_file_parses() {
+        let options = ReadOptions {
+            parser: LineParser::Split,
+            formats: &[],
+            max_files: None,
+            members: &[],
+            newest_first: false,
+            chronological: false,
+            since: None,
+            entries_since: None,
+            max_errors: None,
+            track_source: false,
+            keep_raw: false,
+        };
+        let mut stats = ReadStats::default();
+        let logs = read_parallel(Cursor::new(mixed_file()), &options, &mut stats).unwrap();
+        assert_eq!((stats.lines, stats.failed), (3, 0));
+        let mut paths: Vec<&str> = logs.iter().filter_map(|log| log.path()).collect();
+        paths.sort();
+        assert_eq!(paths, ["/café", "/caf\u{fffd}", "/\u{fffd}\u{fffd}\u{fffd}"]);
+        assert!(logs.iter().any(|log| log.http_user_agent == "bot \u{fffd}"));
+    }
+
+    #[test]
+    fn a_new_fields_directive_changes_the_columns() {
+        let base = W3cFormat::iis(None).unwrap();
+        let mut data = String::new();
+        data.push_str("#Fields: date time c-ip cs-method cs-uri-stem sc-status\n");
+        data.push_str("2024-10-10 13:55:36 203.0.113.1 GET /first 200\n");
+        data.push_str("#Fields: date time cs-method cs-uri-stem sc-status c-ip\n");
+        data.push_str("2024-10-10 13:55:37 GET /second 404 203.0.113.2\n");
+        let options = ReadOptions {
+            parser: LineParser::W3c(&base),
+            formats: &[],
+            max_files: None,
+            members: &[],
+            newest_first: false,
+            chronological: false,
+            since: None,
+            entries_since: None,
+            max_errors: None,
+            track_source: false,
+            keep_raw: false,
+        };
+        let mut stats = ReadStats::default();
+        let logs = read_parallel(Cursor::new(data.into_bytes()), &options, &mut stats).unwrap();
+        assert_eq!((stats.lines, stats.failed), (2, 0));
+        let mut parsed: Vec<(&str, &str, u16)> = logs.iter().map(|log| (log.remote_addr.as_str(), log.path().unwrap(), log.status)).collect();
+        parsed.sort();
+        assert_eq!(parsed, [("203.0.113.1", "/first", 200), ("203.0.113.2", "/second", 404)]);
+    }
+
+    #[test]
+    fn a_plain_file_follows_its_own_fields_directives() {
+        let base = W3cFormat::iis(None).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("u_ex241010.log");
+        let mut data = String::from("#Software: Microsoft Internet Information Services 10.0\r\n");
+        data.push_str("#Fields: date time c-ip cs-method cs-uri-stem sc-status cs-bytes\r\n");
+        data.push_str("2024-10-10 13:55:36 203.0.113.1 POST /upload 201 5120\r\n");
+        data.push_str("#Fields: date time cs-method cs-uri-stem sc-status c-ip\r\n");
+        data.push_str("2024-10-10 13:55:37 GET /second 404 203.0.113.2\r\n");
+        std::fs::write(&path, data).unwrap();
+        let options = ReadOptions {
+            parser: LineParser::W3c(&base),
+            formats: &[],
+            max_files: None,
+            members: &[],
+            newest_first: false,
+            chronological: false,
+            since: None,
+            entries_since: None,
+            max_errors: None,
+            track_source: false,
+            keep_raw: false,
+        };
+        let mut stats = ReadStats::default();
+        let logs = crate::read_log_path(&path, &options, &mut stats).unwrap();
+        assert_eq!((stats.lines, stats.failed), (2, 0));
+        let parsed: Vec<(&str, &str, u16)> = logs.iter().map(|log| (log.remote_addr.as_str(), log.path().unwrap(), log.status)).collect();
+        assert_eq!(parsed, [("203.0.113.1", "/upload", 201), ("203.0.113.2", "/second", 404)]);
+        assert_eq!(logs[0].extra.get("request_length").map(String::as_str), Some("5120"));
+    }
+
+    #[test]
+    fn backslash_paths_match_by_file_name() {
+        assert_eq!(base_name(r"C:\inetpub\logs\access.log"), "access.log");
+        assert_eq!(base_name("logs/access.log"), "access.log");
+    }
+}
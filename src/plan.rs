@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
+
+const SAMPLE_SIZE: u64 = 64 * 1024;
+
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub uncompressed: Option<u64>,
+    pub estimated_lines: u64,
+}
+
+pub fn plan_file(path: &Path) -> io::Result<PlannedFile> {
+    let size = path.metadata()?.len();
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default();
+    let (uncompressed, sample) = match extension.as_str() {
+        "gz" | "tgz" => (Some(gzip_size(path)?), sample(MultiGzDecoder::new(BufReader::new(File::open(path)?)))?),
+        "zip" => {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(path)?)).map_err(io::Error::other)?;
+            let total = (0..archive.len()).filter_map(|index| archive.by_index(index).ok().map(|entry| entry.size())).sum();
+            let first = (0..archive.len()).find(|index| archive.by_index(*index).is_ok_and(|entry| !entry.is_dir()));
+            let sample = match first {
+                Some(index) => sample(archive.by_index(index).map_err(io::Error::other)?)?,
+                None => Vec::new(),
+            };
+            (Some(total), sample)
+        }
+        _ => (None, sample(File::open(path)?)?),
+    };
+    let lines = sample.iter().filter(|byte| **byte == b'\n').count() as u64;
+    let content = uncompressed.unwrap_or(size);
+    let estimated_lines = if lines == 0 {
+        u64::from(!sample.is_empty())
+    } else {
+        (content as f64 / (sample.len() as f64 / lines as f64)).round() as u64
+    };
+    Ok(PlannedFile { path: path.to_path_buf(), size, uncompressed, estimated_lines })
+}
+
+fn sample<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut sample = Vec::new();
+    reader.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+    Ok(sample)
+}
+
+fn gzip_size(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < 4 {
+        return Ok(0);
+    }
+    file.seek(SeekFrom::End(-4))?;
+    let mut trailer = [0; 4];
+    file.read_exact(&mut trailer)?;
+    Ok(u32::from_le_bytes(trailer) as u64)
+}
+
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(feature = "wasm")]
+use std::cell::RefCell;
+use std::error::Error;
+
+use serde_json::Value;
+
+use crate::NginxLog;
+
+pub struct Plugin {
+    path: String,
+    #[cfg(feature = "wasm")]
+    runtime: RefCell<Runtime>,
+}
+
+#[cfg(feature = "wasm")]
+struct Runtime {
+    store: wasmtime::Store<()>,
+    memory: wasmtime::Memory,
+    alloc: wasmtime::TypedFunc<i32, i32>,
+    enrich: wasmtime::TypedFunc<(i32, i32), i64>,
+}
+
+impl Plugin {
+    #[cfg(feature = "wasm")]
+    pub fn load(path: &str) -> Result<Plugin, Box<dyn Error>> {
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::from_file(&engine, path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut store = wasmtime::Store::new(&engine, ());
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).map_err(|e| format!("{}: {}", path, e))?;
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| format!("{}: plugin does not export its memory", path))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc").map_err(|e| format!("{}: alloc: {}", path, e))?;
+        let enrich = instance.get_typed_func::<(i32, i32), i64>(&mut store, "enrich").map_err(|e| format!("{}: enrich: {}", path, e))?;
+        Ok(Plugin { path: path.to_string(), runtime: RefCell::new(Runtime { store, memory, alloc, enrich }) })
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn load(path: &str) -> Result<Plugin, Box<dyn Error>> {
+        Err(format!("{}: built without WASM plugin support; rebuild with --features wasm", path).into())
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn enrich(&self, log: &mut NginxLog) -> Result<(), Box<dyn Error>> {
+        let output = self.call(&serde_json::to_vec(log)?).map_err(|e| format!("{}: {}", self.path, e))?;
+        let fields = match serde_json::from_slice(&output).map_err(|e| format!("{}: invalid enrich output: {}", self.path, e))? {
+            Value::Object(fields) => fields,
+            Value::Null => return Ok(()),
+            other => return Err(format!("{}: enrich must return a JSON object, got {}", self.path, other).into()),
+        };
+        for (name, value) in fields {
+            let value = match value {
+                Value::String(value) => value,
+                Value::Null => continue,
+                other => other.to_string(),
+            };
+            log.extra.insert(name, value);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm")]
+    fn call(&self, input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut runtime = self.runtime.borrow_mut();
+        let runtime = &mut *runtime;
+        let length = i32::try_from(input.len())?;
+        let pointer = runtime.alloc.call(&mut runtime.store, length)?;
+        runtime.memory.write(&mut runtime.store, pointer as u32 as usize, input)?;
+        let packed = runtime.enrich.call(&mut runtime.store, (pointer, length))? as u64;
+        let mut output = vec![0; (packed & 0xffff_ffff) as usize];
+        runtime.memory.read(&runtime.store, (packed >> 32) as usize, &mut output)?;
+        Ok(output)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn call(&self, _input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        Err("built without WASM plugin support".into())
+    }
+}
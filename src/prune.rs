@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+const COMPRESSED: [&str; 6] = ["gz", "tgz", "zip", "bz2", "xz", "zst"];
+
+pub struct Expired {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: DateTime<Local>,
+    pub kept: Option<String>,
+}
+
+pub fn expired_files(dir: &Path, cutoff: DateTime<Local>) -> io::Result<Vec<Expired>> {
+    let open = open_files();
+    let mut expired = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            continue;
+        }
+        let modified = DateTime::<Local>::from(metadata.modified()?);
+        if modified >= cutoff {
+            continue;
+        }
+        let path = entry.path();
+        let kept = if metadata.file_type().is_symlink() {
+            Some(String::from("symlink"))
+        } else if !is_rotated(&entry.file_name().to_string_lossy()) {
+            Some(String::from("not a rotated log"))
+        } else if open.contains(&fs::canonicalize(&path)?) {
+            Some(String::from("open by a running process"))
+        } else {
+            None
+        };
+        expired.push(Expired { path, size: metadata.len(), modified, kept });
+    }
+    expired.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    Ok(expired)
+}
+
+pub fn archive(path: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let target = dir.join(path.file_name().ok_or_else(|| io::Error::other("no file name"))?);
+    if target.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+    }
+    if fs::rename(path, &target).is_err() {
+        fs::copy(path, &target)?;
+        fs::remove_file(path)?;
+    }
+    Ok(target)
+}
+
+// Active logs end in .log; logrotate appends a number or date, and compressed files are never written to
+fn is_rotated(name: &str) -> bool {
+    if name.rsplit_once('.').is_some_and(|(_, extension)| COMPRESSED.contains(&extension)) {
+        return true;
+    }
+    match name.find(".log") {
+        Some(index) => {
+            let suffix = &name[index + ".log".len()..];
+            suffix.len() > 1 && (suffix.starts_with('.') || suffix.starts_with('-')) && suffix[1..].starts_with(|c: char| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+fn open_files() -> HashSet<PathBuf> {
+    let mut open = HashSet::new();
+    let processes = match fs::read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return open,
+    };
+    for process in processes.flatten() {
+        if let Ok(descriptors) = fs::read_dir(process.path().join("fd")) {
+            open.extend(descriptors.flatten().filter_map(|descriptor| fs::read_link(descriptor.path()).ok()));
+        }
+    }
+    open
+}
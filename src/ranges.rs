@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::NaiveDate;
+
+use crate::NginxLog;
+
+pub const PARTIAL_CONTENT: u16 = 206;
+
+// $sent_http_content_range, e.g. "bytes 0-1048575/73400320"
+const CONTENT_RANGE_FIELD: &str = "sent_http_content_range";
+
+#[derive(Default)]
+pub struct RangedFile {
+    pub path: String,
+    pub range_requests: u64,
+    pub range_bytes: u64,
+    pub full_downloads: u64,
+    pub full_bytes: u64,
+    // From Content-Range when logged, otherwise the largest full response
+    pub size: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct RangeClient {
+    pub client: String,
+    pub requests: u64,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+impl RangeClient {
+    pub fn average(&self) -> u64 {
+        self.bytes / self.requests.max(1)
+    }
+}
+
+pub fn total_size(log: &NginxLog) -> Option<u64> {
+    let value = log.extra.get(CONTENT_RANGE_FIELD)?;
+    value.rsplit_once('/').and_then(|(_, total)| total.trim().parse().ok())
+}
+
+fn file(log: &NginxLog) -> &str {
+    let path = log.path().unwrap_or("-");
+    path.split_once('?').map(|(path, _)| path).unwrap_or(path)
+}
+
+// Files with at least one 206 response, most range bytes first
+pub fn ranged_files(logs: &[NginxLog]) -> Vec<RangedFile> {
+    let mut files: HashMap<&str, RangedFile> = HashMap::new();
+    for log in logs.iter().filter(|log| log.status == PARTIAL_CONTENT || log.status == 200) {
+        let path = file(log);
+        let entry = files.entry(path).or_insert_with(|| RangedFile { path: path.to_string(), ..Default::default() });
+        if log.status == PARTIAL_CONTENT {
+            entry.range_requests += 1;
+            entry.range_bytes += log.body_bytes_sent;
+            if let Some(size) = total_size(log) {
+                entry.size = Some(entry.size.unwrap_or(0).max(size));
+            }
+        } else {
+            entry.full_downloads += 1;
+            entry.full_bytes += log.body_bytes_sent;
+            if total_size(log).is_none() {
+                entry.size = Some(entry.size.unwrap_or(0).max(log.body_bytes_sent));
+            }
+        }
+    }
+    let mut files: Vec<RangedFile> = files.into_values().filter(|file| file.range_requests > 0).collect();
+    files.sort_by(|a, b| b.range_bytes.cmp(&a.range_bytes).then_with(|| a.path.cmp(&b.path)));
+    files
+}
+
+// Clients by number of 206 responses, most first
+pub fn range_clients(logs: &[NginxLog]) -> Vec<RangeClient> {
+    let mut clients: HashMap<String, (RangeClient, HashSet<&str>)> = HashMap::new();
+    for log in logs.iter().filter(|log| log.status == PARTIAL_CONTENT) {
+        let client = log.client();
+        let (entry, files) = clients.entry(client.clone()).or_insert_with(|| (RangeClient { client, ..Default::default() }, HashSet::new()));
+        entry.requests += 1;
+        entry.bytes += log.body_bytes_sent;
+        files.insert(file(log));
+    }
+    let mut clients: Vec<RangeClient> = clients.into_values().map(|(client, files)| RangeClient { files: files.len(), ..client }).collect();
+    clients.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.client.cmp(&b.client)));
+    clients
+}
+
+#[derive(Default)]
+pub struct DailyDownloads {
+    pub complete: u64,
+    pub aborted: u64,
+    pub ranges: u64,
+    pub range_bytes: u64,
+}
+
+impl DailyDownloads {
+    // Range requests only add up to whole files on average, so they count as a fraction
+    pub fn estimated(&self, size: u64) -> f64 {
+        self.complete as f64 + self.range_bytes as f64 / size.max(1) as f64
+    }
+}
+
+// Without a known size the largest response for the file is taken as complete
+pub fn inferred_size(logs: &[NginxLog], path: &str) -> Option<u64> {
+    let largest = logs.iter().filter(|log| file(log) == path && log.status == 200).map(|log| log.body_bytes_sent).max();
+    logs.iter().filter(|log| file(log) == path).find_map(total_size).or(largest)
+}
+
+// A full response counts as complete when at least `threshold` of the file was sent
+pub fn downloads(logs: &[NginxLog], path: &str, size: u64, threshold: f64) -> BTreeMap<NaiveDate, DailyDownloads> {
+    let mut days: BTreeMap<NaiveDate, DailyDownloads> = BTreeMap::new();
+    for log in logs.iter().filter(|log| file(log) == path && (log.status == 200 || log.status == PARTIAL_CONTENT)) {
+        let day = days.entry(log.request_time.date_naive()).or_default();
+        match log.status {
+            200 if log.body_bytes_sent as f64 >= size as f64 * threshold => day.complete += 1,
+            200 => day.aborted += 1,
+            _ => {
+                day.ranges += 1;
+                day.range_bytes += log.body_bytes_sent;
+            }
+        }
+    }
+    days
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use crate::digest::path_template;
+use crate::NginxLog;
+
+pub const BURSTS: [u32; 5] = [5, 10, 20, 50, 100];
+
+pub struct Recommendation {
+    pub rate: f64,
+    pub affected: usize,
+    pub clients: usize,
+}
+
+pub fn rejections(times: &[i64], rate: f64, burst: u32) -> usize {
+    let mut rejected = 0;
+    let mut excess = 0.0;
+    let mut last: Option<i64> = None;
+    for &time in times {
+        if let Some(last) = last {
+            let candidate = (excess - rate * (time - last) as f64).max(0.0) + 1.0;
+            if candidate > burst as f64 {
+                rejected += 1;
+                continue;
+            }
+            excess = candidate;
+        }
+        last = Some(time);
+    }
+    rejected
+}
+
+fn required_rate(times: &[i64], burst: u32) -> f64 {
+    if rejections(times, 0.0, burst) == 0 {
+        return 0.0;
+    }
+    let (mut low, mut high) = (0.0, times.len() as f64 + 1.0);
+    for _ in 0..40 {
+        let middle = (low + high) / 2.0;
+        if rejections(times, middle, burst) == 0 {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    high
+}
+
+pub fn client_streams<'a, I: Iterator<Item = &'a NginxLog>>(logs: I) -> Vec<Vec<i64>> {
+    let mut streams: HashMap<&str, Vec<i64>> = HashMap::new();
+    for log in logs {
+        streams.entry(log.remote_addr.as_str()).or_default().push(log.request_time.timestamp());
+    }
+    streams
+        .into_values()
+        .map(|mut times| {
+            times.sort_unstable();
+            times
+        })
+        .collect()
+}
+
+pub fn recommend(streams: &[Vec<i64>], burst: u32, affect_percent: f64) -> Recommendation {
+    let mut rates: Vec<f64> = streams.iter().map(|times| required_rate(times, burst)).collect();
+    rates.sort_by(|a, b| a.total_cmp(b));
+    let allowed = ((rates.len() as f64) * (1.0 - affect_percent / 100.0)).ceil() as usize;
+    let rate = match allowed {
+        0 => 1.0 / 60.0,
+        _ => rates[allowed.min(rates.len()) - 1].max(1.0 / 60.0),
+    };
+    let rate = if rate < 1.0 { (rate * 60.0).ceil() / 60.0 } else { rate.ceil() };
+    let affected = streams.iter().filter(|times| rejections(times, rate, burst) > 0).count();
+    Recommendation { rate, affected, clients: streams.len() }
+}
+
+pub fn top_paths(logs: &[NginxLog], top: usize) -> Vec<(String, Vec<&NginxLog>)> {
+    let mut paths: HashMap<String, Vec<&NginxLog>> = HashMap::new();
+    for log in logs {
+        paths.entry(path_template(log.path().unwrap_or("-"))).or_default().push(log);
+    }
+    let mut paths: Vec<(String, Vec<&NginxLog>)> = paths.into_iter().collect();
+    paths.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+    paths.truncate(top);
+    paths
+}
+
+pub fn nginx_rate(rate: f64) -> String {
+    if rate < 1.0 {
+        format!("{}r/m", (rate * 60.0).round())
+    } else {
+        format!("{}r/s", rate.round())
+    }
+}
+
+pub fn zone_name(path: &str) -> String {
+    let name: String = path.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect();
+    match name.trim_end_matches('_') {
+        "" => String::from("path_root"),
+        name => format!("path{}", name),
+    }
+}
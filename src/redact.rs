@@ -0,0 +1,87 @@
+use crate::cve::percent_decode;
+use crate::NginxLog;
+
+const MASK: &str = "REDACTED";
+const URL_FIELDS: [&str; 4] = ["request_uri", "uri", "args", "query_string"];
+// Kept by --normalize-paths, --ignore-query and --merge-pages, which run first
+const RAW_REQUEST_FIELD: &str = "raw_request";
+
+pub struct Redactor {
+    params: Vec<String>,
+    segments: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new<'a>(params: impl Iterator<Item = &'a str>, segments: impl Iterator<Item = &'a str>) -> Option<Redactor> {
+        let redactor = Redactor { params: names(params), segments: names(segments) };
+        if redactor.params.is_empty() && redactor.segments.is_empty() {
+            None
+        } else {
+            Some(redactor)
+        }
+    }
+
+    pub fn apply(&self, log: &mut NginxLog) {
+        log.request = self.request(&log.request);
+        if let Some(raw) = log.extra.get_mut(RAW_REQUEST_FIELD) {
+            *raw = self.request(raw);
+        }
+        log.http_referer = requote(&log.http_referer, self.url(log.http_referer.trim_matches('"')));
+        for field in URL_FIELDS {
+            if let Some(value) = log.extra.get_mut(field) {
+                *value = if field == "args" || field == "query_string" { self.query(value) } else { self.url(value) };
+            }
+        }
+    }
+
+    fn request(&self, request: &str) -> String {
+        let mut parts: Vec<String> = request.trim_matches('"').split(' ').map(String::from).collect();
+        if let Some(target) = parts.get_mut(1) {
+            *target = self.url(target);
+        }
+        requote(request, parts.join(" "))
+    }
+
+    fn url(&self, url: &str) -> String {
+        match url.split_once('?') {
+            Some((path, query)) => format!("{}?{}", self.path(path), self.query(query)),
+            None => self.path(url),
+        }
+    }
+
+    fn path(&self, path: &str) -> String {
+        if self.segments.is_empty() {
+            return path.to_string();
+        }
+        let mut redacted = Vec::new();
+        let mut mask_next = false;
+        for segment in path.split('/') {
+            redacted.push(if mask_next && !segment.is_empty() { MASK } else { segment });
+            mask_next = self.segments.contains(&percent_decode(segment).to_lowercase());
+        }
+        redacted.join("/")
+    }
+
+    fn query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.params.contains(&percent_decode(key).to_lowercase()) => format!("{}={}", key, MASK),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+fn names<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    values.flat_map(|value| value.split(',')).map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()).collect()
+}
+
+fn requote(original: &str, value: String) -> String {
+    if original.starts_with('"') {
+        format!("\"{}\"", value)
+    } else {
+        value
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::NginxLog;
+
+// Lets the target's own logs tell replayed traffic apart
+pub const REPLAY_HEADER: (&str, &str) = ("X-Replayed-By", "nginx-log-explorer");
+
+pub struct Replayer {
+    target: String,
+    agent: ureq::Agent,
+    rate: f64,
+    methods: Vec<String>,
+    max_requests: Option<usize>,
+}
+
+pub struct Mismatch {
+    pub method: String,
+    pub path: String,
+    pub original: u16,
+    pub replayed: Result<u16, String>,
+}
+
+#[derive(Default)]
+pub struct ReplayReport {
+    pub sent: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub statuses: BTreeMap<u16, u64>,
+    pub mismatches: Vec<Mismatch>,
+    pub elapsed: Duration,
+}
+
+impl Replayer {
+    pub fn new(target: &str, timeout: Duration) -> Result<Replayer, String> {
+        if !target.starts_with("http://") && !target.starts_with("https://") {
+            return Err(format!("invalid replay target: {} (expected an http:// or https:// URL)", target));
+        }
+        Ok(Replayer {
+            target: target.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(timeout).redirects(0).build(),
+            rate: 10.0,
+            methods: vec![String::from("GET"), String::from("HEAD")],
+            max_requests: Some(1000),
+        })
+    }
+
+    pub fn rate(mut self, rate: f64) -> Replayer {
+        self.rate = rate;
+        self
+    }
+
+    pub fn methods(mut self, methods: &[&str]) -> Replayer {
+        self.methods = methods.iter().map(|method| method.to_ascii_uppercase()).collect();
+        self
+    }
+
+    pub fn max_requests(mut self, max_requests: Option<usize>) -> Replayer {
+        self.max_requests = max_requests;
+        self
+    }
+
+    pub fn replay(&self, logs: &[NginxLog]) -> ReplayReport {
+        let mut report = ReplayReport::default();
+        let started = Instant::now();
+        for log in logs {
+            if self.max_requests.is_some_and(|max| report.sent >= max) {
+                break;
+            }
+            let method = match log.method().map(str::to_ascii_uppercase) {
+                Some(method) if self.methods.contains(&method) => method,
+                _ => {
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+            let path = match log.path() {
+                Some(path) if path.starts_with('/') => path,
+                _ => {
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+            let due = started + Duration::from_secs_f64(report.sent as f64 / self.rate);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            let mut request = self.agent.request(&method, &format!("{}{}", self.target, path)).set(REPLAY_HEADER.0, REPLAY_HEADER.1);
+            let user_agent = log.http_user_agent.trim_matches('"');
+            if !user_agent.is_empty() && user_agent != "-" {
+                request = request.set("User-Agent", user_agent);
+            }
+            let replayed = match request.call() {
+                Ok(response) => Ok(response.status()),
+                Err(ureq::Error::Status(status, _)) => Ok(status),
+                Err(error) => Err(error.to_string()),
+            };
+            report.sent += 1;
+            match replayed {
+                Ok(status) => *report.statuses.entry(status).or_insert(0) += 1,
+                Err(_) => report.errors += 1,
+            }
+            if replayed.as_ref().ok() != Some(&log.status) {
+                report.mismatches.push(Mismatch { method, path: path.to_string(), original: log.status, replayed });
+            }
+        }
+        report.elapsed = started.elapsed();
+        report
+    }
+}
+
+pub fn parse_rate(spec: &str) -> Result<f64, String> {
+    let number = spec.strip_suffix("rps").or_else(|| spec.strip_suffix("/s")).unwrap_or(spec);
+    match number.trim().parse::<f64>() {
+        Ok(rate) if rate > 0.0 => Ok(rate),
+        _ => Err(format!("invalid rate: {} (expected requests per second, e.g. 50rps)", spec)),
+    }
+}
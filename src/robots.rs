@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fs;
+
+use crate::in_path;
+
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+pub struct Robots {
+    groups: Vec<Group>,
+}
+
+impl Robots {
+    pub fn load(source: &str) -> Result<Robots, Box<dyn Error>> {
+        let text = if source.starts_with("http://") || source.starts_with("https://") {
+            ureq::get(source).call().map_err(|e| format!("{}: {}", source, e))?.into_string()?
+        } else {
+            fs::read_to_string(source).map_err(|e| in_path(source, e))?
+        };
+        Ok(Robots::parse(&text))
+    }
+
+    pub fn parse(text: &str) -> Robots {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push(Group { agents: Vec::new(), rules: Vec::new() });
+                        in_agents = true;
+                    }
+                    groups.last_mut().unwrap().agents.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    if let Some(group) = groups.last_mut() {
+                        if !value.is_empty() {
+                            group.rules.push(Rule { allow: key == "allow", pattern: value.to_string() });
+                        }
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        Robots { groups }
+    }
+
+    fn group_for(&self, user_agent: &str) -> Option<&Group> {
+        let user_agent = user_agent.to_ascii_lowercase();
+        let specific = self
+            .groups
+            .iter()
+            .flat_map(|group| group.agents.iter().map(move |agent| (agent, group)))
+            .filter(|(agent, _)| agent.as_str() != "*" && user_agent.contains(agent.as_str()))
+            .max_by_key(|(agent, _)| agent.len())
+            .map(|(_, group)| group);
+        specific.or_else(|| self.groups.iter().find(|group| group.agents.iter().any(|agent| agent == "*")))
+    }
+
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        let group = match self.group_for(user_agent) {
+            Some(group) => group,
+            None => return true,
+        };
+        group
+            .rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        let last = index == parts.len() - 1;
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::interval::{Alignment, Interval};
+use crate::{in_path, NginxLog};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub requests: u64,
+    pub bytes: u64,
+    pub statuses: BTreeMap<u16, u64>,
+    pub paths: BTreeMap<String, u64>,
+}
+
+impl Summary {
+    fn add(&mut self, log: &NginxLog) {
+        self.requests += 1;
+        self.bytes += log.body_bytes_sent;
+        *self.statuses.entry(log.status).or_default() += 1;
+        *self.paths.entry(log.path().unwrap_or("-").to_string()).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: &Summary) {
+        self.requests += other.requests;
+        self.bytes += other.bytes;
+        for (status, count) in &other.statuses {
+            *self.statuses.entry(*status).or_default() += count;
+        }
+        for (path, count) in &other.paths {
+            *self.paths.entry(path.clone()).or_default() += count;
+        }
+    }
+
+    pub fn errors(&self, class: u16) -> u64 {
+        self.statuses.iter().filter(|(status, _)| *status / 100 == class).map(|(_, count)| count).sum()
+    }
+
+    pub fn top_path(&self) -> Option<(&str, u64)> {
+        self.paths.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0))).map(|(path, count)| (path.as_str(), *count))
+    }
+
+    fn keep_top_paths(&mut self, top: usize) {
+        if self.paths.len() > top {
+            let mut paths: Vec<(String, u64)> = std::mem::take(&mut self.paths).into_iter().collect();
+            paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            paths.truncate(top);
+            self.paths = paths.into_iter().collect();
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Rollup {
+    pub last_entry: Option<DateTime<Local>>,
+    pub last_entry_count: usize,
+    pub hourly: BTreeMap<DateTime<Local>, Summary>,
+    pub daily: BTreeMap<DateTime<Local>, Summary>,
+}
+
+pub struct Update {
+    pub added: usize,
+    pub skipped: usize,
+}
+
+impl Rollup {
+    pub fn load_or_default(path: &str) -> Result<Rollup, Box<dyn Error>> {
+        if !Path::new(path).exists() {
+            return Ok(Rollup::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| in_path(path, e))?;
+        Ok(serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, serde_json::to_string_pretty(self)?).map_err(|e| in_path(&temporary, e))?;
+        fs::rename(&temporary, path).map_err(|e| in_path(path, e))?;
+        Ok(())
+    }
+
+    pub fn update(&mut self, logs: &[NginxLog], top_paths: usize) -> Update {
+        let mut entries: Vec<&NginxLog> = logs.iter().collect();
+        entries.sort_by_key(|log| log.request_time);
+        let mut seen_at_last = 0;
+        let mut new: BTreeMap<(bool, DateTime<Local>), Summary> = BTreeMap::new();
+        let mut update = Update { added: 0, skipped: 0 };
+        for log in &entries {
+            let time = log.request_time;
+            let counted = match self.last_entry {
+                Some(last) if time < last => true,
+                Some(last) if time == last => {
+                    seen_at_last += 1;
+                    seen_at_last <= self.last_entry_count
+                }
+                _ => false,
+            };
+            if counted {
+                update.skipped += 1;
+                continue;
+            }
+            update.added += 1;
+            for (daily, interval) in [(false, Interval::Seconds(3600)), (true, Interval::Days(1))] {
+                new.entry((daily, interval.bucket_start(time, Alignment::Calendar, time))).or_default().add(log);
+            }
+        }
+        for ((daily, start), summary) in new {
+            let buckets = if daily { &mut self.daily } else { &mut self.hourly };
+            let bucket = buckets.entry(start).or_default();
+            bucket.merge(&summary);
+            bucket.keep_top_paths(top_paths);
+        }
+        if let Some(last) = entries.last().map(|log| log.request_time).filter(|last| self.last_entry.is_none_or(|previous| *last >= previous)) {
+            let count = entries.iter().filter(|log| log.request_time == last).count();
+            self.last_entry_count = if self.last_entry == Some(last) { count.max(self.last_entry_count) } else { count };
+            self.last_entry = Some(last);
+        }
+        update
+    }
+}
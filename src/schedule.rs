@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fs;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+use serde_json::json;
+
+use crate::config::{ScheduledReport, Schedule};
+use crate::interval::Interval;
+use crate::{in_path, mail, notify, summary, NginxLog};
+
+// Enough to find the next match of any valid expression, including 29 February
+const SEARCH_LIMIT_DAYS: i64 = 366 * 4;
+
+// A five-field cron expression: minute, hour, day of month, month, day of week (0 or 7 is Sunday)
+pub struct Cron {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(spec: &str) -> Result<Cron, String> {
+        let expanded = match spec.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("invalid cron expression: {} (expected minute hour day month weekday, e.g. \"0 7 * * *\")", spec));
+        }
+        let mut weekdays = field(fields[4], 0, 7).map_err(|e| format!("{} in {}", e, spec))?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(Cron {
+            minutes: field(fields[0], 0, 59).map_err(|e| format!("{} in {}", e, spec))?,
+            hours: field(fields[1], 0, 23).map_err(|e| format!("{} in {}", e, spec))?,
+            days: field(fields[2], 1, 31).map_err(|e| format!("{} in {}", e, spec))?,
+            months: field(fields[3], 1, 12).map_err(|e| format!("{} in {}", e, spec))?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    // As in cron, a restricted day of month and day of week match if either does
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let day = self.days[time.day() as usize];
+        let weekday = self.weekdays[time.weekday().num_days_from_sunday() as usize];
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(SEARCH_LIMIT_DAYS);
+        let mut time = start;
+        while time < limit {
+            if !self.months[time.month() as usize] || !self.matches_day(&time) {
+                time = (time.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if !self.hours[time.hour() as usize] {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.minutes[time.minute() as usize] {
+                time += Duration::minutes(1);
+            } else {
+                // a time skipped by a daylight saving change never happens
+                match Local.from_local_datetime(&time).earliest() {
+                    Some(local) if local > after => return Some(local),
+                    _ => time += Duration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+// Indexes min..=max set for each "*", "5", "1-5", "*/15", "0-30/10" or comma-separated list of these
+fn field(spec: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut set = vec![false; max as usize + 1];
+    for item in spec.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(|| format!("invalid step: {}", item))?),
+            None => (item, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().map_err(|_| format!("invalid range: {}", item))?, to.parse().map_err(|_| format!("invalid range: {}", item))?),
+                None => {
+                    let value = range.parse().map_err(|_| format!("invalid value: {}", item))?;
+                    (value, if item.contains('/') { max } else { value })
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(format!("{} is outside {}-{}", item, min, max));
+        }
+        for value in (from..=to).step_by(step as usize) {
+            set[value as usize] = true;
+        }
+    }
+    Ok(set)
+}
+
+pub struct Job<'a> {
+    pub name: &'a str,
+    pub cron: Cron,
+    pub window: Duration,
+    schedule: &'a Schedule,
+}
+
+impl<'a> Job<'a> {
+    pub fn from_config(name: &'a str, schedule: &'a Schedule) -> Result<Job<'a>, String> {
+        let context = |e: String| format!("schedules.{}: {}", name, e);
+        let window = Interval::parse(&schedule.window).map_err(context)?.duration().ok_or_else(|| context(String::from("window must be in seconds, minutes, hours, days or weeks")))?;
+        if schedule.path.is_none() && schedule.email.is_empty() && schedule.webhook.is_none() {
+            return Err(context(String::from("no sink; set path, email or webhook")));
+        }
+        if !schedule.email.is_empty() && (schedule.smtp.is_none() || schedule.from.is_none()) {
+            return Err(context(String::from("email needs smtp and from")));
+        }
+        Ok(Job { name, cron: Cron::parse(&schedule.cron).map_err(context)?, window, schedule })
+    }
+
+    // Builds the report over the window ending at `at` and hands it to every sink
+    pub fn run(&self, logs: &[NginxLog], at: DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let logs: Vec<NginxLog> = logs.iter().filter(|log| log.request_time > at - self.window && log.request_time <= at).cloned().collect();
+        let schedule = self.schedule;
+        let (title, sections, digest) = match schedule.report {
+            ScheduledReport::Summary => (summary::title(&logs), summary::traffic_sections(&logs, schedule.top, None), None),
+            ScheduledReport::Digest => {
+                let digest = notify::digest(&logs, if self.window >= Duration::days(7) { "week" } else { "day" }, schedule.top);
+                (digest.title.clone(), notify::sections(&digest), Some(digest))
+            }
+        };
+        let markdown = summary::render_markdown(&title, &sections);
+        if let Some(path) = &schedule.path {
+            let path = path.replace("{name}", self.name).replace("{date}", &at.format("%Y-%m-%d").to_string()).replace("{time}", &at.format("%Y-%m-%dT%H%M").to_string());
+            let body = if path.ends_with(".html") { summary::render_html(&title, &sections) } else { markdown.clone() };
+            fs::write(&path, body).map_err(|e| in_path(&path, e))?;
+        }
+        if !schedule.email.is_empty() {
+            let report = mail::Report {
+                from: schedule.from.as_deref().unwrap_or_default(),
+                to: schedule.email.iter().map(String::as_str).collect(),
+                subject: title.clone(),
+                text: markdown.clone(),
+                html: summary::render_html(&title, &sections),
+            };
+            mail::send(schedule.smtp.as_deref().unwrap_or_default(), report)?;
+        }
+        if let Some(webhook) = &schedule.webhook {
+            let discord = webhook.contains("discord.com/") || webhook.contains("discordapp.com/");
+            let payload = match (&digest, discord) {
+                (Some(digest), true) => notify::discord_payload(digest),
+                (Some(digest), false) => notify::slack_payload(digest),
+                (None, true) => json!({ "content": markdown.chars().take(2000).collect::<String>() }),
+                (None, false) => json!({ "text": markdown }),
+            };
+            notify::post(webhook, &payload)?;
+        }
+        Ok(())
+    }
+}
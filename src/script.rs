@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fs;
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::{in_path, NginxLog};
+
+pub struct Script {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    has_filter: bool,
+    has_fields: bool,
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Script, Box<dyn Error>> {
+        let source = fs::read_to_string(path).map_err(|e| in_path(path, e))?;
+        let engine = Engine::new();
+        let ast = engine.compile(&source).map_err(|e| format!("{}: {}", path, e))?;
+        let defines = |name: &str| ast.iter_functions().any(|function| function.name == name && function.params.len() == 1);
+        let (has_filter, has_fields) = (defines("filter"), defines("fields"));
+        if !has_filter && !has_fields {
+            return Err(format!("{}: script must define fn filter(entry) or fn fields(entry)", path).into());
+        }
+        Ok(Script { path: path.to_string(), engine, ast, has_filter, has_fields })
+    }
+
+    fn entry(log: &NginxLog) -> Map {
+        let mut entry = Map::new();
+        for (name, value) in &log.extra {
+            entry.insert(name.as_str().into(), value.clone().into());
+        }
+        let text = |value: Option<&str>| value.map(|value| Dynamic::from(value.to_string())).unwrap_or(Dynamic::UNIT);
+        entry.insert("remote_addr".into(), log.remote_addr.clone().into());
+        entry.insert("remote_user".into(), log.remote_user.clone().into());
+        entry.insert("time".into(), log.request_time.timestamp().into());
+        entry.insert("request".into(), log.request.trim_matches('"').to_string().into());
+        entry.insert("method".into(), text(log.method()));
+        entry.insert("path".into(), text(log.path()));
+        entry.insert("query".into(), text(log.query()));
+        entry.insert("status".into(), (log.status as i64).into());
+        entry.insert("body_bytes_sent".into(), (log.body_bytes_sent as i64).into());
+        entry.insert("referer".into(), log.http_referer.trim_matches('"').to_string().into());
+        entry.insert("user_agent".into(), log.http_user_agent.trim_matches('"').to_string().into());
+        entry.insert("request_id".into(), text(log.request_id.as_deref()));
+        entry
+    }
+
+    pub fn describe(&self) -> String {
+        let functions: Vec<&str> = [(self.has_fields, "fields"), (self.has_filter, "filter")].iter().filter(|(defined, _)| *defined).map(|(_, name)| *name).collect();
+        format!("{} ({})", self.path, functions.join(", "))
+    }
+
+    pub fn apply(&self, log: &mut NginxLog) -> Result<bool, Box<dyn Error>> {
+        let mut scope = Scope::new();
+        if self.has_fields {
+            let fields: Dynamic = self.engine.call_fn(&mut scope, &self.ast, "fields", (Script::entry(log),)).map_err(|e| format!("{}: fields: {}", self.path, e))?;
+            if let Some(fields) = fields.try_cast::<Map>() {
+                for (name, value) in fields {
+                    if !value.is_unit() {
+                        log.extra.insert(name.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+        if !self.has_filter {
+            return Ok(true);
+        }
+        let keep = self.engine.call_fn::<bool>(&mut scope, &self.ast, "filter", (Script::entry(log),)).map_err(|e| format!("{}: filter: {}", self.path, e))?;
+        Ok(keep)
+    }
+}
@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread;
+
+use chrono::{DateTime, Duration, Local};
+use log::{debug, info};
+use serde_json::{json, Value};
+
+use crate::aggregate::{GroupField, SpillingCounter};
+use crate::anonymize::{Anonymizer, ADDRESS_FIELDS};
+use crate::config::ApiToken;
+use crate::cve::percent_decode;
+use crate::health::Health;
+use crate::interval::{trend_analysis, Alignment, Interval};
+use crate::time_display::TimeDisplay;
+use crate::{logs_json, parse_date_arg, summary, NginxLog};
+
+const WORKERS: usize = 4;
+// Single page calling the endpoints below, compiled into the binary
+const UI: &str = include_str!("../ui/index.html");
+
+type ApiError = (u16, String);
+
+// Restrictions of one [api_tokens.NAME] entry from the config file
+pub struct Access {
+    name: String,
+    token: String,
+    lookback: Option<Duration>,
+    anonymizer: Option<Anonymizer>,
+    hide_fields: Vec<String>,
+}
+
+impl Access {
+    pub fn from_config(name: &str, config: &ApiToken) -> Result<Access, String> {
+        if config.token.len() < 16 {
+            return Err(format!("api_tokens.{}: token must be at least 16 characters", name));
+        }
+        let lookback = match config.lookback.as_deref() {
+            Some(spec) => Some(Interval::parse(spec)?.duration().ok_or_else(|| format!("api_tokens.{}: lookback must be in seconds, minutes, hours, days or weeks", name))?),
+            None => None,
+        };
+        Ok(Access {
+            name: name.to_string(),
+            token: config.token.clone(),
+            lookback,
+            anonymizer: config.anonymize.as_deref().map(|mode| Anonymizer::parse(mode, config.anonymize_salt.as_deref())).transpose().map_err(|e| format!("api_tokens.{}: {}", name, e))?,
+            hide_fields: config.hide_fields.clone(),
+        })
+    }
+
+    fn restricts_entries(&self) -> bool {
+        self.anonymizer.is_some() || !self.hide_fields.is_empty()
+    }
+
+    fn restrict(&self, log: &mut NginxLog) {
+        if let Some(anonymizer) = &self.anonymizer {
+            anonymizer.apply(log);
+        }
+        for field in &self.hide_fields {
+            match field.as_str() {
+                // The forwarded addresses and the known-IP label give the client away too
+                "ip" | "remote_addr" => {
+                    log.remote_addr = String::from("-");
+                    log.known_as = None;
+                    for field in ADDRESS_FIELDS {
+                        log.extra.remove(field);
+                    }
+                }
+                "remote_user" => log.remote_user = String::from("-"),
+                "http_referer" | "referer" => log.http_referer = String::from("-"),
+                "http_user_agent" | "user_agent" => log.http_user_agent = String::from("-"),
+                "request_id" => log.request_id = None,
+                _ => {
+                    log.extra.remove(field);
+                }
+            }
+        }
+    }
+}
+
+// Entries are kept sorted by time, so start and end narrow them with a binary search
+pub struct Api<'a> {
+    logs: Vec<NginxLog>,
+    display: &'a TimeDisplay,
+    // Without tokens every request sees everything
+    tokens: Vec<Access>,
+    health: Health,
+}
+
+impl<'a> Api<'a> {
+    pub fn new(mut logs: Vec<NginxLog>, display: &'a TimeDisplay, tokens: Vec<Access>, health: Health) -> Api<'a> {
+        logs.sort_by_key(|log| log.request_time);
+        Api { logs, display, tokens, health }
+    }
+
+    fn authorize(&self, authorization: Option<&str>) -> Result<Option<&Access>, ApiError> {
+        if self.tokens.is_empty() {
+            return Ok(None);
+        }
+        let token = authorization.and_then(|value| value.strip_prefix("Bearer ")).ok_or((401, String::from("missing bearer token")))?;
+        match self.tokens.iter().find(|access| constant_time_eq(access.token.as_bytes(), token.trim().as_bytes())) {
+            Some(access) => {
+                debug!("stage=serve token={}", access.name);
+                Ok(Some(access))
+            }
+            None => Err((401, String::from("invalid token"))),
+        }
+    }
+
+    pub fn handle(&self, method: &str, url: &str, authorization: Option<&str>) -> Result<Value, ApiError> {
+        if method != "GET" {
+            return Err((405, format!("method not allowed: {}", method)));
+        }
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        // Load balancers and monitoring probe this without a token
+        if path == "/healthz" {
+            return Ok(self.health.json());
+        }
+        let access = self.authorize(authorization)?;
+        let params: HashMap<String, String> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .map(|(name, value)| (percent_decode(name), percent_decode(&value.replace('+', " "))))
+            .collect();
+        match path {
+            "/query" => self.query(&params, access),
+            "/top" => self.top(&params, access),
+            "/trend" => self.trend(&params, access),
+            "/summary" => self.summary(&params, access),
+            _ => Err((404, format!("unknown endpoint: {} (expected /query, /top, /trend, /summary or /healthz)", path))),
+        }
+    }
+
+    fn select(&self, params: &HashMap<String, String>, access: Option<&Access>) -> Result<Cow<'_, [NginxLog]>, ApiError> {
+        let time = |name: &str| params.get(name).map(|value| parse_date_arg(value).map_err(|e| (400, e.to_string()))).transpose();
+        let (mut start, end): (Option<DateTime<Local>>, Option<DateTime<Local>>) = (time("start")?, time("end")?);
+        if let Some(lookback) = access.and_then(|access| access.lookback) {
+            start = start.max(Some(Local::now() - lookback));
+        }
+        let from = start.map(|start| self.logs.partition_point(|log| log.request_time < start)).unwrap_or(0);
+        let to = end.map(|end| self.logs.partition_point(|log| log.request_time <= end)).unwrap_or(self.logs.len());
+        let logs = &self.logs[from..to.max(from)];
+        let status = params.get("status").map(|status| status.parse::<u16>().map_err(|_| (400, format!("invalid status: {}", status)))).transpose()?;
+        let (ip, path) = (params.get("ip"), params.get("path"));
+        let restricted = access.filter(|access| access.restricts_entries());
+        if status.is_none() && ip.is_none() && path.is_none() && restricted.is_none() {
+            return Ok(Cow::Borrowed(logs));
+        }
+        // Filtering on ip happens after masking, so a restricted token cannot probe for full addresses
+        let mut selected: Vec<NginxLog> = logs.iter().filter(|log| status.is_none_or(|status| log.status == status)).filter(|log| path.is_none_or(|path| log.path().is_some_and(|logged| logged.contains(path.as_str())))).cloned().collect();
+        if let Some(access) = restricted {
+            selected.iter_mut().for_each(|log| access.restrict(log));
+        }
+        if let Some(ip) = ip {
+            selected.retain(|log| log.remote_addr == *ip);
+        }
+        Ok(Cow::Owned(selected))
+    }
+
+    fn number(params: &HashMap<String, String>, name: &str, default: usize) -> Result<usize, ApiError> {
+        match params.get(name) {
+            Some(value) => value.parse().map_err(|_| (400, format!("invalid {}: {}", name, value))),
+            None => Ok(default),
+        }
+    }
+
+    fn query(&self, params: &HashMap<String, String>, access: Option<&Access>) -> Result<Value, ApiError> {
+        let logs = self.select(params, access)?;
+        let limit = Api::number(params, "limit", 100)?;
+        let entries = logs_json(&logs[..limit.min(logs.len())], self.display).map_err(|e| (500, e.to_string()))?;
+        Ok(json!({ "total": logs.len(), "entries": entries }))
+    }
+
+    fn top(&self, params: &HashMap<String, String>, access: Option<&Access>) -> Result<Value, ApiError> {
+        let logs = self.select(params, access)?;
+        let field = GroupField::parse(params.get("by").map(String::as_str).unwrap_or("path")).map_err(|e| (400, e))?;
+        let mut counter = SpillingCounter::new(None);
+        for log in logs.iter() {
+            counter.add(field.key(log), log.body_bytes_sent).map_err(|e| (500, e.to_string()))?;
+        }
+        let top = counter.top(Api::number(params, "top", 20)?).map_err(|e| (500, e.to_string()))?;
+        Ok(top.iter().map(|(value, totals)| json!({ "value": value, "requests": totals.requests, "body_bytes_sent": totals.bytes })).collect())
+    }
+
+    fn trend(&self, params: &HashMap<String, String>, access: Option<&Access>) -> Result<Value, ApiError> {
+        let logs = self.select(params, access)?;
+        let interval = Interval::parse(params.get("interval").map(String::as_str).unwrap_or("1h")).map_err(|e| (400, e))?;
+        let align = if params.get("align").map(String::as_str) == Some("first") { Alignment::First } else { Alignment::Calendar };
+        Ok(trend_analysis(&logs, interval, align).into_iter().map(|(start, count)| json!({ "timestamp": self.display.json(start), "requests": count })).collect())
+    }
+
+    fn summary(&self, params: &HashMap<String, String>, access: Option<&Access>) -> Result<Value, ApiError> {
+        let logs = self.select(params, access)?;
+        let sections: Vec<Value> = summary::traffic_sections(&logs, Api::number(params, "top", 10)?, None)
+            .iter()
+            .map(|section| {
+                let rows: Vec<Value> = section.rows.iter().map(|row| section.headers.iter().zip(row).map(|(header, cell)| (header.to_string(), Value::from(cell.as_str()))).collect()).collect();
+                json!({ "title": section.title, "rows": rows })
+            })
+            .collect();
+        Ok(json!({ "title": summary::title(&logs), "sections": sections }))
+    }
+}
+
+fn authorization(request: &tiny_http::Request) -> Option<String> {
+    request.headers().iter().find(|header| header.field.equiv("Authorization")).map(|header| header.value.as_str().to_string())
+}
+
+// ":8080" listens on every interface
+pub fn listen_address(spec: &str) -> String {
+    if spec.starts_with(':') {
+        format!("0.0.0.0{}", spec)
+    } else {
+        spec.to_string()
+    }
+}
+
+// Does not stop at the first differing byte, so response times don't reveal how much of a guess was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub fn serve(address: &str, api: &Api) -> Result<(), Box<dyn Error>> {
+    let server = tiny_http::Server::http(address).map_err(|e| format!("{}: {}", address, e))?;
+    info!("stage=serve address={} entries={}", address, api.logs.len());
+    eprintln!("Serving {} entries on http://{}", api.logs.len(), address);
+    thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    let (status, content_type, body) = match request.url() {
+                        "/" | "/index.html" => (200, "text/html; charset=utf-8", UI.to_string()),
+                        url => match api.handle(request.method().as_str(), url, authorization(&request).as_deref()) {
+                            Ok(body) => (200, "application/json", body.to_string()),
+                            Err((status, message)) => (status, "application/json", json!({ "error": message }).to_string()),
+                        },
+                    };
+                    debug!("stage=serve method={} url={} status={}", request.method(), request.url(), status);
+                    let header = tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap();
+                    let response = tiny_http::Response::from_string(body).with_status_code(status).with_header(header);
+                    if let Err(e) = request.respond(response) {
+                        debug!("stage=serve reason={}", e);
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use crate::NginxLog;
+
+pub const DEFAULT_TIMEOUT: &str = "30m";
+
+pub fn sessions(logs: &[NginxLog], timeout: i64) -> Vec<Vec<&NginxLog>> {
+    let mut visitors: HashMap<(&str, &str), Vec<&NginxLog>> = HashMap::new();
+    for log in logs {
+        visitors.entry((log.remote_addr.as_str(), log.http_user_agent.as_str())).or_default().push(log);
+    }
+    let mut sessions = Vec::new();
+    for mut requests in visitors.into_values() {
+        requests.sort_by_key(|log| log.request_time);
+        let mut current: Vec<&NginxLog> = Vec::new();
+        for log in requests {
+            if let Some(last) = current.last() {
+                if (log.request_time - last.request_time).num_seconds() > timeout {
+                    sessions.push(std::mem::take(&mut current));
+                }
+            }
+            current.push(log);
+        }
+        if !current.is_empty() {
+            sessions.push(current);
+        }
+    }
+    sessions
+}
+
+pub fn parse_steps<'a, I: IntoIterator<Item = &'a str>>(args: I) -> Result<Vec<String>, String> {
+    let joined = args.into_iter().collect::<Vec<_>>().join(" ");
+    let steps: Vec<String> = joined.split("->").map(|step| step.trim().to_string()).collect();
+    if steps.len() < 2 || steps.iter().any(|step| step.is_empty()) {
+        return Err(format!("invalid funnel: {} (expected at least two steps like /cart -> /checkout)", joined));
+    }
+    Ok(steps)
+}
+
+fn matches_step(path: &str, step: &str) -> bool {
+    let path = path.split('?').next().unwrap_or(path);
+    path == step || path.strip_prefix(step).is_some_and(|rest| step.ends_with('/') || rest.starts_with('/'))
+}
+
+pub fn funnel(sessions: &[Vec<&NginxLog>], steps: &[String]) -> Vec<u64> {
+    let mut reached = vec![0; steps.len()];
+    for session in sessions {
+        let mut step = 0;
+        for log in session {
+            if step < steps.len() && matches_step(log.path().unwrap_or("-"), &steps[step]) {
+                reached[step] += 1;
+                step += 1;
+            }
+        }
+    }
+    reached
+}
+
+const STATIC_EXTENSIONS: [&str; 20] = [
+    "css", "js", "mjs", "map", "png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "ico", "woff", "woff2", "ttf", "eot", "otf", "mp4", "webm", "txt",
+];
+
+pub fn is_static_asset(path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or(path);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((_, extension)) => STATIC_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+pub struct EntryExit {
+    pub entries: HashMap<String, u64>,
+    pub exits: HashMap<String, u64>,
+}
+
+pub fn entry_exit(sessions: &[Vec<&NginxLog>], include_static: bool) -> EntryExit {
+    let mut report = EntryExit { entries: HashMap::new(), exits: HashMap::new() };
+    for session in sessions {
+        let pages: Vec<&str> = session
+            .iter()
+            .filter_map(|log| log.path())
+            .map(|path| path.split('?').next().unwrap_or(path))
+            .filter(|path| include_static || !is_static_asset(path))
+            .collect();
+        if let (Some(first), Some(last)) = (pages.first(), pages.last()) {
+            *report.entries.entry(first.to_string()).or_default() += 1;
+            *report.exits.entry(last.to_string()).or_default() += 1;
+        }
+    }
+    report
+}
@@ -0,0 +1,28 @@
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub fn sparkline(values: &[f64]) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|&value| {
+            if max <= 0.0 {
+                return BARS[0];
+            }
+            BARS[((value / max) * (BARS.len() - 1) as f64).round() as usize]
+        })
+        .collect()
+}
+
+const PARTIAL_BLOCKS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    if max <= 0.0 {
+        return String::new();
+    }
+    let eighths = (value / max * width as f64 * 8.0).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if eighths % 8 > 0 {
+        bar.push(PARTIAL_BLOCKS[eighths % 8 - 1]);
+    }
+    bar
+}
@@ -0,0 +1,94 @@
+use std::error::Error;
+
+use regex::Regex;
+
+use crate::NginxLog;
+
+// Column names and the rows, formatted as strings
+pub type QueryResult = (Vec<String>, Vec<Vec<String>>);
+
+// The literal in `time >= '2024-05-01'`, used to skip files whose entries all came before it.
+// Only a bound ANDed at the top level of the WHERE clause holds for every row: with a subquery,
+// OR, NOT or CASE anywhere, or the bound inside parentheses, nothing is pruned
+pub fn time_lower_bound(sql: &str) -> Option<String> {
+    // string literals blanked out, byte for byte, so their text is not read as SQL
+    let masked = Regex::new(r"'[^']*'").unwrap().replace_all(sql, |captures: &regex::Captures| format!("'{}'", "x".repeat(captures[0].len() - 2)));
+    if Regex::new(r"(?i)\bselect\b").unwrap().find_iter(&masked).count() > 1 || Regex::new(r"(?i)\b(or|not|case)\b").unwrap().is_match(&masked) {
+        return None;
+    }
+    let start = Regex::new(r"(?i)\bwhere\b").unwrap().find(&masked)?.end();
+    let end = Regex::new(r"(?i)\b(group|having|order|limit|window|qualify)\b").unwrap().find_at(&masked, start).map_or(masked.len(), |clause| clause.start());
+    let bound = Regex::new(r"(?i)\btime\s*>=?\s*(?:timestamp\s*)?'").unwrap();
+    bound.find_iter(&masked[..end]).filter(|found| found.start() >= start).find_map(|found| {
+        let depth = masked[start..found.start()].chars().fold(0, |depth, c| match c {
+            '(' => depth + 1,
+            ')' => depth - 1,
+            _ => depth,
+        });
+        if depth != 0 {
+            return None;
+        }
+        let literal = &sql[found.end()..];
+        literal.find('\'').map(|close| literal[..close].to_string()).filter(|literal| !literal.is_empty())
+    })
+}
+
+#[cfg(feature = "sql")]
+pub fn query(logs: &[NginxLog], sql: &str) -> Result<QueryResult, Box<dyn Error>> {
+    use std::sync::Arc;
+
+    use arrow::util::display::{ArrayFormatter, FormatOptions};
+    use datafusion::datasource::MemTable;
+    use datafusion::prelude::SessionContext;
+
+    let (schema, batches) = crate::ipc::record_batches(logs)?;
+    let context = SessionContext::new();
+    context.register_table("logs", Arc::new(MemTable::try_new(schema, vec![batches])?))?;
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let frame = runtime.block_on(context.sql(sql))?;
+    let columns = frame.schema().fields().iter().map(|field| field.name().clone()).collect();
+    let results = runtime.block_on(frame.collect())?;
+    let options = FormatOptions::default().with_null("-");
+    let mut rows = Vec::new();
+    for batch in &results {
+        let formatters = batch.columns().iter().map(|array| ArrayFormatter::try_new(array.as_ref(), &options)).collect::<Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            rows.push(formatters.iter().map(|formatter| formatter.value(row).to_string()).collect());
+        }
+    }
+    Ok((columns, rows))
+}
+
+#[cfg(not(feature = "sql"))]
+pub fn query(_logs: &[NginxLog], _sql: &str) -> Result<QueryResult, Box<dyn Error>> {
+    Err("sql: built without SQL support; rebuild with --features sql".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_bounds_are_found() {
+        assert_eq!(time_lower_bound("SELECT path FROM logs WHERE status = 500 AND time >= '2024-05-01' GROUP BY 1").as_deref(), Some("2024-05-01"));
+        assert_eq!(time_lower_bound("select * from logs where time > timestamp '2024-05-01T00:00:00'").as_deref(), Some("2024-05-01T00:00:00"));
+    }
+
+    #[test]
+    fn timestamps_without_an_offset_prune_from_their_date() {
+        let day = |date: &str| crate::parse_date_arg(date).unwrap() - chrono::Duration::days(1);
+        assert_eq!(crate::sql_since("SELECT * FROM logs WHERE time > timestamp '2024-05-01T00:00:00'"), Some(day("2024-05-01")));
+        assert_eq!(crate::sql_since("SELECT * FROM logs WHERE time >= '2024-05-01 10:00:00'"), Some(day("2024-05-01")));
+        assert_eq!(crate::sql_since("SELECT * FROM logs WHERE time >= 'yesterday'"), None);
+    }
+
+    #[test]
+    fn bounds_that_may_not_hold_for_every_row_are_ignored() {
+        assert_eq!(time_lower_bound("SELECT * FROM logs WHERE NOT (time >= '2024-05-01')"), None);
+        assert_eq!(time_lower_bound("SELECT * FROM logs WHERE status = 500 OR time >= '2024-05-01'"), None);
+        assert_eq!(time_lower_bound("SELECT * FROM logs WHERE path IN (SELECT path FROM logs WHERE time >= '2024-05-01')"), None);
+        assert_eq!(time_lower_bound("SELECT * FROM logs WHERE (time >= '2024-05-01' AND status = 500)"), None);
+        assert_eq!(time_lower_bound("SELECT * FROM logs WHERE path = 'time >= ''2024-05-01'''"), None);
+        assert_eq!(time_lower_bound("SELECT count(*) FROM logs GROUP BY path HAVING min(time) >= '2024-05-01'"), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::digest::error_digest;
+use crate::geo::{geo_counts, GeoDb, GeoLevel};
+use crate::plan::human_size;
+use crate::sparkline::sparkline;
+use crate::NginxLog;
+
+// Slices of the covered time in the overview sparklines
+const SPARK_SLICES: i64 = 24;
+
+pub struct Section {
+    pub title: &'static str,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+pub fn title(logs: &[NginxLog]) -> String {
+    let first = logs.iter().map(|log| log.request_time).min();
+    let last = logs.iter().map(|log| log.request_time).max();
+    match (first, last) {
+        (Some(first), Some(last)) if first.date_naive() == last.date_naive() => format!("Traffic report {}", first.format("%Y-%m-%d")),
+        (Some(first), Some(last)) => format!("Traffic report {} to {}", first.format("%Y-%m-%d"), last.format("%Y-%m-%d")),
+        _ => String::from("Traffic report"),
+    }
+}
+
+pub fn traffic_sections(logs: &[NginxLog], top: usize, geo: Option<(&GeoDb, GeoLevel)>) -> Vec<Section> {
+    let clients: HashSet<&str> = logs.iter().map(|log| log.remote_addr.as_str()).collect();
+    let count = |class: u16| logs.iter().filter(|log| log.status / 100 == class).count().to_string();
+    let mut overview = vec![
+        vec![String::from("Requests"), logs.len().to_string()],
+        vec![String::from("Bytes sent"), human_size(logs.iter().map(|log| log.body_bytes_sent).sum())],
+        vec![String::from("Unique clients"), clients.len().to_string()],
+        vec![String::from("4xx responses"), count(4)],
+        vec![String::from("5xx responses"), count(5)],
+    ];
+    if let Some((requests, rates)) = shape(logs) {
+        overview.push(vec![String::from("Requests over time"), requests]);
+        overview.push(vec![String::from("5xx rate over time"), rates]);
+    }
+    let mut sections = vec![Section { title: "Overview", headers: vec!["Metric", "Value"], rows: overview }];
+
+    let mut paths: HashMap<&str, u64> = HashMap::new();
+    for log in logs {
+        *paths.entry(log.path().unwrap_or("-")).or_default() += 1;
+    }
+    let mut paths: Vec<(&str, u64)> = paths.into_iter().collect();
+    paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let rows = paths.into_iter().take(top).map(|(path, count)| vec![path.to_string(), count.to_string()]).collect();
+    sections.push(Section { title: "Top paths", headers: vec!["Path", "Requests"], rows });
+
+    let rows = error_digest(logs, 500)
+        .into_iter()
+        .take(top)
+        .map(|incident| vec![incident.template, incident.upstream, incident.status.to_string(), incident.count.to_string()])
+        .collect();
+    sections.push(Section { title: "Server errors", headers: vec!["Path", "Upstream", "Status", "Count"], rows });
+
+    if let Some((db, level)) = geo {
+        let rows = geo_counts(logs, db, level)
+            .into_iter()
+            .take(top)
+            .map(|count| vec![count.country, count.city.unwrap_or_default(), count.count.to_string()])
+            .collect();
+        sections.push(Section { title: "Countries", headers: vec!["Country", "City", "Requests"], rows });
+    }
+    sections
+}
+
+// Sparklines of the requests and the 5xx rate, with the covered time cut into equal slices
+fn shape(logs: &[NginxLog]) -> Option<(String, String)> {
+    let first = logs.iter().map(|log| log.request_time).min()?;
+    let last = logs.iter().map(|log| log.request_time).max()?;
+    let span = (last - first).num_seconds() + 1;
+    let slices = span.min(SPARK_SLICES);
+    let mut requests = vec![0.0; slices as usize];
+    let mut errors = vec![0.0; slices as usize];
+    for log in logs {
+        let slice = ((log.request_time - first).num_seconds() * slices / span) as usize;
+        requests[slice] += 1.0;
+        if log.status >= 500 {
+            errors[slice] += 1.0;
+        }
+    }
+    let rates: Vec<f64> = requests.iter().zip(&errors).map(|(&count, &errors)| if count > 0.0 { errors / count * 100.0 } else { 0.0 }).collect();
+    let max_requests = requests.iter().copied().fold(0.0, f64::max);
+    let max_rate = rates.iter().copied().fold(0.0, f64::max);
+    Some((format!("{} (max {})", sparkline(&requests), max_requests), format!("{} (max {:.1}%)", sparkline(&rates), max_rate)))
+}
+
+pub fn render_markdown(title: &str, sections: &[Section]) -> String {
+    let mut out = format!("# {}\n", title);
+    for section in sections {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("None.\n");
+            continue;
+        }
+        out.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+        out.push_str(&format!("|{}\n", " --- |".repeat(section.headers.len())));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn render_html(title: &str, sections: &[Section]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(title)
+    );
+    for section in sections {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(section.title)));
+        if section.rows.is_empty() {
+            out.push_str("<p>None.</p>\n");
+            continue;
+        }
+        out.push_str("<table>\n<tr>");
+        for header in &section.headers {
+            out.push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        out.push_str("</tr>\n");
+        for row in &section.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::cve::{percent_decode, ProbeHits};
+use crate::NginxLog;
+
+pub struct Limits {
+    pub request_line: usize,
+    pub query: usize,
+}
+
+pub fn suspicious_requests(logs: &[NginxLog], limits: &Limits) -> Vec<ProbeHits> {
+    let mut hits: Vec<ProbeHits> = Vec::new();
+    for log in logs {
+        for id in findings(log, limits) {
+            let position = match hits.iter().position(|hit| hit.id == id) {
+                Some(position) => position,
+                None => {
+                    hits.push(ProbeHits { id: id.to_string(), hits: 0, non_404: 0, ips: HashMap::new(), statuses: HashMap::new() });
+                    hits.len() - 1
+                }
+            };
+            let hit = &mut hits[position];
+            hit.hits += 1;
+            if log.status != 404 {
+                hit.non_404 += 1;
+            }
+            *hit.ips.entry(log.client()).or_insert(0) += 1;
+            *hit.statuses.entry(log.status).or_insert(0) += 1;
+        }
+    }
+    hits.sort_by(|a, b| b.non_404.cmp(&a.non_404).then_with(|| b.hits.cmp(&a.hits)));
+    hits
+}
+
+fn findings(log: &NginxLog, limits: &Limits) -> Vec<&'static str> {
+    let mut findings = Vec::new();
+    if log.request.trim_matches('"').len() > limits.request_line {
+        findings.push("oversized-request-line");
+    }
+    if log.query().is_some_and(|query| query.len() > limits.query) {
+        findings.push("oversized-query");
+    }
+    let path = log.path().unwrap_or("").split('?').next().unwrap_or("");
+    let bytes = path_bytes(path);
+    if bytes.iter().any(|byte| byte.is_ascii_control()) {
+        findings.push("control-chars-in-path");
+    }
+    if bytes.iter().any(|byte| !byte.is_ascii()) {
+        findings.push("non-ascii-path");
+    }
+    findings
+}
+
+// nginx writes non-printable request bytes as \xHH; clients may also percent-encode them
+fn path_bytes(path: &str) -> Vec<u8> {
+    let decoded = percent_decode(path);
+    let bytes = decoded.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') && i + 3 < bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i + 2..i + 4]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                unescaped.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        unescaped.push(bytes[i]);
+        i += 1;
+    }
+    unescaped
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub enum TimeFormat {
+    Default,
+    Iso8601,
+    Epoch,
+    EpochMillis,
+    Strftime(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DisplayZone {
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeDisplay {
+    pub format: TimeFormat,
+    pub zone: DisplayZone,
+}
+
+impl TimeFormat {
+    pub fn parse(value: &str) -> Result<TimeFormat, String> {
+        match value {
+            "default" => Ok(TimeFormat::Default),
+            "iso8601" | "rfc3339" => Ok(TimeFormat::Iso8601),
+            "epoch" => Ok(TimeFormat::Epoch),
+            "epoch-ms" => Ok(TimeFormat::EpochMillis),
+            _ if value.contains('%') => Ok(TimeFormat::Strftime(value.to_string())),
+            _ => Err(format!("unknown time format: {} (expected default, iso8601, epoch, epoch-ms or a strftime pattern)", value)),
+        }
+    }
+}
+
+impl DisplayZone {
+    pub fn parse(value: &str) -> Result<DisplayZone, String> {
+        match value {
+            "local" => Ok(DisplayZone::Local),
+            "utc" | "UTC" | "Z" => Ok(DisplayZone::Utc),
+            _ => {
+                if let Ok(offset) = value.parse::<FixedOffset>() {
+                    return Ok(DisplayZone::Fixed(offset));
+                }
+                value
+                    .parse::<Tz>()
+                    .map(DisplayZone::Named)
+                    .map_err(|_| format!("unknown time zone: {} (expected local, utc, an offset like +02:00 or a zone name)", value))
+            }
+        }
+    }
+}
+
+impl TimeDisplay {
+    pub fn render(&self, time: DateTime<Local>) -> String {
+        match self.zone {
+            DisplayZone::Local => self.render_in(time),
+            DisplayZone::Utc => self.render_in(time.with_timezone(&Utc)),
+            DisplayZone::Fixed(offset) => self.render_in(time.with_timezone(&offset)),
+            DisplayZone::Named(zone) => self.render_in(time.with_timezone(&zone)),
+        }
+    }
+
+    pub fn json(&self, time: DateTime<Local>) -> Value {
+        match self.format {
+            TimeFormat::Epoch => Value::from(time.timestamp()),
+            TimeFormat::EpochMillis => Value::from(time.timestamp_millis()),
+            TimeFormat::Default => TimeDisplay { format: TimeFormat::Iso8601, zone: self.zone }.json(time),
+            _ => Value::from(self.render(time)),
+        }
+    }
+
+    fn render_in<Z: TimeZone>(&self, time: DateTime<Z>) -> String
+    where
+        Z::Offset: std::fmt::Display,
+    {
+        match &self.format {
+            TimeFormat::Default => time.to_string(),
+            TimeFormat::Iso8601 => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            TimeFormat::Epoch => time.timestamp().to_string(),
+            TimeFormat::EpochMillis => time.timestamp_millis().to_string(),
+            TimeFormat::Strftime(pattern) => time.format(pattern).to_string(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use crate::digest::path_template;
+use crate::NginxLog;
+
+// $request_length counts the whole request including headers; $content_length only the body
+const LENGTH_FIELDS: [&str; 3] = ["request_length", "content_length", "http_content_length"];
+
+#[derive(Default)]
+pub struct Ingress {
+    pub key: String,
+    pub requests: u64,
+    pub received: u64,
+    pub largest: u64,
+    pub sent: u64,
+}
+
+pub fn request_bytes(log: &NginxLog) -> Option<u64> {
+    LENGTH_FIELDS.iter().filter_map(|name| log.extra.get(*name)).find_map(|value| value.parse().ok())
+}
+
+pub fn ingress_by<F>(logs: &[NginxLog], key: F) -> Vec<Ingress>
+where
+    F: Fn(&NginxLog) -> String,
+{
+    let mut totals: HashMap<String, Ingress> = HashMap::new();
+    for log in logs {
+        let bytes = match request_bytes(log) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        let key = key(log);
+        let entry = totals.entry(key.clone()).or_insert_with(|| Ingress { key, ..Default::default() });
+        entry.requests += 1;
+        entry.received += bytes;
+        entry.largest = entry.largest.max(bytes);
+        entry.sent += log.body_bytes_sent;
+    }
+    let mut totals: Vec<Ingress> = totals.into_values().collect();
+    totals.sort_by(|a, b| b.received.cmp(&a.received).then_with(|| a.key.cmp(&b.key)));
+    totals
+}
+
+pub fn endpoint(log: &NginxLog) -> String {
+    format!("{} {}", log.method().unwrap_or("-"), path_template(log.path().unwrap_or("-")))
+}
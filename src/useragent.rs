@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::interval::{Alignment, Interval};
+use crate::NginxLog;
+
+const BROWSERS: &[(&str, &str)] = &[
+    ("Edge", r"Edg(?:e|A|iOS)?/(\d+)"),
+    ("Opera", r"OPR/(\d+)"),
+    ("Samsung Internet", r"SamsungBrowser/(\d+)"),
+    ("Firefox", r"(?:Firefox|FxiOS)/(\d+)"),
+    ("Chrome", r"(?:Chrome|CriOS)/(\d+)"),
+    ("Safari", r"Version/(\d+)[\d.]* (?:Mobile/\S+ )?Safari/"),
+    ("Internet Explorer", r"MSIE (\d+)|Trident/.*rv:(\d+)"),
+];
+
+const SEARCH_BOTS: &[(&str, &str)] = &[
+    ("Googlebot", "googlebot"),
+    ("Google-InspectionTool", "google-inspectiontool"),
+    ("AdsBot-Google", "adsbot-google"),
+    ("Bingbot", "bingbot"),
+    ("YandexBot", "yandex"),
+    ("Baiduspider", "baiduspider"),
+    ("DuckDuckBot", "duckduckbot"),
+    ("Applebot", "applebot"),
+    ("Yahoo Slurp", "slurp"),
+    ("SeznamBot", "seznambot"),
+    ("PetalBot", "petalbot"),
+    ("Sogou", "sogou"),
+];
+
+pub fn search_bot(user_agent: &str) -> Option<&'static str> {
+    let user_agent = user_agent.to_ascii_lowercase();
+    SEARCH_BOTS.iter().find(|(_, token)| user_agent.contains(token)).map(|(name, _)| *name)
+}
+
+pub fn is_bot(user_agent: &str) -> bool {
+    let lower = user_agent.to_ascii_lowercase();
+    search_bot(user_agent).is_some() || ["bot", "crawl", "spider", "slurp"].iter().any(|token| lower.contains(token))
+}
+
+pub struct VersionTrend {
+    pub versions: Vec<String>,
+    pub buckets: BTreeMap<DateTime<Local>, HashMap<String, u64>>,
+}
+
+fn browser_patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| BROWSERS.iter().map(|(name, pattern)| (*name, Regex::new(pattern).unwrap())).collect())
+}
+
+pub fn browser_version(user_agent: &str) -> Option<(&'static str, u32)> {
+    for (name, regex) in browser_patterns() {
+        if let Some(captures) = regex.captures(user_agent) {
+            let version = captures.iter().skip(1).flatten().next()?;
+            return Some((name, version.as_str().parse().ok()?));
+        }
+    }
+    None
+}
+
+pub fn browser_version_trend(logs: &[NginxLog], interval: Interval, align: Alignment, family: Option<&str>) -> VersionTrend {
+    let mut buckets: BTreeMap<DateTime<Local>, HashMap<String, u64>> = BTreeMap::new();
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    let origin = match logs.iter().map(|log| log.request_time).min() {
+        Some(origin) => origin,
+        None => return VersionTrend { versions: Vec::new(), buckets },
+    };
+    for log in logs {
+        let (name, major) = match browser_version(&log.http_user_agent) {
+            Some(version) => version,
+            None => continue,
+        };
+        if family.is_some_and(|family| !family.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        let version = format!("{} {}", name, major);
+        let bucket = interval.bucket_start(log.request_time, align, origin);
+        *buckets.entry(bucket).or_default().entry(version.clone()).or_insert(0) += 1;
+        *totals.entry(version).or_insert(0) += 1;
+    }
+    let mut versions: Vec<(String, u64)> = totals.into_iter().collect();
+    versions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    VersionTrend {
+        versions: versions.into_iter().map(|(version, _)| version).collect(),
+        buckets,
+    }
+}
+
+#[derive(Default)]
+pub struct FingerprintStats<'a> {
+    pub requests: u64,
+    pub clients: HashSet<&'a str>,
+    pub agents: HashMap<&'a str, u64>,
+}
+
+pub fn fingerprint_stats(logs: &[NginxLog]) -> Vec<(&str, FingerprintStats<'_>)> {
+    let mut fingerprints: HashMap<&str, FingerprintStats> = HashMap::new();
+    for log in logs {
+        if let Some(fingerprint) = log.tls_fingerprint() {
+            let stats = fingerprints.entry(fingerprint).or_default();
+            stats.requests += 1;
+            stats.clients.insert(log.remote_addr.as_str());
+            *stats.agents.entry(log.http_user_agent.trim_matches('"')).or_default() += 1;
+        }
+    }
+    let mut fingerprints: Vec<(&str, FingerprintStats)> = fingerprints.into_iter().collect();
+    fingerprints.sort_by(|a, b| b.1.requests.cmp(&a.1.requests).then_with(|| a.0.cmp(b.0)));
+    fingerprints
+}
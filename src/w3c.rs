@@ -0,0 +1,148 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+use crate::cve::percent_decode;
+use crate::NginxLog;
+
+const CLOUDFRONT_FIELDS: &str = "date time x-edge-location sc-bytes c-ip cs-method cs(Host) cs-uri-stem sc-status cs(Referer) cs(User-Agent) cs-uri-query cs(Cookie) \
+    x-edge-result-type x-edge-request-id x-host-header cs-protocol cs-bytes time-taken x-forwarded-for ssl-protocol ssl-cipher x-edge-response-result-type \
+    cs-protocol-version fle-status fle-encrypted-fields c-port time-to-first-byte x-edge-detailed-result-type sc-content-type sc-content-len sc-range-start sc-range-end";
+
+// The fields IIS logs by default in W3C format
+const IIS_FIELDS: &str = "date time s-ip cs-method cs-uri-stem cs-uri-query s-port cs-username c-ip cs(User-Agent) cs(Referer) sc-status sc-substatus sc-win32-status time-taken";
+
+// IIS columns with an nginx counterpart, renamed so reports line up across both servers
+const IIS_NAMES: [(&str, &str); 5] = [("s-ip", "server_addr"), ("s-port", "server_port"), ("s-sitename", "server_name"), ("s-computername", "server"), ("cs-bytes", "request_length")];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Profile {
+    Generic,
+    Iis,
+    CloudFront,
+}
+
+pub struct W3cFormat {
+    fields: Vec<String>,
+    profile: Profile,
+}
+
+impl W3cFormat {
+    pub fn compile(directive: &str) -> Result<W3cFormat, String> {
+        let fields: Vec<String> = directive.trim().trim_start_matches("#Fields:").split_whitespace().map(str::to_lowercase).collect();
+        if !fields.iter().any(|field| field == "date") || !fields.iter().any(|field| field == "time") {
+            return Err(format!("W3C fields need date and time: {}", fields.join(" ")));
+        }
+        if !fields.iter().any(|field| field == "cs-uri-stem" || field == "cs-uri") {
+            return Err(format!("W3C fields need cs-uri-stem or cs-uri: {}", fields.join(" ")));
+        }
+        Ok(W3cFormat { fields, profile: Profile::Generic })
+    }
+
+    pub fn cloudfront(directive: Option<&str>) -> Result<W3cFormat, String> {
+        let format = W3cFormat::compile(directive.unwrap_or(CLOUDFRONT_FIELDS))?;
+        Ok(W3cFormat { profile: Profile::CloudFront, ..format })
+    }
+
+    pub fn iis(directive: Option<&str>) -> Result<W3cFormat, String> {
+        let format = W3cFormat::compile(directive.unwrap_or(IIS_FIELDS))?;
+        Ok(W3cFormat { profile: Profile::Iis, ..format })
+    }
+
+    // The same profile with the columns of a #Fields: directive found in the input
+    pub fn with_fields(&self, directive: &str) -> Result<W3cFormat, String> {
+        Ok(W3cFormat { profile: self.profile, ..W3cFormat::compile(directive)? })
+    }
+
+    pub fn parse_line(&self, line: &str) -> Option<NginxLog> {
+        let values = split_fields(line);
+        let cloudfront = self.profile == Profile::CloudFront;
+        // CloudFront appends fields over time, so older files log a prefix of the current list
+        if values.len() > self.fields.len() || (values.len() < self.fields.len() && !cloudfront) {
+            return None;
+        }
+        let mut log = NginxLog { remote_addr: String::from("-"), remote_user: String::from("-"), http_referer: String::from("-"), http_user_agent: String::from("-"), ..Default::default() };
+        let (mut date, mut time, mut method, mut path, mut query, mut protocol) = (None, None, None, None, None, None);
+        for (field, value) in self.fields.iter().zip(values) {
+            if value == "-" {
+                continue;
+            }
+            match field.as_str() {
+                "date" => date = Some(NaiveDate::parse_from_str(&value, "%Y-%m-%d").ok()?),
+                "time" => time = Some(NaiveTime::parse_from_str(&value, "%H:%M:%S%.f").ok()?),
+                "c-ip" => log.remote_addr = value,
+                "cs-username" => log.remote_user = value,
+                "cs-method" => method = Some(value),
+                "cs-uri-stem" | "cs-uri" => path = Some(value),
+                "cs-uri-query" => query = Some(value),
+                "cs-version" | "cs-protocol-version" => protocol = Some(value),
+                "sc-status" => log.status = value.parse().ok()?,
+                "sc-bytes" => log.body_bytes_sent = value.parse().ok()?,
+                "cs(referer)" if cloudfront => log.http_referer = percent_decode(&value),
+                "cs(referer)" | "cs(referrer)" => log.http_referer = value,
+                "cs(user-agent)" if cloudfront => log.http_user_agent = percent_decode(&value),
+                "cs(user-agent)" => log.http_user_agent = value.replace('+', " "),
+                "cs-host" | "cs(host)" | "x-host-header" => {
+                    log.extra.insert(String::from("host"), value);
+                }
+                "time-taken" => {
+                    log.extra.insert(String::from("request_time"), time_taken(&value)?);
+                }
+                "x-request-id" | "x-edge-request-id" => log.request_id = Some(value),
+                "x-forwarded-for" => {
+                    log.extra.insert(String::from("http_x_forwarded_for"), value);
+                }
+                _ => {
+                    let renamed = IIS_NAMES.iter().find(|(iis, _)| self.profile == Profile::Iis && iis == field).map(|(_, name)| name.to_string());
+                    log.extra.insert(renamed.unwrap_or_else(|| field_name(field)), value);
+                }
+            }
+        }
+        log.request_time = Utc.from_utc_datetime(&NaiveDateTime::new(date?, time?)).with_timezone(&Local);
+        let target = match query {
+            Some(query) => format!("{}?{}", path?, query),
+            None => path?,
+        };
+        log.request = format!("{} {} {}", method.as_deref().unwrap_or("GET"), target, protocol.as_deref().unwrap_or("HTTP/1.1"));
+        Some(log)
+    }
+}
+
+fn field_name(field: &str) -> String {
+    field.trim_start_matches("cs(").trim_start_matches("sc(").trim_end_matches(')').replace('-', "_")
+}
+
+// IIS logs time-taken in milliseconds, CDN exports in fractional seconds
+fn time_taken(value: &str) -> Option<String> {
+    if value.contains('.') {
+        value.parse::<f64>().ok().map(|seconds| format!("{:.3}", seconds))
+    } else {
+        value.parse::<u64>().ok().map(|millis| format!("{:.3}", millis as f64 / 1000.0))
+    }
+}
+
+pub fn split_fields(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let (mut quoted, mut started) = (false, false);
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    values.push(std::mem::take(&mut value));
+                    started = false;
+                }
+            }
+            c => {
+                value.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        values.push(value);
+    }
+    values
+}